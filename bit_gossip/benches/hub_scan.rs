@@ -0,0 +1,103 @@
+//! Cost of `neighbor_to` from the hubs of star-like graphs, with and without hub summaries.
+//!
+//! Without a summary, a query from a hub checks the destination's bit of each of its edges in turn;
+//! with one, it only checks the edges whose word of destination bits is non-zero.
+//!
+//! Run with `cargo bench --bench hub_scan`.
+
+use bit_gossip::graph::{parallel::ParaGraph, sequential::SeqGraph};
+use std::time::Instant;
+
+const ROUNDS: usize = 20;
+
+/// Minimum degree of a summarized hub.
+const MIN_DEGREE: usize = 64;
+
+/// Time a query from every hub to every node, and return the time per query
+/// with the sum of the next hops, so that the queries can't be optimized away.
+macro_rules! time {
+    ($graph:expr, $hubs:expr) => {{
+        let nodes_len = $graph.nodes_len() as u16;
+
+        let now = Instant::now();
+        let mut sum = 0usize;
+        for _ in 0..ROUNDS {
+            for &hub in &$hubs {
+                for dest in 0..nodes_len {
+                    sum += $graph.neighbor_to(hub, dest).map_or(0, usize::from);
+                }
+            }
+        }
+        let elapsed = now.elapsed() / (ROUNDS * $hubs.len() * nodes_len as usize) as u32;
+        (elapsed, sum)
+    }};
+}
+
+/// Time the queries of `time!` without and with a hub summary, and print both.
+macro_rules! bench {
+    ($name:expr, $graph:expr, $hubs:expr) => {{
+        let mut graph = $graph;
+
+        let (plain, plain_sum) = time!(graph, $hubs);
+        graph.summarize_hubs(MIN_DEGREE);
+        let (summarized, summarized_sum) = time!(graph, $hubs);
+
+        assert_eq!(plain_sum, summarized_sum);
+
+        println!(
+            "{:<36} plain: {:>9.2?}/query   summarized: {:>9.2?}/query   ({:.2}x)",
+            $name,
+            plain,
+            summarized,
+            plain.as_secs_f64() / summarized.as_secs_f64()
+        );
+    }};
+}
+
+/// A center node `0` with `leaves` leaves.
+fn star(leaves: u16) -> Vec<(u16, u16)> {
+    (1..=leaves).map(|leaf| (0, leaf)).collect()
+}
+
+/// A center node `0` with `hubs` hubs, each with `leaves` leaves.
+fn star_of_stars(hubs: u16, leaves: u16) -> Vec<(u16, u16)> {
+    let mut edges = Vec::new();
+    for hub in 1..=hubs {
+        edges.push((0, hub));
+        for leaf in 0..leaves {
+            edges.push((hub, 1 + hubs + (hub - 1) * leaves + leaf));
+        }
+    }
+    edges
+}
+
+fn main() {
+    let graphs = [
+        ("star, 2000 leaves", star(2000), vec![0]),
+        (
+            "star of 20 stars of 100",
+            star_of_stars(20, 100),
+            (0..=20).collect::<Vec<_>>(),
+        ),
+    ];
+
+    for (name, edges, hubs) in graphs {
+        let nodes_len = edges
+            .iter()
+            .map(|&(a, b)| a.max(b) as usize + 1)
+            .max()
+            .unwrap();
+
+        let mut builder = SeqGraph::<u16>::builder(nodes_len);
+        for &(a, b) in &edges {
+            builder.connect(a, b);
+        }
+        bench!(format!("SeqGraph, {name}"), builder.build(), hubs);
+
+        let mut builder = ParaGraph::<u16>::builder(nodes_len);
+        for &(a, b) in &edges {
+            builder.connect(a, b);
+        }
+        bench!(format!("ParaGraph, {name}"), builder.build(), hubs);
+    }
+}
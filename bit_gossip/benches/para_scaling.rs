@@ -0,0 +1,62 @@
+//! Build time of [ParaGraph] on thread pools of increasing size.
+//!
+//! Each round of gossip merges the done and active masks of every worker once,
+//! instead of every worker writing to shared atomic masks;
+//! this shows how the build scales with the number of threads on the machine it runs on.
+//!
+//! Run with `cargo bench --bench para_scaling`.
+//!
+//! TODO: record the results on a machine with 16 or more cores in the README;
+//! so far the bench has only been run on a single core.
+
+use bit_gossip::{graph::parallel::ParaGraph, maze::build_maze_from_seed};
+use std::time::{Duration, Instant};
+
+const ROUNDS: u32 = 3;
+
+fn main() {
+    let (width, height) = (48, 48);
+    let edges = build_maze_from_seed(width, height, [7; 32]);
+    let nodes_len = (width * height) as usize;
+
+    let max_threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let threads = (0..)
+        .map(|i| 1 << i)
+        .take_while(|&n| n < max_threads)
+        .chain([max_threads]);
+
+    println!("{width}x{height} maze, up to {max_threads} threads\n");
+
+    let mut single = Duration::ZERO;
+    for num_threads in threads {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .build()
+            .unwrap();
+
+        // the edges are counted, so that the build can't be optimized away
+        let now = Instant::now();
+        let mut edges_len = 0;
+        for _ in 0..ROUNDS {
+            let mut builder = ParaGraph::<u16>::builder(nodes_len);
+            for &(a, b) in &edges {
+                builder.connect(a, b);
+            }
+            edges_len += pool.install(|| builder.build()).edges_len();
+        }
+        let elapsed = now.elapsed() / ROUNDS;
+
+        assert_eq!(edges_len, edges.len() * ROUNDS as usize);
+
+        if num_threads == 1 {
+            single = elapsed;
+        }
+
+        println!(
+            "{:>3} threads: {:>9.2?}/build   ({:.2}x)",
+            num_threads,
+            elapsed,
+            single.as_secs_f64() / elapsed.as_secs_f64()
+        );
+    }
+}
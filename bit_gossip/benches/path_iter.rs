@@ -0,0 +1,101 @@
+//! Per-path cost of `path_to` against stepping with `neighbor_to`, on paths of 100+ hops.
+//!
+//! `path_to` resolves the destination's bit once for the whole path,
+//! and skips the edge back to the previous node at every step.
+//!
+//! Run with `cargo bench --bench path_iter`.
+
+use bit_gossip::{
+    graph::{parallel::ParaGraph, sequential::SeqGraph},
+    maze::build_maze_from_seed,
+    Graph128,
+};
+use std::time::Instant;
+
+const ROUNDS: usize = 200;
+
+/// Time walking every pair with `path_to`, and with `neighbor_to` in a loop,
+/// and print the time per path of both.
+macro_rules! bench {
+    ($name:expr, $graph:expr, $pairs:expr) => {{
+        let graph = &$graph;
+        let pairs = &$pairs;
+
+        // the path lengths are summed and compared, so that the walks can't be optimized away
+        let now = Instant::now();
+        let mut path_to_len = 0;
+        for _ in 0..ROUNDS {
+            for &(curr, dest) in pairs {
+                path_to_len += graph.path_to(curr, dest).count();
+            }
+        }
+        let path_to = now.elapsed() / (ROUNDS * pairs.len()) as u32;
+
+        let now = Instant::now();
+        let mut neighbor_to_len = 0;
+        for _ in 0..ROUNDS {
+            for &(mut curr, dest) in pairs {
+                neighbor_to_len += 1;
+                while let Some(next) = graph.neighbor_to(curr, dest) {
+                    curr = next;
+                    neighbor_to_len += 1;
+                }
+            }
+        }
+        let neighbor_to = now.elapsed() / (ROUNDS * pairs.len()) as u32;
+
+        assert_eq!(path_to_len, neighbor_to_len);
+
+        println!(
+            "{:<10} path_to: {:>9.2?}/path   neighbor_to loop: {:>9.2?}/path   ({:.2}x)",
+            $name,
+            path_to,
+            neighbor_to,
+            neighbor_to.as_secs_f64() / path_to.as_secs_f64()
+        );
+    }};
+}
+
+fn main() {
+    // a maze has long winding paths; keep the pairs that are at least 100 hops apart
+    let (width, height) = (48, 48);
+    let edges = build_maze_from_seed(width, height, [7; 32]);
+    let nodes_len = (width * height) as usize;
+
+    let mut builder = SeqGraph::<u16>::builder(nodes_len);
+    for &(a, b) in &edges {
+        builder.connect(a, b);
+    }
+    let seq = builder.build();
+
+    let mut builder = ParaGraph::<u16>::builder(nodes_len);
+    for &(a, b) in &edges {
+        builder.connect(a, b);
+    }
+    let par = builder.build();
+
+    let pairs = (0..nodes_len as u16)
+        .step_by(37)
+        .flat_map(|a| (0..nodes_len as u16).step_by(41).map(move |b| (a, b)))
+        .filter(|&(a, b)| seq.path_to(a, b).count() > 100)
+        .collect::<Vec<_>>();
+    let hops = pairs
+        .iter()
+        .map(|&(a, b)| seq.path_to(a, b).count() - 1)
+        .sum::<usize>()
+        / pairs.len();
+    println!("{} pairs of {} hops on average\n", pairs.len(), hops);
+
+    bench!("SeqGraph", seq, pairs);
+    bench!("ParaGraph", par, pairs);
+
+    // a snake through all 128 nodes, which is the longest path a Graph128 can have
+    let mut builder = Graph128::builder(128);
+    for i in 0..127 {
+        builder.connect(i, i + 1);
+    }
+    let graph = builder.build();
+    let pairs = (0..8).map(|i| (i, 127 - i)).collect::<Vec<_>>();
+
+    bench!("Graph128", graph, pairs);
+}
@@ -145,11 +145,26 @@ impl AtomicBitVec {
     }
 
     /// Truncate the size of the bitvec to the given length of bits.
+    ///
+    /// Does nothing if the bitvec is already shorter than the given length.
     pub fn truncate(&mut self, bit_len: usize) {
         let (i, j) = (bit_len / BITS, bit_len % BITS);
         self.0.truncate(i + (j > 0) as usize);
-        if j > 0 {
-            self.0[i].fetch_and(Digit::MAX >> (BITS - j), Relaxed);
+        if let (true, Some(digit)) = (j > 0, self.0.get(i)) {
+            digit.fetch_and(Digit::MAX >> (BITS - j), Relaxed);
+        }
+    }
+
+    /// Resize the bitvec to hold the given length of bits.
+    ///
+    /// Growing fills the new bits with zeros; shrinking is the same as [truncate](Self::truncate).
+    pub fn resize(&mut self, bit_len: usize) {
+        let len = bit_len / BITS + (bit_len % BITS > 0) as usize;
+
+        if len > self.0.len() {
+            self.0.resize_with(len, || AtomicDigit::new(0));
+        } else {
+            self.truncate(bit_len);
         }
     }
 }
@@ -188,6 +203,17 @@ impl AtomicBitVec {
     }
 }
 
+impl Clone for AtomicBitVec {
+    fn clone(&self) -> Self {
+        Self(
+            self.0
+                .iter()
+                .map(|a| AtomicDigit::new(a.load(Relaxed)))
+                .collect(),
+        )
+    }
+}
+
 impl fmt::Debug for AtomicBitVec {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "AtomicBitVec(")?;
@@ -1,7 +1,7 @@
-use super::{
-    digit::{Digit, BITS},
-    AtomicBitVec,
-};
+use super::digit::{Digit, BITS};
+#[cfg(feature = "parallel")]
+use super::AtomicBitVec;
+use crate::graph::stats::record_bitvec_grow;
 use std::{fmt, iter::repeat};
 
 /// An array of digits to work with underlying bits.
@@ -16,6 +16,21 @@ use std::{fmt, iter::repeat};
 /// - lack the convenience methods I need.
 ///
 /// This data structure is very bare with the absolute minimum functionalities implemented.
+///
+/// # Layout
+///
+/// The layout of the digits is stable, and can be relied on for custom serialization or GPU uploads
+/// through [as_raw_digits](Self::as_raw_digits) and [from_raw_digits](Self::from_raw_digits):
+/// - bit `i` is bit `i % BITS` of digit `i / BITS`, counting from the least significant bit;
+/// - digits are [Digit], `u64` on 64-bit targets and `u32` otherwise, with [BITS](Self::DIGIT_BITS) bits each;
+/// - trailing zero digits are trimmed, so a zero bit vector has no digits.
+///
+/// This layout only changes in a semver-breaking release.
+///
+/// The digits are a public field, so the trimming only holds for bit vectors
+/// built and modified through the methods of this type;
+/// code that writes to the field directly must trim them itself,
+/// or methods like [is_zero](Self::is_zero) and [eq](Self::eq) may give wrong answers.
 #[derive(Clone)]
 pub struct BitVec(pub Vec<Digit>);
 
@@ -23,6 +38,38 @@ impl BitVec {
     /// Initialize with empty vector.
     pub const ZERO: Self = Self(Vec::new());
 
+    /// Number of bits in each [Digit] on this target.
+    pub const DIGIT_BITS: usize = BITS;
+
+    /// Return the raw digits of the bit vector, in the stable layout described on [BitVec].
+    #[inline]
+    pub fn as_raw_digits(&self) -> &[Digit] {
+        &self.0
+    }
+
+    /// Create a bit vector from raw digits in the stable layout described on [BitVec].
+    ///
+    /// Trailing zero digits are trimmed.
+    #[inline]
+    pub fn from_raw_digits(digits: Vec<Digit>) -> Self {
+        let mut res = Self(digits);
+        res.normalize();
+        res
+    }
+
+    /// Iterate over the non-zero digits of the bit vector,
+    /// along with the index of the first bit of each digit.
+    ///
+    /// This skips runs of zero bits a whole digit at a time, which is handy for sparse copies.
+    #[inline]
+    pub fn iter_digits(&self) -> impl Iterator<Item = (usize, Digit)> + '_ {
+        self.0
+            .iter()
+            .enumerate()
+            .filter(|(_, &digit)| digit != 0)
+            .map(|(i, &digit)| (i * BITS, digit))
+    }
+
     /// Initialize with a `true` bit at the given bit index.
     #[inline]
     pub fn one(bit_index: usize) -> Self {
@@ -59,7 +106,9 @@ impl BitVec {
 
         // if setting value to 1, we might need to resize the array
         if value && i >= self.0.len() {
+            let capacity = self.0.capacity();
             self.0.resize(i + 1, 0);
+            record_bitvec_grow(capacity, self.0.capacity());
         }
 
         if value {
@@ -144,6 +193,9 @@ impl BitVec {
                 *last &= Digit::MAX >> (BITS - j);
             }
         }
+
+        // the cut may leave zero digits at the end
+        self.normalize();
     }
 }
 
@@ -173,6 +225,7 @@ impl BitVec {
     /// a = a & !b
     ///
     /// Takes an `AtomicBitVec` as the right-hand side.
+    #[cfg(feature = "parallel")]
     pub fn bitand_not_assign_atomic(&mut self, rhs: &AtomicBitVec) {
         if self.is_zero() {
             return;
@@ -195,8 +248,11 @@ impl BitVec {
             return;
         }
 
+        let capacity = self.0.capacity();
+
         if self.is_zero() {
-            self.0 = rhs.0.clone();
+            self.0.clone_from(&rhs.0);
+            record_bitvec_grow(capacity, self.0.capacity());
             return;
         }
 
@@ -206,6 +262,7 @@ impl BitVec {
 
         if self.0.len() < rhs.0.len() {
             self.0.extend_from_slice(&rhs.0[self.0.len()..]);
+            record_bitvec_grow(capacity, self.0.capacity());
         }
     }
 
@@ -223,7 +280,9 @@ impl BitVec {
 
         let rhs_len = rhs1.0.len().min(rhs2.0.len());
         if self.0.len() < rhs_len {
+            let capacity = self.0.capacity();
             self.0.reserve_exact(rhs_len - self.0.len());
+            record_bitvec_grow(capacity, self.0.capacity());
             for (b, c) in rhs1.0.iter().zip(rhs2.0.iter()).skip(self.0.len()) {
                 self.0.push(b & c);
             }
@@ -249,7 +308,9 @@ impl BitVec {
         }
 
         if rhs2.0.len() > self.0.len() {
+            let capacity = self.0.capacity();
             self.0.reserve_exact(rhs2.0.len() - self.0.len());
+            record_bitvec_grow(capacity, self.0.capacity());
 
             for (b, c) in bc {
                 self.0.push(!b & c);
@@ -276,6 +337,7 @@ impl fmt::Debug for BitVec {
 
 /// Iterates over each Digit element in the array,
 /// and then iterates over each bit in the Digit element.
+#[derive(Debug, Clone)]
 pub struct IterOnes<'a> {
     data: &'a BitVec,
     array_index: usize,
@@ -350,6 +412,50 @@ impl<'a> Iterator for IterZeros<'a> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_raw_digits() {
+        let mut bv = BitVec::ZERO;
+        for i in [1, BITS + 2, 3 * BITS] {
+            bv.set_bit(i, true);
+        }
+
+        assert_eq!(bv.as_raw_digits(), &[0b10, 0b100, 0, 1]);
+        assert_eq!(
+            bv.iter_digits().collect::<Vec<_>>(),
+            vec![(0, 0b10), (BITS, 0b100), (3 * BITS, 1)]
+        );
+
+        let copy = BitVec::from_raw_digits(bv.as_raw_digits().to_vec());
+        assert_eq!(
+            copy.iter_ones().collect::<Vec<_>>(),
+            vec![1, BITS + 2, 3 * BITS]
+        );
+
+        // trailing zero digits are trimmed
+        let bv = BitVec::from_raw_digits(vec![0b1, 0, 0]);
+        assert_eq!(bv.as_raw_digits(), &[1]);
+        assert!(BitVec::from_raw_digits(vec![0; 4]).is_zero());
+    }
+
+    #[test]
+    fn test_truncate() {
+        let mut bv = BitVec::ZERO;
+        bv.set_bit(100, true);
+        bv.truncate(10);
+
+        assert!(bv.is_zero());
+        assert!(bv.eq(&BitVec::ZERO));
+        assert!(bv.as_raw_digits().is_empty());
+
+        let mut bv = BitVec::ZERO;
+        bv.set_bit(3, true);
+        bv.set_bit(2 * BITS + 1, true);
+        bv.truncate(BITS + 5);
+
+        assert_eq!(bv.as_raw_digits(), &[0b1000]);
+        assert_eq!(bv.iter_ones().collect::<Vec<_>>(), vec![3]);
+    }
+
     #[test]
     fn test_iter_zeros() {
         let mut bv = BitVec::ZERO;
@@ -6,7 +6,9 @@ mod atomic_bitvec;
 pub use atomic_bitvec::AtomicBitVec;
 
 mod bitvec;
-pub use bitvec::BitVec;
+pub use bitvec::{BitVec, IterOnes};
+
+pub use digit::{Digit, BITS};
 
 mod digit {
     macro_rules! cfg_32 {
@@ -33,6 +35,7 @@ mod digit {
         pub type Digit = u64;
     }
 
+    #[cfg(feature = "parallel")]
     cfg_digit! {
         pub type AtomicDigit = std::sync::atomic::AtomicU32;
         pub type AtomicDigit = std::sync::atomic::AtomicU64;
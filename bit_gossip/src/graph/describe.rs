@@ -0,0 +1,193 @@
+//! JSON summary of a built graph, for editors and launchers that display navigation-asset metadata.
+//!
+//! Enabled with the `serde` feature.
+
+use super::{store::EdgeStore, Backend, Graph, U16orU32};
+use crate::bitvec::Digit;
+use serde_json::{json, Value};
+use std::mem::size_of;
+
+/// Version of the [Graph::describe] schema.
+///
+/// Bumped only when a field is removed or changes meaning; new fields may be added at any time.
+pub const DESCRIBE_SCHEMA_VERSION: u32 = 1;
+
+impl<NodeId: U16orU32, S: EdgeStore<NodeId>> Graph<NodeId, S> {
+    /// Return a JSON summary of the graph in a stable schema,
+    /// so external tools can show navigation-asset metadata without linking against this crate.
+    ///
+    /// ```json
+    /// {
+    ///   "schema_version": 1,
+    ///   "nodes": 4,
+    ///   "edges": 2,
+    ///   "components": 2,
+    ///   "topology_hash": 1234567890,
+    ///   "memory": { "edge_bits": 32, "neighbors": 24, "total": 56 },
+    ///   "backend": "sequential",
+    ///   "build": {
+    ///     "node_id_bits": 16,
+    ///     "parallel_feature": true,
+    ///     "retained_dests": null,
+    ///     "hub_min_degree": null
+    ///   }
+    /// }
+    /// ```
+    ///
+    /// - `components` counts connected components, isolated nodes included.
+    /// - `memory` is an estimate in bytes of the heap used by the edges' bits and the neighbor lists;
+    ///   data shared with clones or forks is counted in full.
+    /// - `build.retained_dests` is the number of destinations kept by
+    ///   [retain_dests](Self::retain_dests), or `null` if the graph has every destination.
+    /// - `build.hub_min_degree` is the minimum degree of the nodes summarized by
+    ///   [summarize_hubs](Self::summarize_hubs), or `null` if hubs are not summarized.
+    /// - The builder's `stable_order` and `parallel_setup` settings are not included:
+    ///   they only change how a build is scheduled, and the built graph is the same either way.
+    ///
+    /// ```
+    /// use bit_gossip::Graph;
+    ///
+    /// let mut builder = Graph::builder(4);
+    /// builder.connect(0u16, 1);
+    /// builder.connect(2, 3);
+    /// let graph = builder.build();
+    ///
+    /// let description = graph.describe();
+    /// assert_eq!(description["nodes"], 4);
+    /// assert_eq!(description["edges"], 2);
+    /// assert_eq!(description["components"], 2);
+    /// ```
+    pub fn describe(&self) -> Value {
+        let (edge_bits, retained_dests) = match self {
+            Graph::Sequential(graph) => (
+                graph
+                    .edges
+                    .iter()
+                    .map(|(_, bits)| bits.0.len() * size_of::<Digit>())
+                    .sum::<usize>(),
+                graph.dests.as_ref().map(|dests| dests.len()),
+            ),
+            #[cfg(feature = "parallel")]
+            Graph::Parallel(graph) => (
+                graph
+                    .edges
+                    .values()
+                    .map(|bits| bits.0.len() * size_of::<Digit>())
+                    .sum::<usize>(),
+                graph.dests.as_ref().map(|dests| dests.len()),
+            ),
+        };
+
+        let neighbors = (0..self.nodes_len())
+            .map(|node| {
+                // neighbor ids and their edge data
+                self.neighbors(NodeId::from_usize(node)).len()
+                    * (size_of::<NodeId>() + size_of::<u16>())
+            })
+            .sum::<usize>();
+
+        let hub_min_degree = self.hubs().map(|hubs| hubs.min_degree());
+
+        let backend = match self.backend() {
+            Backend::Sequential => "sequential",
+            Backend::Parallel => "parallel",
+        };
+
+        json!({
+            "schema_version": DESCRIBE_SCHEMA_VERSION,
+            "nodes": self.nodes_len(),
+            "edges": self.edges_len(),
+            "components": self.components_len(),
+            "topology_hash": self.topology_hash(),
+            "memory": {
+                "edge_bits": edge_bits,
+                "neighbors": neighbors,
+                "total": edge_bits + neighbors,
+            },
+            "backend": backend,
+            "build": {
+                "node_id_bits": size_of::<NodeId>() * 8,
+                "parallel_feature": cfg!(feature = "parallel"),
+                "retained_dests": retained_dests,
+                "hub_min_degree": hub_min_degree,
+            },
+        })
+    }
+
+    /// Return the number of connected components, isolated nodes included.
+    fn components_len(&self) -> usize {
+        let nodes_len = self.nodes_len();
+        let mut visited = vec![false; nodes_len];
+        let mut stack = Vec::new();
+        let mut components = 0;
+
+        for start in 0..nodes_len {
+            if visited[start] {
+                continue;
+            }
+            components += 1;
+            visited[start] = true;
+            stack.push(start);
+
+            while let Some(node) = stack.pop() {
+                for &neighbor in self.neighbors(NodeId::from_usize(node)) {
+                    let neighbor = neighbor.as_usize();
+                    if !visited[neighbor] {
+                        visited[neighbor] = true;
+                        stack.push(neighbor);
+                    }
+                }
+            }
+        }
+
+        components
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe() {
+        for multi_threaded in [false, true] {
+            let builder = Graph::<u16>::builder(6);
+
+            #[cfg(feature = "parallel")]
+            let builder = builder.multi_threaded(multi_threaded);
+
+            let mut builder = builder;
+            builder.connect(0, 1);
+            builder.connect(1, 2);
+            builder.connect(3, 4);
+            let mut graph = builder.build();
+
+            let description = graph.describe();
+            assert_eq!(description["schema_version"], DESCRIBE_SCHEMA_VERSION);
+            assert_eq!(description["nodes"], 6);
+            assert_eq!(description["edges"], 3);
+            assert_eq!(description["components"], 3);
+            assert_eq!(description["topology_hash"], graph.topology_hash());
+            assert_eq!(description["memory"]["neighbors"], 6 * 4);
+            assert!(description["memory"]["edge_bits"].as_u64().unwrap() > 0);
+            assert_eq!(description["build"]["node_id_bits"], 16);
+            assert!(description["build"]["retained_dests"].is_null());
+            assert!(description["build"]["hub_min_degree"].is_null());
+
+            let backend = if cfg!(feature = "parallel") && multi_threaded {
+                "parallel"
+            } else {
+                "sequential"
+            };
+            assert_eq!(description["backend"], backend);
+
+            let mut dests = crate::bitvec::BitVec::ZERO;
+            dests.set_bit(2, true);
+            graph.retain_dests(&dests);
+            assert_eq!(graph.describe()["build"]["retained_dests"], 1);
+
+            graph.summarize_hubs(2);
+            assert_eq!(graph.describe()["build"]["hub_min_degree"], 2);
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! destination interest sets, used by `retain_dests` to shrink a graph down to a few destinations.
+//!
+//! A built graph keeps one bit per destination node in each edge.
+//! When only a few destinations matter to a subsystem (e.g. objective nodes for a strategic AI),
+//! the bits of all other destinations can be dropped, and the retained ones packed into columns.
+//! [DestColumns] maps a destination node to its column in the packed bits.
+
+use crate::bitvec::{BitVec, Digit, BITS};
+
+/// Map of retained destination nodes to their columns in the packed bit vectors.
+///
+/// The column of a destination is the number of retained destinations before it,
+/// found with a prefix sum of the set bits of each word.
+#[derive(Debug, Clone)]
+pub struct DestColumns {
+    /// retained destination nodes
+    retained: BitVec,
+    /// number of retained destinations before each word of `retained`
+    ranks: Vec<usize>,
+}
+
+impl DestColumns {
+    /// Create the columns for the given set of destinations.
+    pub fn new(retained: BitVec) -> Self {
+        let mut ranks = Vec::with_capacity(retained.0.len());
+        let mut rank = 0;
+
+        for word in &retained.0 {
+            ranks.push(rank);
+            rank += word.count_ones() as usize;
+        }
+
+        Self { retained, ranks }
+    }
+
+    /// Return the column of the given destination node,
+    /// or `None` if the destination is not retained.
+    #[inline]
+    pub fn column(&self, dest: usize) -> Option<usize> {
+        let (i, j) = (dest / BITS, dest % BITS);
+        let word = *self.retained.0.get(i)?;
+
+        if word & (1 << j) == 0 {
+            return None;
+        }
+
+        let below = word & ((1 as Digit) << j).wrapping_sub(1);
+        Some(self.ranks[i] + below.count_ones() as usize)
+    }
+
+    /// Return the retained destination nodes.
+    #[inline]
+    pub fn retained(&self) -> &BitVec {
+        &self.retained
+    }
+
+    /// Return the number of retained destination nodes.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.ranks.last().map_or(0, |&rank| {
+            rank + self.retained.0.last().unwrap().count_ones() as usize
+        })
+    }
+
+    /// Check if no destination nodes are retained.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.retained.is_zero()
+    }
+
+    /// Narrow down the retained destinations of a graph to `dests`.
+    ///
+    /// `prev` is the graph's current columns, or `None` if it still has a bit for every node.
+    pub(crate) fn narrow(prev: Option<&DestColumns>, dests: &BitVec) -> Self {
+        let mut retained = dests.clone();
+
+        if let Some(prev) = prev {
+            retained.0.truncate(prev.retained.0.len());
+            for (word, prev) in retained.0.iter_mut().zip(&prev.retained.0) {
+                *word &= prev;
+            }
+            while retained.0.last() == Some(&0) {
+                retained.0.pop();
+            }
+        }
+
+        Self::new(retained)
+    }
+
+    /// Pack an edge's bits, laid out in the `prev` columns, into these columns.
+    pub(crate) fn pack(&self, prev: Option<&DestColumns>, bits: &BitVec) -> BitVec {
+        let mut packed = BitVec::ZERO;
+
+        for (column, dest) in self.retained.iter_ones().enumerate() {
+            let index = match prev {
+                Some(prev) => prev.column(dest),
+                None => Some(dest),
+            };
+
+            if index.map_or(false, |i| bits.get_bit(i)) {
+                packed.set_bit(column, true);
+            }
+        }
+
+        packed
+    }
+}
+
+/// Return the destinations that are in both `a` and `b`.
+pub(crate) fn intersect(a: &BitVec, b: &BitVec) -> BitVec {
+    BitVec::from_raw_digits(a.0.iter().zip(&b.0).map(|(a, b)| a & b).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_columns() {
+        let mut dests = BitVec::ZERO;
+        for dest in [3, 70, 71, 200] {
+            dests.set_bit(dest, true);
+        }
+
+        let columns = DestColumns::new(dests.clone());
+        assert_eq!(columns.len(), 4);
+        assert_eq!(columns.column(3), Some(0));
+        assert_eq!(columns.column(70), Some(1));
+        assert_eq!(columns.column(71), Some(2));
+        assert_eq!(columns.column(200), Some(3));
+        assert_eq!(columns.column(4), None);
+        assert_eq!(columns.column(1000), None);
+
+        // narrowing again keeps only the intersection, packed from the previous columns
+        let mut bits = BitVec::ZERO;
+        bits.set_bit(columns.column(71).unwrap(), true);
+        bits.set_bit(columns.column(200).unwrap(), true);
+
+        let mut dests = BitVec::ZERO;
+        for dest in [5, 71, 200] {
+            dests.set_bit(dest, true);
+        }
+
+        let narrowed = DestColumns::narrow(Some(&columns), &dests);
+        assert_eq!(narrowed.len(), 2);
+        assert_eq!(narrowed.column(5), None);
+
+        let packed = narrowed.pack(Some(&columns), &bits);
+        assert!(packed.get_bit(narrowed.column(71).unwrap()));
+        assert!(packed.get_bit(narrowed.column(200).unwrap()));
+    }
+}
@@ -0,0 +1,157 @@
+//! Hot-swappable graph for engine integrations that rebuild the graph in the background
+//! and swap it in mid-frame.
+//!
+//! [ArcGraph] holds the current graph; systems pin a snapshot of it with [ArcGraph::guard]
+//! for the duration of a frame or system, and borrow neighbor slices from that [NeighborsGuard].
+//! Swapping in a new graph never invalidates a guard: it keeps its snapshot until dropped.
+//!
+//! ```
+//! use bit_gossip::{graph::hot_swap::ArcGraph, Graph};
+//!
+//! let mut builder = Graph::builder(3);
+//! builder.connect(0u16, 1);
+//! builder.connect(1, 2);
+//! let graph = ArcGraph::new(builder.build());
+//!
+//! // start of frame
+//! let guard = graph.guard();
+//! let neighbors = guard.neighbors(1);
+//!
+//! // the graph is rebuilt and swapped in mid-frame
+//! let mut builder = graph.load().fork().into_builder();
+//! builder.disconnect(1, 2);
+//! graph.swap(builder.build());
+//!
+//! // this frame still sees the graph it started with
+//! assert_eq!(neighbors, &[0, 2]);
+//! assert_eq!(guard.neighbor_to(0, 2), Some(1));
+//!
+//! // the next frame sees the new graph
+//! assert_eq!(graph.guard().neighbors(1), &[0]);
+//! ```
+
+use super::{Graph, U16orU32};
+use std::{
+    ops::Deref,
+    sync::{Arc, PoisonError, RwLock},
+};
+
+/// A graph that can be replaced while other threads are reading it.
+///
+/// Readers take a [NeighborsGuard] that pins the graph at the time it was taken;
+/// [swap](Self::swap) replaces the graph for guards taken afterwards.
+#[derive(Debug)]
+pub struct ArcGraph<NodeId: U16orU32 = u16> {
+    current: RwLock<Arc<Graph<NodeId>>>,
+}
+
+impl<NodeId: U16orU32> ArcGraph<NodeId> {
+    /// Create a new ArcGraph holding the given graph.
+    #[inline]
+    pub fn new(graph: Graph<NodeId>) -> Self {
+        Self {
+            current: RwLock::new(Arc::new(graph)),
+        }
+    }
+
+    /// Pin the current graph, and return a guard to query it with.
+    ///
+    /// Slices returned by the guard are tied to the guard, not to this ArcGraph,
+    /// so they stay valid when a new graph is swapped in.
+    #[inline]
+    pub fn guard(&self) -> NeighborsGuard<NodeId> {
+        NeighborsGuard { graph: self.load() }
+    }
+
+    /// Return the current graph.
+    #[inline]
+    pub fn load(&self) -> Arc<Graph<NodeId>> {
+        self.current
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Replace the current graph with the given graph, and return the previous one.
+    ///
+    /// Existing guards keep the previous graph until they are dropped.
+    #[inline]
+    pub fn swap(&self, graph: Graph<NodeId>) -> Arc<Graph<NodeId>> {
+        let mut current = self.current.write().unwrap_or_else(PoisonError::into_inner);
+        std::mem::replace(&mut *current, Arc::new(graph))
+    }
+}
+
+impl<NodeId: U16orU32> From<Graph<NodeId>> for ArcGraph<NodeId> {
+    #[inline]
+    fn from(graph: Graph<NodeId>) -> Self {
+        Self::new(graph)
+    }
+}
+
+/// A snapshot of an [ArcGraph], pinned for the duration of a frame or system.
+///
+/// All [Graph] queries are available through `Deref`;
+/// borrowed results, like neighbor slices, live as long as the guard.
+#[derive(Debug, Clone)]
+pub struct NeighborsGuard<NodeId: U16orU32 = u16> {
+    graph: Arc<Graph<NodeId>>,
+}
+
+impl<NodeId: U16orU32> NeighborsGuard<NodeId> {
+    /// Return the neighbors of the given node in the pinned graph.
+    #[inline]
+    pub fn neighbors(&self, node: NodeId) -> &[NodeId] {
+        self.graph.neighbors(node)
+    }
+
+    /// Return the pinned graph.
+    #[inline]
+    pub fn graph(&self) -> &Graph<NodeId> {
+        &self.graph
+    }
+}
+
+impl<NodeId: U16orU32> Deref for NeighborsGuard<NodeId> {
+    type Target = Graph<NodeId>;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.graph
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_swap_across_threads() {
+        let mut builder = Graph::<u16>::builder(4);
+        builder.connect(0, 1);
+        builder.connect(1, 2);
+        builder.connect(2, 3);
+        let graph = Arc::new(ArcGraph::new(builder.build()));
+
+        let guard = graph.guard();
+
+        let swapper = {
+            let graph = graph.clone();
+            std::thread::spawn(move || {
+                let mut builder = Graph::<u16>::builder(4);
+                builder.connect(0, 3);
+                graph.swap(builder.build())
+            })
+        };
+        let previous = swapper.join().unwrap();
+
+        // the guard and the returned graph are the same snapshot
+        assert!(std::ptr::eq(guard.graph(), &*previous));
+        assert_eq!(guard.neighbors(1), &[0, 2]);
+        assert_eq!(guard.path_to(0, 3).collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+
+        let guard = graph.guard();
+        assert_eq!(guard.neighbors(1), &[] as &[u16]);
+        assert_eq!(guard.neighbor_to(0, 3), Some(3));
+    }
+}
@@ -0,0 +1,138 @@
+//! summaries of the edges of high-degree nodes, to skip most of their neighbors in `neighbor_to`.
+//!
+//! `neighbor_to` checks the destination's bit of each of the current node's edges in turn,
+//! so a query from a hub with thousands of neighbors can check thousands of edges.
+//! A [HubSummary] records, for each hub and each word of destination bits,
+//! which of its edges have any bit set in that word from the hub's side;
+//! a query only checks those edges, and skips the rest without looking them up.
+//!
+//! Summaries are opt-in, with [GraphBuilder::hub_summaries](super::GraphBuilder::hub_summaries)
+//! or [Graph::summarize_hubs](super::Graph::summarize_hubs).
+//! They take about `degree × destinations / BITS` bits for each hub:
+//! one bit per edge for each word of [BITS] destination bits.
+
+use super::U16orU32;
+use crate::bitvec::{BitVec, Digit, IterOnes, BITS};
+use std::borrow::Cow;
+
+/// For each node with at least `min_degree` neighbors, and each word of destination bits,
+/// the positions of the neighbors whose edge has any bit set in that word, from the node's side.
+#[derive(Debug, Clone)]
+pub struct HubSummary {
+    min_degree: usize,
+    /// rows of each node, one per word of destination bits; empty for nodes that are not hubs
+    rows: Vec<Box<[BitVec]>>,
+}
+
+impl HubSummary {
+    /// Summarize the edges of every node with at least `min_degree` neighbors.
+    ///
+    /// `columns` is the number of destination bits of each edge,
+    /// and `edge_bits` returns the bits of the edge between two nodes, from the lower node's side.
+    pub(crate) fn new<'e, NodeId: U16orU32>(
+        neighbors: &[Vec<NodeId>],
+        columns: usize,
+        min_degree: usize,
+        edge_bits: impl Fn(NodeId, NodeId) -> Option<Cow<'e, BitVec>>,
+    ) -> Self {
+        let words = (columns + BITS - 1) / BITS;
+
+        // bits of the last word that are destinations
+        let valid = |w: usize| {
+            if (w + 1) * BITS <= columns {
+                Digit::MAX
+            } else {
+                ((1 as Digit) << (columns % BITS)) - 1
+            }
+        };
+
+        let rows = neighbors
+            .iter()
+            .enumerate()
+            .map(|(a, a_neighbors)| {
+                if a_neighbors.len() < min_degree.max(1) {
+                    return Box::default();
+                }
+
+                let a_id = NodeId::from_usize(a);
+                let mut rows = vec![BitVec::ZERO; words];
+
+                for (i, &b) in a_neighbors.iter().enumerate() {
+                    let (lo, hi) = crate::edge_id(a_id, b);
+                    let bits = edge_bits(lo, hi);
+                    let raw = bits.as_deref().map_or(&[][..], BitVec::as_raw_digits);
+
+                    for (w, row) in rows.iter_mut().enumerate() {
+                        let word = raw.get(w).copied().unwrap_or(0);
+                        let word = if a_id < b { word } else { !word & valid(w) };
+
+                        if word != 0 {
+                            row.set_bit(i, true);
+                        }
+                    }
+                }
+
+                rows.into_boxed_slice()
+            })
+            .collect();
+
+        Self { min_degree, rows }
+    }
+
+    /// Return the minimum number of neighbors of a summarized node.
+    #[inline]
+    pub fn min_degree(&self) -> usize {
+        self.min_degree
+    }
+
+    /// Return the number of summarized nodes.
+    pub fn hubs_len(&self) -> usize {
+        self.rows.iter().filter(|rows| !rows.is_empty()).count()
+    }
+
+    /// Return the positions in `node`'s neighbors of the edges that may lead to the destination
+    /// with the bit index `dest_index`, or `None` if `node` is not summarized.
+    #[inline]
+    pub fn candidates(&self, node: usize, dest_index: usize) -> Option<IterOnes<'_>> {
+        self.rows
+            .get(node)?
+            .get(dest_index / BITS)
+            .map(BitVec::iter_ones)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::sequential::SeqGraph;
+
+    #[test]
+    fn test_hub_queries_match() {
+        // a center connected to 4 hubs, each with 20 leaves; 0 is the center
+        let mut builder = SeqGraph::<u16>::builder(85);
+        for hub in 1..=4u16 {
+            builder.connect(0, hub);
+            for leaf in 0..20 {
+                builder.connect(hub, 5 + (hub - 1) * 20 + leaf);
+            }
+        }
+        let plain = builder.build();
+
+        let mut summarized = plain.clone();
+        summarized.summarize_hubs(4);
+        let hubs = summarized.hubs.as_deref().unwrap();
+        assert_eq!(hubs.hubs_len(), 5);
+
+        for curr in 0..85 {
+            for dest in 0..85 {
+                assert_eq!(
+                    summarized.neighbor_to(curr, dest),
+                    plain.neighbor_to(curr, dest),
+                    "{curr} -> {dest}"
+                );
+                assert!(summarized
+                    .neighbors_to(curr, dest)
+                    .eq(plain.neighbors_to(curr, dest)));
+            }
+        }
+    }
+}
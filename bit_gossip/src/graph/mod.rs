@@ -7,9 +7,12 @@
 //! the best implementation based on the number of threads available.
 //!
 //! You can also manually choose the implementation by calling the [GraphBuilder::multi_threaded] method.
+//! [GraphBuilder::planned_backend] tells you which one will be used, and why.
 //!
 //! If you also want, you can use either [ParaGraph](parallel::ParaGraph) or [SeqGraph](sequential::SeqGraph) directly.
 //!
+//! [SeqGraph](sequential::SeqGraph) can also keep its edges in a different layout; see the [store] module.
+//!
 //! # Examples
 //!
 //! ## Basic Usage
@@ -103,9 +106,22 @@
 //! }
 //! ```
 
+#[cfg(feature = "serde")]
+pub mod describe;
+pub mod dests;
+pub mod hot_swap;
+pub mod hubs;
 #[cfg(feature = "parallel")]
 pub mod parallel;
 pub mod sequential;
+pub mod sharded;
+pub mod stats;
+pub mod store;
+mod yielding;
+
+use crate::bitvec::BitVec;
+use std::collections::HashSet;
+use store::{EdgeStore, HashMapStore};
 
 /// Unweighted Undirected graph that can be used to find shortest paths between nodes.
 ///
@@ -116,10 +132,15 @@ pub mod sequential;
 /// If you want to resize the graph, or add/remove edges, you can
 /// convert it into a builder by calling `.into_builder()`.`
 ///
+/// Cloning a built graph is cheap, as its internals are shared behind an `Arc`.
+///
+/// The sequential backend stores the edges' bits in the [EdgeStore] `S`;
+/// see [GraphBuilder::with_store]. The parallel backend always keeps them in atomic bit vectors.
+///
 /// To see a basic use case examples, check the [graph](crate::graph) module documentation.
-#[derive(Debug)]
-pub enum Graph<NodeId: U16orU32 = u16> {
-    Sequential(sequential::SeqGraph<NodeId>),
+#[derive(Debug, Clone)]
+pub enum Graph<NodeId: U16orU32 = u16, S: EdgeStore<NodeId> = HashMapStore<NodeId>> {
+    Sequential(sequential::SeqGraph<NodeId, S>),
     #[cfg(feature = "parallel")]
     Parallel(parallel::ParaGraph<NodeId>),
 }
@@ -140,18 +161,30 @@ impl<NodeId: U16orU32> Graph<NodeId> {
 
         GraphBuilder::new(nodes_len)
     }
+}
 
+impl<NodeId: U16orU32, S: EdgeStore<NodeId>> Graph<NodeId, S> {
     /// Converts this graph into a builder.
     ///
     /// This is useful if you want to update the graph,
     /// like resizing nodes or adding/removing edges.
     ///
     /// Then you can build the graph again.
-    pub fn into_builder(self) -> GraphBuilder<NodeId> {
-        let nodes_len = match &self {
-            Graph::Sequential(ref builder) => builder.nodes_len(),
-            #[cfg(feature = "parallel")]
-            Graph::Parallel(ref builder) => builder.nodes_len(),
+    ///
+    /// No computed paths are carried over, on either backend:
+    /// the rebuilt graph is the same as one built from scratch with the same edges.
+    ///
+    /// The builder keeps the graph's backend, the minimum degree of its
+    /// [summarized hubs](Self::summarize_hubs), and the
+    /// [stable_order](GraphBuilder::stable_order) setting of a parallel graph,
+    /// or the [parallel_setup](GraphBuilder::parallel_setup) setting of a sequential one.
+    pub fn into_builder(self) -> GraphBuilder<NodeId, S> {
+        let hub_min_degree = self.hubs().map(hubs::HubSummary::min_degree);
+
+        #[cfg(feature = "parallel")]
+        let (stable_order, parallel_setup) = match &self {
+            Graph::Sequential(graph) => (false, graph.parallel_setup),
+            Graph::Parallel(graph) => (graph.stable_order, false),
         };
 
         let inner = match self {
@@ -164,13 +197,262 @@ impl<NodeId: U16orU32> Graph<NodeId> {
             GraphBuilderEnum::Sequential(_) => Some(false),
             #[cfg(feature = "parallel")]
             GraphBuilderEnum::Parallel(_) => Some(true),
-            GraphBuilderEnum::None => unreachable!(),
         };
 
         GraphBuilder {
             inner,
             multi_threaded,
-            nodes_len,
+            #[cfg(feature = "parallel")]
+            stable_order,
+            #[cfg(feature = "parallel")]
+            parallel_setup,
+            hub_min_degree,
+        }
+    }
+
+    /// Return a new graph that shares all of its data with this one,
+    /// as the starting point of a variant of this graph, like a seasonal version of a map.
+    ///
+    /// This is as cheap as `clone()`. Convert the fork with [into_builder](Self::into_builder),
+    /// make the edits and build it again:
+    /// on either backend, the edges' bit vectors are shared copy-on-write,
+    /// so the variant only keeps new bits for the edges whose shortest paths changed.
+    ///
+    /// ```
+    /// use bit_gossip::Graph;
+    ///
+    /// let mut builder = Graph::builder(4);
+    /// builder.connect(0u16, 1);
+    /// builder.connect(1, 2);
+    /// builder.connect(2, 3);
+    /// builder.connect(3, 0);
+    /// let summer = builder.build();
+    ///
+    /// // in winter, the river floods and the bridge between 3 and 0 is closed
+    /// let mut builder = summer.fork().into_builder();
+    /// builder.disconnect(3, 0);
+    /// let winter = builder.build();
+    ///
+    /// assert_eq!(summer.neighbor_to(0, 3), Some(3));
+    /// assert_eq!(winter.neighbor_to(0, 3), Some(1));
+    /// ```
+    #[inline]
+    pub fn fork(&self) -> Self {
+        self.clone()
+    }
+
+    /// Drop the bits of all destinations except `dests`, to save memory in a special-purpose graph;
+    /// e.g. a strategic AI that only ever paths to a few objective nodes.
+    ///
+    /// Queries to retained destinations work as before;
+    /// queries to any other destination return `None`, as if there were no path.
+    /// Call this on a [fork](Self::fork) to keep the full graph available elsewhere.
+    ///
+    /// Converting the graph back with [into_builder](Self::into_builder)
+    /// rebuilds the bits of every destination.
+    ///
+    /// ```
+    /// use bit_gossip::{bitvec::BitVec, Graph};
+    ///
+    /// let mut builder = Graph::builder(4);
+    /// builder.connect(0u16, 1);
+    /// builder.connect(1, 2);
+    /// builder.connect(2, 3);
+    /// let graph = builder.build();
+    ///
+    /// // the strategic AI only ever heads to the objective at node 3
+    /// let mut objectives = BitVec::ZERO;
+    /// objectives.set_bit(3, true);
+    ///
+    /// let mut strategic = graph.fork();
+    /// strategic.retain_dests(&objectives);
+    ///
+    /// assert_eq!(strategic.neighbor_to(0, 3), Some(1));
+    /// assert_eq!(strategic.neighbor_to(0, 2), None);
+    /// assert_eq!(graph.neighbor_to(0, 2), Some(1));
+    /// ```
+    pub fn retain_dests(&mut self, dests: &BitVec) {
+        match self {
+            Graph::Sequential(graph) => graph.retain_dests(dests),
+            #[cfg(feature = "parallel")]
+            Graph::Parallel(graph) => graph.retain_dests(dests),
+        }
+    }
+
+    /// Summarize the edges of every node with at least `min_degree` neighbors,
+    /// so that queries from those nodes only check the edges that may lead to the destination,
+    /// instead of every edge; see the [hubs] module.
+    ///
+    /// This pays off on star-like graphs, where a few nodes have hundreds of neighbors or more;
+    /// see [GraphBuilder::hub_summaries] to summarize them as part of the build.
+    /// Queries return the same nodes, in the same order, with or without the summary.
+    ///
+    /// ```
+    /// use bit_gossip::Graph;
+    ///
+    /// // a plaza with 200 streets leading out of it
+    /// let mut builder = Graph::builder(201);
+    /// for i in 1..=200u16 {
+    ///     builder.connect(0, i);
+    /// }
+    /// let mut graph = builder.build();
+    /// graph.summarize_hubs(64);
+    ///
+    /// assert_eq!(graph.neighbor_to(0, 150), Some(150));
+    /// assert_eq!(graph.path_to(3, 150).collect::<Vec<_>>(), vec![3, 0, 150]);
+    /// ```
+    pub fn summarize_hubs(&mut self, min_degree: usize) {
+        match self {
+            Graph::Sequential(graph) => graph.summarize_hubs(min_degree),
+            #[cfg(feature = "parallel")]
+            Graph::Parallel(graph) => graph.summarize_hubs(min_degree),
+        }
+    }
+
+    /// Return the edge summaries of [summarize_hubs](Self::summarize_hubs), if enabled.
+    pub fn hubs(&self) -> Option<&hubs::HubSummary> {
+        match self {
+            Graph::Sequential(graph) => graph.hubs.as_deref(),
+            #[cfg(feature = "parallel")]
+            Graph::Parallel(graph) => graph.hubs.as_deref(),
+        }
+    }
+
+    /// Apply the given edge edits in place, and recompute the bits of only the destinations `dests`
+    /// with a plain BFS from each of them.
+    ///
+    /// This is meant for games where only a handful of destinations are ever queried,
+    /// like a tower, a base or a player spawn,
+    /// to update them instantly on a door-open event instead of rebuilding the whole graph.
+    /// The cost is one BFS and one pass over the edges per destination.
+    ///
+    /// Queries to `dests` are up to date afterwards;
+    /// the bits of all other destinations are left as they were, and may be wrong
+    /// until the graph is rebuilt with [into_builder](Self::into_builder).
+    /// It pairs well with [retain_dests](Self::retain_dests), which drops the other destinations;
+    /// destinations dropped that way are skipped.
+    ///
+    /// **Note:** like a full build, an edge between two nodes at the same distance from a destination
+    /// is reported as leading toward it from one of the two nodes.
+    ///
+    /// ```
+    /// use bit_gossip::{bitvec::BitVec, graph::EdgeEdit, Graph};
+    ///
+    /// // a corridor 0 - 1 - 2 - 3 - 4, with a locked door between 0 and 4
+    /// let mut builder = Graph::builder(5);
+    /// builder.connect(0u16, 1);
+    /// builder.connect(1, 2);
+    /// builder.connect(2, 3);
+    /// builder.connect(3, 4);
+    /// let mut graph = builder.build();
+    /// assert_eq!(graph.neighbor_to(0, 4), Some(1));
+    ///
+    /// let mut base = BitVec::ZERO;
+    /// base.set_bit(4, true);
+    ///
+    /// // the door opens
+    /// graph.retarget_after_edits(&[EdgeEdit::Connect(0, 4)], &base);
+    /// assert_eq!(graph.neighbor_to(0, 4), Some(4));
+    /// assert_eq!(graph.path_to(1, 4).collect::<Vec<_>>(), vec![1, 0, 4]);
+    /// ```
+    pub fn retarget_after_edits(&mut self, edits: &[EdgeEdit<NodeId>], dests: &BitVec) {
+        match self {
+            Graph::Sequential(graph) => graph.retarget_after_edits(edits, dests),
+            #[cfg(feature = "parallel")]
+            Graph::Parallel(graph) => graph.retarget_after_edits(edits, dests),
+        }
+    }
+
+    /// Return whether the bits of the given destination are up to date,
+    /// so that queries to it return its shortest paths.
+    ///
+    /// This lets calling code choose between the precomputed paths and a fallback strategy
+    /// up front, instead of reading a `None` from [neighbor_to](Self::neighbor_to) as "no path";
+    /// see [ready_dests](Self::ready_dests).
+    ///
+    /// ```
+    /// use bit_gossip::{bitvec::BitVec, graph::EdgeEdit, Graph};
+    ///
+    /// let mut builder = Graph::builder(4);
+    /// builder.connect(0u16, 1);
+    /// builder.connect(1, 2);
+    /// builder.connect(2, 3);
+    /// let mut graph = builder.build();
+    /// assert!(graph.dest_ready(0));
+    ///
+    /// let mut base = BitVec::ZERO;
+    /// base.set_bit(3, true);
+    ///
+    /// // only the paths to the base are recomputed, so the others may be stale
+    /// graph.retarget_after_edits(&[EdgeEdit::Connect(0, 3)], &base);
+    /// assert!(graph.dest_ready(3));
+    /// assert!(!graph.dest_ready(0));
+    /// assert_eq!(graph.ready_dests().iter_ones().collect::<Vec<_>>(), vec![3]);
+    /// ```
+    #[inline]
+    pub fn dest_ready(&self, dest: NodeId) -> bool {
+        check_nodes!("dest_ready", self.nodes_len(), dest);
+        self.ready_dests().get_bit(dest.as_usize())
+    }
+
+    /// Return the destinations whose bits are up to date.
+    ///
+    /// Every node is ready after a build.
+    /// Destinations dropped with [retain_dests](Self::retain_dests) are not ready,
+    /// and after [retarget_after_edits](Self::retarget_after_edits) with any edits,
+    /// only the retargeted destinations are ready until the graph is rebuilt.
+    #[inline]
+    pub fn ready_dests(&self) -> &BitVec {
+        match self {
+            Graph::Sequential(graph) => graph.ready_dests(),
+            #[cfg(feature = "parallel")]
+            Graph::Parallel(graph) => graph.ready_dests(),
+        }
+    }
+
+    /// Return which implementation this graph was built with.
+    #[inline]
+    pub fn backend(&self) -> Backend {
+        match self {
+            Graph::Sequential(_) => Backend::Sequential,
+            #[cfg(feature = "parallel")]
+            Graph::Parallel(_) => Backend::Parallel,
+        }
+    }
+
+    /// Return the graph as a [SeqGraph](sequential::SeqGraph),
+    /// copying the bits into plain bit vectors if it was built with the parallel backend.
+    ///
+    /// The paths are not computed again.
+    /// E.g. build in parallel on a machine with many cores, and ship the plain representation.
+    ///
+    /// ```
+    /// use bit_gossip::Graph;
+    ///
+    /// let mut builder = Graph::builder(3);
+    /// builder.connect(0u16, 1);
+    /// builder.connect(1, 2);
+    /// let graph = builder.build().into_seq();
+    ///
+    /// assert_eq!(graph.neighbor_to(0, 2), Some(1));
+    /// ```
+    pub fn into_seq(self) -> sequential::SeqGraph<NodeId, S> {
+        match self {
+            Graph::Sequential(graph) => graph,
+            #[cfg(feature = "parallel")]
+            Graph::Parallel(graph) => graph.into(),
+        }
+    }
+
+    /// Return the graph as a [ParaGraph](parallel::ParaGraph),
+    /// copying the bits into atomic bit vectors if it was built with the sequential backend.
+    ///
+    /// The paths are not computed again.
+    #[cfg(feature = "parallel")]
+    pub fn into_par(self) -> parallel::ParaGraph<NodeId> {
+        match self {
+            Graph::Sequential(graph) => graph.into(),
+            Graph::Parallel(graph) => graph,
         }
     }
 
@@ -191,6 +473,7 @@ impl<NodeId: U16orU32> Graph<NodeId> {
     /// or [neighbors_to](Self::neighbors_to) to get all neighboring nodes.
     #[inline]
     pub fn neighbor_to(&self, curr: NodeId, dest: NodeId) -> Option<NodeId> {
+        check_nodes!("neighbor_to", self.nodes_len(), curr, dest);
         self.neighbors_to(curr, dest).next()
     }
 
@@ -214,15 +497,162 @@ impl<NodeId: U16orU32> Graph<NodeId> {
         dest: NodeId,
         f: impl Fn(NodeId) -> bool,
     ) -> Option<NodeId> {
+        check_nodes!("neighbor_to_with", self.nodes_len(), curr, dest);
         self.neighbors_to(curr, dest).find(|&n| f(n))
     }
 
+    /// Given a current node and a destination node, and a set of forbidden edges,
+    /// return the neighboring node of current that is the shortest path to the destination node
+    /// without going through any forbidden edge.
+    ///
+    /// This is useful for edges that are closed for a single agent only, like doors it has no key for,
+    /// without rebuilding the graph.
+    ///
+    /// Edges may be given in either order, e.g. as [edge_id](crate::edge_id).
+    ///
+    /// The precomputed path is used if it avoids all forbidden edges.
+    /// Otherwise, the next node is found by a breadth-first search on the graph without the forbidden edges,
+    /// which is slower, but falls back to an equally short or a longer path when there is one.
+    ///
+    /// `None` is returned when:
+    /// - `curr` and `dest` are the same node
+    /// - `curr` has no path to `dest` that avoids the forbidden edges
+    ///
+    /// ```
+    /// use bit_gossip::Graph;
+    /// use std::collections::HashSet;
+    ///
+    /// // 0 -- 1 -- 2
+    /// // |         |
+    /// // 3 -- 4 -- 5
+    /// let mut builder = Graph::builder(6);
+    /// builder.connect(0u16, 1);
+    /// builder.connect(1, 2);
+    /// builder.connect(0, 3);
+    /// builder.connect(3, 4);
+    /// builder.connect(4, 5);
+    /// builder.connect(2, 5);
+    /// let graph = builder.build();
+    ///
+    /// assert_eq!(graph.neighbor_to(0, 2), Some(1));
+    ///
+    /// // the door between 1 and 2 is locked for this agent
+    /// let locked = HashSet::from([(2, 1)]);
+    /// assert_eq!(graph.neighbor_to_avoiding_edges(0, 2, &locked), Some(3));
+    /// assert_eq!(graph.neighbor_to_avoiding_edges(1, 2, &locked), Some(0));
+    /// ```
+    pub fn neighbor_to_avoiding_edges(
+        &self,
+        curr: NodeId,
+        dest: NodeId,
+        forbidden: &HashSet<(NodeId, NodeId)>,
+    ) -> Option<NodeId> {
+        check_nodes!("neighbor_to_avoiding_edges", self.nodes_len(), curr, dest);
+        let is_forbidden =
+            |a: NodeId, b: NodeId| forbidden.contains(&(a, b)) || forbidden.contains(&(b, a));
+
+        if curr == dest {
+            return None;
+        }
+
+        let next = self.neighbor_to(curr, dest)?;
+        if forbidden.is_empty() {
+            return Some(next);
+        }
+
+        // the precomputed path is a shortest path, so it is still one if it avoids the forbidden edges
+        let mut prev = curr;
+        let mut clean = true;
+        for node in self.path_to(curr, dest).skip(1) {
+            if is_forbidden(prev, node) {
+                clean = false;
+                break;
+            }
+            prev = node;
+        }
+        if clean {
+            return Some(next);
+        }
+
+        // search from the destination, so the node that discovers `curr` is its next node
+        let mut visited = vec![false; self.nodes_len()];
+        let mut queue = std::collections::VecDeque::new();
+        visited[dest.as_usize()] = true;
+        queue.push_back(dest);
+
+        while let Some(node) = queue.pop_front() {
+            for &neighbor in self.neighbors(node) {
+                if visited[neighbor.as_usize()] || is_forbidden(node, neighbor) {
+                    continue;
+                }
+                if neighbor == curr {
+                    return Some(node);
+                }
+                visited[neighbor.as_usize()] = true;
+                queue.push_back(neighbor);
+            }
+        }
+
+        None
+    }
+
+    /// Same as [neighbor_to](Self::neighbor_to) if `dest` is [ready](Self::dest_ready);
+    /// otherwise, fall back to a breadth-first search of at most `hop_budget` hops from `curr`,
+    /// so agents keep moving while the exact data is unavailable,
+    /// e.g. for destinations dropped with [retain_dests](Self::retain_dests).
+    ///
+    /// The search returns the first hop of a shortest path if `dest` is within `hop_budget` hops;
+    /// it costs up to every node within that many hops of `curr`, and searches at least one hop.
+    /// If `dest` is farther away, the search still returns a neighbor, toward the last node it reached;
+    /// it keeps the agent moving, but not necessarily closer to `dest`.
+    ///
+    /// `None` is returned when:
+    /// - `curr` and `dest` are the same node
+    /// - `curr` has no path to `dest`; if `dest` is not ready, this is only known when every node
+    ///   connected to `curr` is within `hop_budget` hops
+    ///
+    /// ```
+    /// use bit_gossip::{bitvec::BitVec, Graph};
+    ///
+    /// let mut builder = Graph::builder(6);
+    /// for i in 0..5u16 {
+    ///     builder.connect(i, i + 1);
+    /// }
+    /// let mut graph = builder.build();
+    ///
+    /// // only the paths to node 5 are kept
+    /// let mut dests = BitVec::ZERO;
+    /// dests.set_bit(5, true);
+    /// graph.retain_dests(&dests);
+    ///
+    /// assert_eq!(graph.neighbor_to(3, 0), None);
+    /// assert_eq!(graph.neighbor_to_or_greedy(3, 0, 4), Some(2));
+    ///
+    /// // 0 is out of the budget, so the search moves toward the last node it reached, 5
+    /// assert_eq!(graph.neighbor_to_or_greedy(3, 0, 2), Some(4));
+    /// ```
+    pub fn neighbor_to_or_greedy(
+        &self,
+        curr: NodeId,
+        dest: NodeId,
+        hop_budget: usize,
+    ) -> Option<NodeId> {
+        check_nodes!("neighbor_to_or_greedy", self.nodes_len(), curr, dest);
+
+        if self.dest_ready(dest) {
+            return self.neighbor_to(curr, dest);
+        }
+
+        greedy_neighbor_to(|node| self.neighbors(node), curr, dest, hop_budget, None)
+    }
+
     /// Given a current node and a destination node,
     /// return all neighboring nodes of current that are shortest paths to the destination node.
     ///
     /// The nodes will be returned in the same order for the same inputs. However, the ordering of the nodes is not guaranteed.
     #[inline]
-    pub fn neighbors_to(&self, curr: NodeId, dest: NodeId) -> NeighborsToIter<'_, NodeId> {
+    pub fn neighbors_to(&self, curr: NodeId, dest: NodeId) -> NeighborsToIter<'_, NodeId, S> {
+        check_nodes!("neighbors_to", self.nodes_len(), curr, dest);
         match self {
             Graph::Sequential(graph) => NeighborsToIter::Sequential(graph.neighbors_to(curr, dest)),
             #[cfg(feature = "parallel")]
@@ -239,7 +669,8 @@ impl<NodeId: U16orU32> Graph<NodeId> {
     ///
     /// If there is no path, the list will be empty.
     #[inline]
-    pub fn path_to(&self, curr: NodeId, dest: NodeId) -> PathIter<'_, NodeId> {
+    pub fn path_to(&self, curr: NodeId, dest: NodeId) -> PathIter<'_, NodeId, S> {
+        check_nodes!("path_to", self.nodes_len(), curr, dest);
         match self {
             Graph::Sequential(graph) => PathIter::Sequential(graph.path_to(curr, dest)),
             #[cfg(feature = "parallel")]
@@ -247,9 +678,87 @@ impl<NodeId: U16orU32> Graph<NodeId> {
         }
     }
 
+    /// Given a current node and a destination node,
+    /// return every `stride`-th node of the path from the current node to the destination node,
+    /// followed by the destination node.
+    ///
+    /// The current node itself is not returned.
+    /// This is a coarse version of [path_to](Self::path_to), cheap to send over the network;
+    /// movement between two waypoints can be re-queried locally with [neighbor_to](Self::neighbor_to).
+    ///
+    /// If there is no path, the list will be empty.
+    ///
+    /// Panics if `stride` is 0.
+    ///
+    /// ```
+    /// use bit_gossip::Graph;
+    ///
+    /// let mut builder = Graph::builder(8);
+    /// for i in 0..7u16 {
+    ///     builder.connect(i, i + 1);
+    /// }
+    /// let graph = builder.build();
+    ///
+    /// assert_eq!(graph.waypoints_to(0, 7, 3).collect::<Vec<_>>(), vec![3, 6, 7]);
+    /// assert_eq!(graph.waypoints_to(0, 6, 3).collect::<Vec<_>>(), vec![3, 6]);
+    /// assert_eq!(graph.waypoints_to(0, 2, 3).collect::<Vec<_>>(), vec![2]);
+    /// ```
+    #[inline]
+    pub fn waypoints_to(
+        &self,
+        curr: NodeId,
+        dest: NodeId,
+        stride: usize,
+    ) -> WaypointsIter<'_, NodeId, S> {
+        check_nodes!("waypoints_to", self.nodes_len(), curr, dest);
+        assert!(stride > 0, "waypoint stride must be at least 1");
+
+        let mut path = self.path_to(curr, dest);
+        // skip the current node
+        path.next();
+
+        WaypointsIter {
+            path,
+            stride,
+            hops: 0,
+            pending: None,
+        }
+    }
+
+    /// Given a current node and a source node,
+    /// return the node right before `curr` on a shortest path from `src` to `curr`.
+    ///
+    /// The graph is undirected, so this is the neighbor of `curr` that is one step closer to `src`;
+    /// calling it repeatedly retraces the way back to `src`.
+    ///
+    /// `None` is returned when:
+    /// - `curr` and `src` are the same node
+    /// - there is no path from `src` to `curr`
+    ///
+    /// ```
+    /// use bit_gossip::Graph;
+    ///
+    /// let mut builder = Graph::builder(4);
+    /// builder.connect(0u16, 1);
+    /// builder.connect(1, 2);
+    /// builder.connect(2, 3);
+    /// let graph = builder.build();
+    ///
+    /// // walked from 0 to 3; step back toward 0
+    /// assert_eq!(graph.prev_node(3, 0), Some(2));
+    /// assert_eq!(graph.prev_node(2, 0), Some(1));
+    /// assert_eq!(graph.prev_node(0, 0), None);
+    /// ```
+    #[inline]
+    pub fn prev_node(&self, curr: NodeId, src: NodeId) -> Option<NodeId> {
+        check_nodes!("prev_node", self.nodes_len(), curr, src);
+        self.neighbor_to(curr, src)
+    }
+
     /// Check if there is a path from the current node to the destination node.
     #[inline]
     pub fn path_exists(&self, curr: NodeId, dest: NodeId) -> bool {
+        check_nodes!("path_exists", self.nodes_len(), curr, dest);
         match self {
             Graph::Sequential(graph) => graph.path_exists(curr, dest),
             #[cfg(feature = "parallel")]
@@ -257,9 +766,249 @@ impl<NodeId: U16orU32> Graph<NodeId> {
         }
     }
 
+    /// Estimate how many edge-disjoint shortest paths there are between nodes `a` and `b`,
+    /// counting at most `limit` of them.
+    ///
+    /// Paths are taken greedily from the shortest-path DAG toward `b`, and each found path
+    /// uses up its edges; so the result is a lower bound on the true number, computed in
+    /// `O(limit × edges)` without running a max-flow, since each of the up to `limit` searches
+    /// may visit every edge.
+    /// A result of `1` means there is a single chokepoint corridor on the shortest route.
+    ///
+    /// `0` is returned when `a` and `b` are the same node, or when there is no path between them.
+    ///
+    /// ```sh
+    /// 0 -- 1
+    /// |    |
+    /// 2 -- 3 -- 4
+    /// ```
+    ///
+    /// ```
+    /// use bit_gossip::Graph;
+    ///
+    /// let mut builder = Graph::builder(5);
+    /// builder.connect(0u16, 1);
+    /// builder.connect(0, 2);
+    /// builder.connect(1, 3);
+    /// builder.connect(2, 3);
+    /// builder.connect(3, 4);
+    /// let graph = builder.build();
+    ///
+    /// // two corridors from 0 to 3
+    /// assert_eq!(graph.min_cut_estimate(0, 3, 8), 2);
+    /// assert_eq!(graph.min_cut_estimate(0, 3, 1), 1);
+    /// // but both go through the edge 3 -- 4
+    /// assert_eq!(graph.min_cut_estimate(0, 4, 8), 1);
+    /// ```
+    pub fn min_cut_estimate(&self, a: NodeId, b: NodeId, limit: usize) -> usize {
+        check_nodes!("min_cut_estimate", self.nodes_len(), a, b);
+        if a == b || !self.path_exists(a, b) {
+            return 0;
+        }
+
+        let mut used = HashSet::new();
+        // nodes that can no longer reach `b`; edges only get used up, so they stay dead
+        let mut dead = vec![false; self.nodes_len()];
+        let mut on_path = vec![false; self.nodes_len()];
+        let mut path = Vec::new();
+        let mut count = 0;
+
+        while count < limit {
+            path.clear();
+            path.push(a);
+            on_path[a.as_usize()] = true;
+
+            while let Some(&curr) = path.last() {
+                if curr == b {
+                    break;
+                }
+
+                let next = self.neighbors_to(curr, b).find(|&n| {
+                    !dead[n.as_usize()]
+                        && !on_path[n.as_usize()]
+                        && !used.contains(&crate::edge_id(curr, n))
+                });
+
+                match next {
+                    Some(n) => {
+                        path.push(n);
+                        on_path[n.as_usize()] = true;
+                    }
+                    None => {
+                        dead[curr.as_usize()] = true;
+                        on_path[curr.as_usize()] = false;
+                        path.pop();
+                    }
+                }
+            }
+
+            if path.is_empty() {
+                break;
+            }
+
+            for w in path.windows(2) {
+                used.insert(crate::edge_id(w[0], w[1]));
+            }
+            for n in &path {
+                on_path[n.as_usize()] = false;
+            }
+
+            count += 1;
+        }
+
+        count
+    }
+
+    /// Given a destination node and another graph over the same nodes,
+    /// return the nodes whose next node toward the destination differs between the two graphs.
+    ///
+    /// E.g. compare the graphs before and after opening a door,
+    /// to highlight the regions of the map that will route differently.
+    ///
+    /// A node that can reach the destination in only one of the graphs is included;
+    /// nodes that exist in only one of the graphs are compared as if they had no path.
+    /// When there are multiple shortest paths, a node that picks a different one is included too.
+    /// The nodes are returned in ascending order.
+    ///
+    /// ```sh
+    /// 0 -- 1 -- 2 ·· 3 -- 4
+    /// ```
+    ///
+    /// ```
+    /// use bit_gossip::Graph;
+    ///
+    /// let mut builder = Graph::builder(5);
+    /// builder.connect(0u16, 1);
+    /// builder.connect(1, 2);
+    /// builder.connect(3, 4);
+    /// let closed = builder.build();
+    ///
+    /// let mut builder = closed.clone().into_builder();
+    /// builder.connect(2, 3);
+    /// let open = builder.build();
+    ///
+    /// // with the door open, 3 and 4 can go to 0; 1 and 2 go the same way as before
+    /// assert_eq!(closed.diff_flow(0, &open), vec![3, 4]);
+    /// assert!(closed.diff_flow(0, &closed).is_empty());
+    /// ```
+    pub fn diff_flow(&self, dest: NodeId, other: &Graph<NodeId, S>) -> Vec<NodeId> {
+        check_nodes!("diff_flow", self.nodes_len(), dest);
+
+        // next nodes toward an unreachable node are not meaningful, so only compare reachable ones
+        let ours = self.reachable_from(dest);
+        let theirs = other.reachable_from(dest);
+
+        (0..self.nodes_len().max(other.nodes_len()))
+            .filter(|&i| {
+                let node = NodeId::from_usize(i);
+                let ours = match ours.get(i) {
+                    Some(true) => self.neighbor_to(node, dest),
+                    _ => None,
+                };
+                let theirs = match theirs.get(i) {
+                    Some(true) => other.neighbor_to(node, dest),
+                    _ => None,
+                };
+                ours != theirs
+            })
+            .map(NodeId::from_usize)
+            .collect()
+    }
+
+    /// Return the shortest path tree rooted at the given node:
+    /// the parent of each node, which is its next node on a shortest path to `root`.
+    ///
+    /// Each parent is one of [neighbors_to](Self::neighbors_to)`(node, root)`,
+    /// so the tree follows the same paths as the rest of the queries;
+    /// it is extracted with a single search from `root`, instead of a query per node.
+    ///
+    /// The parent is `None` for `root` itself, and for nodes that have no path to `root`.
+    ///
+    /// This is useful for grouping nodes by subtree or by distance from a key location,
+    /// e.g. for spawning or ambience systems.
+    ///
+    /// ```sh
+    /// 0 -- 1 -- 2
+    ///      |
+    ///      3 -- 4    5
+    /// ```
+    ///
+    /// ```
+    /// use bit_gossip::Graph;
+    ///
+    /// let mut builder = Graph::builder(6);
+    /// builder.connect(0u16, 1);
+    /// builder.connect(1, 2);
+    /// builder.connect(1, 3);
+    /// builder.connect(3, 4);
+    /// let graph = builder.build();
+    ///
+    /// let parents = graph.shortest_path_tree(1);
+    /// assert_eq!(parents, vec![Some(1), None, Some(1), Some(1), Some(3), None]);
+    /// ```
+    pub fn shortest_path_tree(&self, root: NodeId) -> Vec<Option<NodeId>> {
+        check_nodes!("shortest_path_tree", self.nodes_len(), root);
+
+        let mut depth = vec![u32::MAX; self.nodes_len()];
+        // the node that discovered each node; always one step closer to `root`
+        let mut found_by = vec![root; self.nodes_len()];
+        let mut parents = vec![None; self.nodes_len()];
+        let mut queue = std::collections::VecDeque::new();
+
+        depth[root.as_usize()] = 0;
+        queue.push_back(root);
+
+        while let Some(node) = queue.pop_front() {
+            let d = depth[node.as_usize()];
+
+            // every node one step closer has been found by now; prefer the stored next node,
+            // and fall back to the discovering node where the bits pick an equally far neighbor
+            if node != root {
+                parents[node.as_usize()] = self
+                    .neighbors_to(node, root)
+                    .find(|n| depth[n.as_usize()] < d)
+                    .or(Some(found_by[node.as_usize()]));
+            }
+
+            for &neighbor in self.neighbors(node) {
+                if depth[neighbor.as_usize()] == u32::MAX {
+                    depth[neighbor.as_usize()] = d + 1;
+                    found_by[neighbor.as_usize()] = node;
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+
+        parents
+    }
+
+    /// Return whether each node is connected to `node`;
+    /// empty if `node` is out of range.
+    fn reachable_from(&self, node: NodeId) -> Vec<bool> {
+        if node.as_usize() >= self.nodes_len() {
+            return Vec::new();
+        }
+
+        let mut visited = vec![false; self.nodes_len()];
+        let mut stack = vec![node];
+        visited[node.as_usize()] = true;
+
+        while let Some(node) = stack.pop() {
+            for &neighbor in self.neighbors(node) {
+                if !visited[neighbor.as_usize()] {
+                    visited[neighbor.as_usize()] = true;
+                    stack.push(neighbor);
+                }
+            }
+        }
+
+        visited
+    }
+
     /// Return a list of all neighboring nodes of the given node.
     #[inline]
     pub fn neighbors(&self, node: NodeId) -> &[NodeId] {
+        check_nodes!("neighbors", self.nodes_len(), node);
         match self {
             Graph::Sequential(graph) => graph.neighbors(node),
             #[cfg(feature = "parallel")]
@@ -267,6 +1016,51 @@ impl<NodeId: U16orU32> Graph<NodeId> {
         }
     }
 
+    /// Return the user data attached to the edge between node_a and node_b
+    /// with [GraphBuilder::connect_with_data].
+    ///
+    /// Edges added with [GraphBuilder::connect] have the data `0`.
+    /// The data is only stored for you, and is never used for pathfinding.
+    ///
+    /// `None` is returned when there is no edge between the two nodes.
+    ///
+    /// ```
+    /// use bit_gossip::Graph;
+    ///
+    /// const DOOR: u16 = 7;
+    ///
+    /// let mut builder = Graph::builder(3);
+    /// builder.connect(0u16, 1);
+    /// builder.connect_with_data(1, 2, DOOR);
+    /// let graph = builder.build();
+    ///
+    /// assert_eq!(graph.edge_data(2, 1), Some(DOOR));
+    /// assert_eq!(graph.edge_data(0, 1), Some(0));
+    /// assert_eq!(graph.edge_data(0, 2), None);
+    /// ```
+    #[inline]
+    pub fn edge_data(&self, a: NodeId, b: NodeId) -> Option<u16> {
+        match self {
+            Graph::Sequential(graph) => graph.edge_data(a, b),
+            #[cfg(feature = "parallel")]
+            Graph::Parallel(graph) => graph.edge_data(a, b),
+        }
+    }
+
+    /// Return a hash of this graph's topology: the number of nodes and the set of edges.
+    ///
+    /// The hash does not depend on the order edges were added in or on the internal layout,
+    /// so it can be used as a cache key to check whether a map has changed since it was last built.
+    /// The same topology gives the same hash for every graph type and builder.
+    #[inline]
+    pub fn topology_hash(&self) -> u64 {
+        match self {
+            Graph::Sequential(graph) => graph.topology_hash(),
+            #[cfg(feature = "parallel")]
+            Graph::Parallel(graph) => graph.topology_hash(),
+        }
+    }
+
     /// Return the number of nodes in the graph.
     #[inline]
     pub fn nodes_len(&self) -> usize {
@@ -288,15 +1082,24 @@ impl<NodeId: U16orU32> Graph<NodeId> {
     }
 }
 
-/// An iterator that returns a path from the current node to the destination node.
+/// An edit to the edges of a built graph; see [Graph::retarget_after_edits].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EdgeEdit<NodeId: U16orU32 = u16> {
+    /// Add an edge between the two nodes.
+    Connect(NodeId, NodeId),
+    /// Remove the edge between the two nodes.
+    Disconnect(NodeId, NodeId),
+}
+
+/// An iterator that returns a path from the current node to the destination node.
 #[derive(Debug)]
-pub enum PathIter<'a, NodeId: U16orU32> {
-    Sequential(sequential::PathIter<'a, NodeId>),
+pub enum PathIter<'a, NodeId: U16orU32, S: EdgeStore<NodeId> = HashMapStore<NodeId>> {
+    Sequential(sequential::PathIter<'a, NodeId, S>),
     #[cfg(feature = "parallel")]
     Parallel(parallel::PathIter<'a, NodeId>),
 }
 
-impl<NodeId: U16orU32> Iterator for PathIter<'_, NodeId> {
+impl<NodeId: U16orU32, S: EdgeStore<NodeId>> Iterator for PathIter<'_, NodeId, S> {
     type Item = NodeId;
 
     #[inline]
@@ -309,15 +1112,48 @@ impl<NodeId: U16orU32> Iterator for PathIter<'_, NodeId> {
     }
 }
 
+/// An iterator that returns every `stride`-th node of a path, and the destination node.
+///
+/// Returned by [Graph::waypoints_to].
+#[derive(Debug)]
+pub struct WaypointsIter<'a, NodeId: U16orU32, S: EdgeStore<NodeId> = HashMapStore<NodeId>> {
+    path: PathIter<'a, NodeId, S>,
+    stride: usize,
+    /// number of hops taken along the path so far
+    hops: usize,
+    /// last node of the path seen, if it was not returned yet
+    pending: Option<NodeId>,
+}
+
+impl<NodeId: U16orU32, S: EdgeStore<NodeId>> Iterator for WaypointsIter<'_, NodeId, S> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for node in self.path.by_ref() {
+            self.hops += 1;
+
+            if self.hops % self.stride == 0 {
+                self.pending = None;
+                return Some(node);
+            }
+
+            self.pending = Some(node);
+        }
+
+        // the destination, if it was not on a stride
+        self.pending.take()
+    }
+}
+
 /// An iterator that returns neighboring nodes that are shortest paths to the destination node.
 #[derive(Debug)]
-pub enum NeighborsToIter<'a, NodeId: U16orU32> {
-    Sequential(sequential::NeighborsToIter<'a, NodeId>),
+pub enum NeighborsToIter<'a, NodeId: U16orU32, S: EdgeStore<NodeId> = HashMapStore<NodeId>> {
+    Sequential(sequential::NeighborsToIter<'a, NodeId, S>),
     #[cfg(feature = "parallel")]
     Parallel(parallel::NeighborsToIter<'a, NodeId>),
 }
 
-impl<NodeId: U16orU32> Iterator for NeighborsToIter<'_, NodeId> {
+impl<NodeId: U16orU32, S: EdgeStore<NodeId>> Iterator for NeighborsToIter<'_, NodeId, S> {
     type Item = NodeId;
 
     #[inline]
@@ -331,49 +1167,161 @@ impl<NodeId: U16orU32> Iterator for NeighborsToIter<'_, NodeId> {
 }
 
 /// A builder for creating a new graph and all shortest paths.
+///
+/// The sequential backend stores the edges' bits in the [EdgeStore] `S`,
+/// chosen with [with_store](Self::with_store); [new](Self::new) uses the default [HashMapStore].
 #[derive(Debug)]
-pub struct GraphBuilder<NodeId: U16orU32 = u16> {
-    inner: GraphBuilderEnum<NodeId>,
+pub struct GraphBuilder<NodeId: U16orU32 = u16, S: EdgeStore<NodeId> = HashMapStore<NodeId>> {
+    inner: GraphBuilderEnum<NodeId, S>,
     multi_threaded: Option<bool>,
-    nodes_len: usize,
+    #[cfg(feature = "parallel")]
+    stable_order: bool,
+    #[cfg(feature = "parallel")]
+    parallel_setup: bool,
+    hub_min_degree: Option<usize>,
 }
 
 #[derive(Debug)]
-enum GraphBuilderEnum<NodeId: U16orU32> {
-    Sequential(sequential::SeqGraphBuilder<NodeId>),
+enum GraphBuilderEnum<NodeId: U16orU32, S: EdgeStore<NodeId>> {
+    Sequential(sequential::SeqGraphBuilder<NodeId, S>),
     #[cfg(feature = "parallel")]
     Parallel(parallel::ParaGraphBuilder<NodeId>),
-    None,
 }
 
-impl<NodeId: U16orU32> GraphBuilderEnum<NodeId> {
+impl<NodeId: U16orU32, S: EdgeStore<NodeId>> GraphBuilderEnum<NodeId, S> {
+    /// Create the builder of the given backend.
+    fn new(nodes_len: usize, backend: Backend) -> Self {
+        match backend {
+            #[cfg(feature = "parallel")]
+            Backend::Parallel => {
+                GraphBuilderEnum::Parallel(parallel::ParaGraphBuilder::new(nodes_len))
+            }
+            _ => GraphBuilderEnum::Sequential(sequential::SeqGraphBuilder::with_store(nodes_len)),
+        }
+    }
+
     #[inline]
-    fn is_none(&self) -> bool {
-        matches!(self, GraphBuilderEnum::None)
+    fn backend(&self) -> Backend {
+        match self {
+            GraphBuilderEnum::Sequential(_) => Backend::Sequential,
+            #[cfg(feature = "parallel")]
+            GraphBuilderEnum::Parallel(_) => Backend::Parallel,
+        }
     }
 
-    #[allow(unused_variables)]
-    fn set_builder(&mut self, nodes_len: usize, multi_threaded: Option<bool>) {
-        #[cfg(feature = "parallel")]
-        let builder = {
-            let multi_threaded = multi_threaded.unwrap_or_else(|| {
-                let available_parallelism = std::thread::available_parallelism()
-                    .map(|e| e.get())
-                    .unwrap_or(1);
-                available_parallelism > 1
-            });
-
-            if multi_threaded {
-                GraphBuilderEnum::Parallel(parallel::ParaGraphBuilder::new(nodes_len))
-            } else {
-                GraphBuilderEnum::Sequential(sequential::SeqGraphBuilder::new(nodes_len))
+    /// Return the sequential builder, moving the edges over if this is a parallel builder.
+    fn into_seq(self) -> sequential::SeqGraphBuilder<NodeId, S> {
+        match self {
+            GraphBuilderEnum::Sequential(builder) => builder,
+            #[cfg(feature = "parallel")]
+            GraphBuilderEnum::Parallel(builder) => {
+                let mut seq = sequential::SeqGraphBuilder::with_store(builder.nodes_len());
+                for_each_edge(&builder.nodes.inner, &builder.nodes.data, |a, b, data| {
+                    seq.connect_with_data(a, b, data)
+                });
+                seq
             }
-        };
+        }
+    }
 
-        #[cfg(not(feature = "parallel"))]
-        let builder = GraphBuilderEnum::Sequential(sequential::SeqGraphBuilder::new(nodes_len));
+    /// Return the parallel builder, moving the edges over if this is a sequential builder.
+    #[cfg(feature = "parallel")]
+    fn into_par(self) -> parallel::ParaGraphBuilder<NodeId> {
+        match self {
+            GraphBuilderEnum::Parallel(builder) => builder,
+            GraphBuilderEnum::Sequential(builder) => {
+                let mut par = parallel::ParaGraphBuilder::new(builder.nodes_len());
+                for_each_edge(&builder.nodes.inner, &builder.nodes.data, |a, b, data| {
+                    par.connect_with_data(a, b, data)
+                });
+                par
+            }
+        }
+    }
+}
+
+/// Implementation used by a [Graph]: [SeqGraph](sequential::SeqGraph) or [ParaGraph](parallel::ParaGraph).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Backend {
+    Sequential,
+    Parallel,
+}
+
+/// The backend a [GraphBuilder] will build with, and the inputs it was chosen from.
+///
+/// Returned by [GraphBuilder::planned_backend].
+/// Its `Display` output explains the choice in a short sentence, which is handy for logging.
+///
+#[cfg_attr(feature = "parallel", doc = "```")]
+#[cfg_attr(not(feature = "parallel"), doc = "```ignore")]
+/// use bit_gossip::{graph::Backend, Graph};
+///
+/// let builder = Graph::<u16>::builder(100).multi_threaded(false);
+/// let plan = builder.planned_backend();
+///
+/// assert_eq!(plan.backend, Backend::Sequential);
+/// assert_eq!(plan.multi_threaded, Some(false));
+/// assert_eq!(plan.to_string(), "sequential: set with `multi_threaded(false)`");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackendPlan {
+    /// The backend that will be used.
+    pub backend: Backend,
+    /// The explicit choice made with [GraphBuilder::multi_threaded], if any.
+    pub multi_threaded: Option<bool>,
+    /// Number of threads reported by [std::thread::available_parallelism];
+    /// `None` if it could not be determined, in which case it is treated as 1.
+    pub available_parallelism: Option<usize>,
+    /// Whether the `parallel` feature is enabled; without it, the backend is always sequential.
+    pub parallel_feature: bool,
+}
+
+impl BackendPlan {
+    fn new(multi_threaded: Option<bool>) -> Self {
+        let available_parallelism = std::thread::available_parallelism().map(|n| n.get()).ok();
+        let parallel_feature = cfg!(feature = "parallel");
+
+        let parallel = parallel_feature
+            && multi_threaded.unwrap_or_else(|| available_parallelism.unwrap_or(1) > 1);
+
+        BackendPlan {
+            backend: if parallel {
+                Backend::Parallel
+            } else {
+                Backend::Sequential
+            },
+            multi_threaded,
+            available_parallelism,
+            parallel_feature,
+        }
+    }
+}
+
+impl std::fmt::Display for BackendPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let backend = match self.backend {
+            Backend::Sequential => "sequential",
+            Backend::Parallel => "parallel",
+        };
 
-        *self = builder;
+        match (
+            self.parallel_feature,
+            self.multi_threaded,
+            self.available_parallelism,
+        ) {
+            (false, _, _) => write!(f, "{backend}: the `parallel` feature is disabled"),
+            (true, Some(multi_threaded), _) => {
+                write!(f, "{backend}: set with `multi_threaded({multi_threaded})`")
+            }
+            (true, None, Some(n)) if n > 1 => write!(f, "{backend}: {n} threads available"),
+            (true, None, Some(_)) => write!(f, "{backend}: only 1 thread available"),
+            (true, None, None) => {
+                write!(
+                    f,
+                    "{backend}: available parallelism could not be determined"
+                )
+            }
+        }
     }
 }
 
@@ -381,141 +1329,515 @@ impl<NodeId: U16orU32> GraphBuilder<NodeId> {
     /// Create a new GraphBuilder with the given number of nodes.
     #[inline]
     pub fn new(nodes_len: usize) -> Self {
+        Self::with_store(nodes_len)
+    }
+}
+
+impl<NodeId: U16orU32, S: EdgeStore<NodeId>> GraphBuilder<NodeId, S> {
+    /// Create a new GraphBuilder with the given number of nodes,
+    /// storing the edges of the sequential backend in the store `S`.
+    ///
+    /// ```
+    /// use bit_gossip::{
+    ///     graph::{store::CsrStore, GraphBuilder},
+    ///     Graph,
+    /// };
+    ///
+    /// let mut builder = GraphBuilder::<u16, CsrStore<u16>>::with_store(3);
+    /// builder.connect(0, 1);
+    /// builder.connect(1, 2);
+    ///
+    /// let graph: Graph<u16, CsrStore<u16>> = builder.build();
+    /// assert_eq!(graph.neighbor_to(0, 2), Some(1));
+    /// ```
+    #[inline]
+    pub fn with_store(nodes_len: usize) -> Self {
         GraphBuilder {
-            inner: GraphBuilderEnum::None,
+            inner: GraphBuilderEnum::new(nodes_len, BackendPlan::new(None).backend),
             multi_threaded: None,
-            nodes_len,
+            #[cfg(feature = "parallel")]
+            stable_order: false,
+            #[cfg(feature = "parallel")]
+            parallel_setup: false,
+            hub_min_degree: None,
         }
     }
 
+    /// Choose the parallel backend if `true`, or the sequential backend if `false`,
+    /// instead of choosing it from the available parallelism.
+    ///
+    /// If edges were already added, they are moved over to the chosen backend's builder.
     #[cfg(feature = "parallel")]
     #[inline]
     pub fn multi_threaded(mut self, multi_threaded: bool) -> Self {
         self.multi_threaded = Some(multi_threaded);
+        self.inner = match BackendPlan::new(self.multi_threaded).backend {
+            Backend::Parallel => GraphBuilderEnum::Parallel(self.inner.into_par()),
+            Backend::Sequential => GraphBuilderEnum::Sequential(self.inner.into_seq()),
+        };
+        self
+    }
+
+    /// Make the parallel build process the nodes of each gossip round in index order,
+    /// trading a little speed for reproducible intermediate states.
+    ///
+    /// This has no effect on the sequential backend, which is always in index order.
+    /// See [ParaGraphBuilder::stable_order](parallel::ParaGraphBuilder::stable_order).
+    #[cfg(feature = "parallel")]
+    #[inline]
+    pub fn stable_order(mut self, stable_order: bool) -> Self {
+        self.stable_order = stable_order;
+        self
+    }
+
+    /// Run the initial setup pass of the sequential build on multiple threads,
+    /// for when the sequential backend was chosen for its memory profile
+    /// but startup latency still matters.
+    ///
+    /// This has no effect on the parallel backend, which is parallel throughout.
+    /// See [SeqGraphBuilder::parallel_setup](sequential::SeqGraphBuilder::parallel_setup).
+    #[cfg(feature = "parallel")]
+    #[inline]
+    pub fn parallel_setup(mut self, parallel_setup: bool) -> Self {
+        self.parallel_setup = parallel_setup;
+        self
+    }
+
+    /// Summarize the edges of every node with at least `min_degree` neighbors once the graph is built,
+    /// to speed up queries from those nodes on star-like graphs.
+    ///
+    /// See [Graph::summarize_hubs].
+    #[inline]
+    pub fn hub_summaries(mut self, min_degree: usize) -> Self {
+        self.hub_min_degree = Some(min_degree);
         self
     }
 
+    /// Return the backend this builder will build with, and why it was chosen.
+    ///
+    /// Unless set with [multi_threaded](Self::multi_threaded),
+    /// the parallel backend is chosen when more than one thread is available.
+    pub fn planned_backend(&self) -> BackendPlan {
+        let mut plan = BackendPlan::new(self.multi_threaded);
+        plan.backend = self.inner.backend();
+        plan
+    }
+
     /// Resize the graph to the given number of nodes.
     ///
     /// All edges that are connected to nodes that are removed will also be removed.
     pub fn resize(&mut self, nodes_len: usize) {
-        if self.inner.is_none() {
-            self.inner.set_builder(self.nodes_len, self.multi_threaded);
-        }
-
         match &mut self.inner {
             GraphBuilderEnum::Sequential(builder) => builder.resize(nodes_len),
             #[cfg(feature = "parallel")]
             GraphBuilderEnum::Parallel(builder) => builder.resize(nodes_len),
-            GraphBuilderEnum::None => unreachable!(),
         }
     }
 
     /// Add an edge between node_a and node_b
     #[inline]
     pub fn connect(&mut self, a: NodeId, b: NodeId) {
-        if self.inner.is_none() {
-            self.inner.set_builder(self.nodes_len, self.multi_threaded);
-        }
-
         match &mut self.inner {
             GraphBuilderEnum::Sequential(builder) => builder.connect(a, b),
             #[cfg(feature = "parallel")]
             GraphBuilderEnum::Parallel(builder) => builder.connect(a, b),
-            GraphBuilderEnum::None => unreachable!(),
         }
     }
 
-    /// Remove an edge between node_a and node_b
+    /// Add an edge between node_a and node_b, with user data attached to it.
+    ///
+    /// The data is not used for pathfinding; read it back with [Graph::edge_data].
+    /// This is useful for things like door IDs or traversal animations.
+    ///
+    /// If the edge already exists, its data is replaced.
     #[inline]
-    pub fn disconnect(&mut self, a: NodeId, b: NodeId) {
-        if self.inner.is_none() {
-            self.inner.set_builder(self.nodes_len, self.multi_threaded);
+    pub fn connect_with_data(&mut self, a: NodeId, b: NodeId, data: u16) {
+        match &mut self.inner {
+            GraphBuilderEnum::Sequential(builder) => builder.connect_with_data(a, b, data),
+            #[cfg(feature = "parallel")]
+            GraphBuilderEnum::Parallel(builder) => builder.connect_with_data(a, b, data),
         }
+    }
 
+    /// Remove an edge between node_a and node_b
+    #[inline]
+    pub fn disconnect(&mut self, a: NodeId, b: NodeId) {
         match &mut self.inner {
             GraphBuilderEnum::Sequential(builder) => builder.disconnect(a, b),
             #[cfg(feature = "parallel")]
             GraphBuilderEnum::Parallel(builder) => builder.disconnect(a, b),
-            GraphBuilderEnum::None => unreachable!(),
         }
     }
 
     #[inline]
-    pub fn build(self) -> Graph<NodeId> {
-        let mut builder = self.inner;
-        if builder.is_none() {
-            builder.set_builder(self.nodes_len, self.multi_threaded);
-        }
+    pub fn build(self) -> Graph<NodeId, S> {
+        let hub_min_degree = self.hub_min_degree;
 
-        match builder {
+        let graph = match self.inner {
+            #[cfg(feature = "parallel")]
+            GraphBuilderEnum::Sequential(builder) => {
+                Graph::Sequential(builder.parallel_setup(self.parallel_setup).build())
+            }
+            #[cfg(not(feature = "parallel"))]
             GraphBuilderEnum::Sequential(builder) => Graph::Sequential(builder.build()),
             #[cfg(feature = "parallel")]
-            GraphBuilderEnum::Parallel(builder) => Graph::Parallel(builder.build()),
-            GraphBuilderEnum::None => unreachable!(),
+            GraphBuilderEnum::Parallel(builder) => {
+                Graph::Parallel(builder.stable_order(self.stable_order).build())
+            }
+        };
+
+        with_hubs(graph, hub_min_degree, Graph::summarize_hubs)
+    }
+
+    /// Build the graph, calling `yield_fn` after every `every_n_rounds` rounds of gossip.
+    ///
+    /// This lets a long build report progress, or check on other work, while it runs.
+    /// `every_n_rounds` of `0` never calls `yield_fn`.
+    ///
+    /// See [build_async](Self::build_async) to build inside an async task.
+    pub fn build_yielding(self, every_n_rounds: usize, yield_fn: impl FnMut()) -> Graph<NodeId, S> {
+        let hub_min_degree = self.hub_min_degree;
+
+        let graph = match self.inner {
+            #[cfg(feature = "parallel")]
+            GraphBuilderEnum::Sequential(builder) => Graph::Sequential(
+                builder
+                    .parallel_setup(self.parallel_setup)
+                    .build_yielding(every_n_rounds, yield_fn),
+            ),
+            #[cfg(not(feature = "parallel"))]
+            GraphBuilderEnum::Sequential(builder) => {
+                Graph::Sequential(builder.build_yielding(every_n_rounds, yield_fn))
+            }
+            #[cfg(feature = "parallel")]
+            GraphBuilderEnum::Parallel(builder) => Graph::Parallel(
+                builder
+                    .stable_order(self.stable_order)
+                    .build_yielding(every_n_rounds, yield_fn),
+            ),
+        };
+
+        with_hubs(graph, hub_min_degree, Graph::summarize_hubs)
+    }
+
+    /// Build the graph inside an async task,
+    /// yielding to the executor after every `every_n_rounds` rounds of gossip.
+    ///
+    /// A build is CPU-bound and takes up to seconds for large graphs;
+    /// yielding lets the other tasks of the executor's thread run in between,
+    /// e.g. to keep a game engine's task pool responsive without spawning a dedicated thread.
+    /// `every_n_rounds` of `0` never yields.
+    ///
+    /// The number of rounds is about the longest shortest path in the graph,
+    /// so yielding every few rounds is usually frequent enough.
+    ///
+    /// ```
+    /// use bit_gossip::Graph;
+    ///
+    /// # fn spawn<F: std::future::Future + Send>(_: F) {}
+    /// let mut builder = Graph::builder(100);
+    /// for i in 0..99u16 {
+    ///     builder.connect(i, i + 1);
+    /// }
+    ///
+    /// // e.g. with bevy: `AsyncComputeTaskPool::get().spawn(...)`
+    /// spawn(async move {
+    ///     let graph = builder.build_async(4).await;
+    ///     assert_eq!(graph.neighbor_to(0, 99), Some(1));
+    /// });
+    /// ```
+    pub async fn build_async(self, every_n_rounds: usize) -> Graph<NodeId, S> {
+        let hub_min_degree = self.hub_min_degree;
+
+        let graph = match self.inner {
+            #[cfg(feature = "parallel")]
+            GraphBuilderEnum::Sequential(builder) => Graph::Sequential(
+                builder
+                    .parallel_setup(self.parallel_setup)
+                    .build_async(every_n_rounds)
+                    .await,
+            ),
+            #[cfg(not(feature = "parallel"))]
+            GraphBuilderEnum::Sequential(builder) => {
+                Graph::Sequential(builder.build_async(every_n_rounds).await)
+            }
+            #[cfg(feature = "parallel")]
+            GraphBuilderEnum::Parallel(builder) => Graph::Parallel(
+                builder
+                    .stable_order(self.stable_order)
+                    .build_async(every_n_rounds)
+                    .await,
+            ),
+        };
+
+        with_hubs(graph, hub_min_degree, Graph::summarize_hubs)
+    }
+
+    /// Build the graph, and return it with the statistics of the build.
+    ///
+    /// With the `track-alloc` feature, the statistics include the allocations made during the build;
+    /// see the [stats] module.
+    ///
+    /// ```
+    /// use bit_gossip::Graph;
+    ///
+    /// let mut builder = Graph::builder(3);
+    /// builder.connect(0u16, 1);
+    /// builder.connect(1, 2);
+    ///
+    /// let (graph, stats) = builder.build_with_stats();
+    /// assert_eq!(graph.neighbor_to(0, 2), Some(1));
+    /// println!("built in {:?}", stats.duration);
+    /// ```
+    pub fn build_with_stats(self) -> (Graph<NodeId, S>, stats::BuildStats) {
+        stats::measure(|| self.build())
+    }
+
+    /// Build the graph with the sequential backend,
+    /// and return the concrete [SeqGraph](sequential::SeqGraph) instead of the [Graph] enum.
+    ///
+    /// The backend choice of [multi_threaded](Self::multi_threaded) is ignored;
+    /// if the edges were already added to a parallel builder, they are moved to a sequential one first.
+    pub fn build_seq(self) -> sequential::SeqGraph<NodeId, S> {
+        let builder = self.inner.into_seq();
+
+        #[cfg(feature = "parallel")]
+        let builder = builder.parallel_setup(self.parallel_setup);
+
+        with_hubs(
+            builder.build(),
+            self.hub_min_degree,
+            sequential::SeqGraph::summarize_hubs,
+        )
+    }
+
+    /// Build the graph with the parallel backend,
+    /// and return the concrete [ParaGraph](parallel::ParaGraph) instead of the [Graph] enum.
+    ///
+    /// The backend choice of [multi_threaded](Self::multi_threaded) is ignored;
+    /// if the edges were already added to a sequential builder, they are moved to a parallel one first.
+    #[cfg(feature = "parallel")]
+    pub fn build_par(self) -> parallel::ParaGraph<NodeId> {
+        let graph = self
+            .inner
+            .into_par()
+            .stable_order(self.stable_order)
+            .build();
+
+        with_hubs(
+            graph,
+            self.hub_min_degree,
+            parallel::ParaGraph::summarize_hubs,
+        )
+    }
+
+    /// Build the graph in `shards` shards of destinations, each written to a file,
+    /// and return a [ShardedGraph](sharded::ShardedGraph) that loads them as queries need them.
+    ///
+    /// This is for maps whose paths don't fit in memory:
+    /// only one shard is in memory at a time during the build, and a few during queries.
+    /// The files are written to a new directory in the system's temporary directory,
+    /// and removed when the graph is dropped; see [build_sharded_in](Self::build_sharded_in)
+    /// to choose the directory.
+    ///
+    /// The shards are computed with a breadth-first search from every destination,
+    /// instead of the gossip of [build](Self::build); see the [sharded] module.
+    ///
+    /// Returns an error if a shard file can't be written.
+    pub fn build_sharded(self, shards: usize) -> std::io::Result<sharded::ShardedGraph<NodeId>> {
+        let dir = sharded::temp_dir()?;
+        let graph = sharded::ShardedGraph::build(self.into_neighbors(), shards, &dir, true);
+
+        if graph.is_err() {
+            let _ = std::fs::remove_dir(&dir);
         }
+        graph
+    }
+
+    /// Same as [build_sharded](Self::build_sharded), but writes the shard files to `dir`,
+    /// which is created if it doesn't exist.
+    ///
+    /// The files are named `shard-{index}.bin`, and existing files of the same names are overwritten.
+    /// They are removed when the graph is dropped, but `dir` itself is kept.
+    pub fn build_sharded_in(
+        self,
+        dir: impl AsRef<std::path::Path>,
+        shards: usize,
+    ) -> std::io::Result<sharded::ShardedGraph<NodeId>> {
+        std::fs::create_dir_all(dir.as_ref())?;
+        sharded::ShardedGraph::build(self.into_neighbors(), shards, dir.as_ref(), false)
+    }
+
+    /// Return the canonicalized neighbor lists of the builder.
+    fn into_neighbors(self) -> Vec<Vec<NodeId>> {
+        let mut nodes = self.inner.into_seq().nodes;
+        nodes.canonicalize();
+        nodes.inner
     }
 
     /// Return the number of nodes in this graph.
     #[inline]
     pub fn nodes_len(&self) -> usize {
-        match self {
-            GraphBuilder {
-                inner: GraphBuilderEnum::Sequential(builder),
-                ..
-            } => builder.nodes_len(),
+        match &self.inner {
+            GraphBuilderEnum::Sequential(builder) => builder.nodes_len(),
             #[cfg(feature = "parallel")]
-            GraphBuilder {
-                inner: GraphBuilderEnum::Parallel(builder),
-                ..
-            } => builder.nodes_len(),
-            GraphBuilder {
-                inner: GraphBuilderEnum::None,
-                nodes_len,
-                ..
-            } => *nodes_len,
+            GraphBuilderEnum::Parallel(builder) => builder.nodes_len(),
         }
     }
 
     /// Return the number of edges in this graph.
     #[inline]
     pub fn edges_len(&self) -> usize {
-        match self {
-            GraphBuilder {
-                inner: GraphBuilderEnum::Sequential(builder),
-                ..
-            } => builder.edges_len(),
+        match &self.inner {
+            GraphBuilderEnum::Sequential(builder) => builder.edges_len(),
+            #[cfg(feature = "parallel")]
+            GraphBuilderEnum::Parallel(builder) => builder.edges_len(),
+        }
+    }
+
+    /// Return a hash of this graph's topology: the number of nodes and the set of edges.
+    ///
+    /// The hash does not depend on the order edges were added in or on the internal layout,
+    /// so it can be used as a cache key to check whether a map has changed since it was last built.
+    /// The same topology gives the same hash for every graph type and builder.
+    #[inline]
+    pub fn topology_hash(&self) -> u64 {
+        match &self.inner {
+            GraphBuilderEnum::Sequential(builder) => builder.topology_hash(),
             #[cfg(feature = "parallel")]
-            GraphBuilder {
-                inner: GraphBuilderEnum::Parallel(builder),
-                ..
-            } => builder.edges_len(),
-            GraphBuilder {
-                inner: GraphBuilderEnum::None,
-                ..
-            } => 0,
+            GraphBuilderEnum::Parallel(builder) => builder.topology_hash(),
         }
     }
 
     /// Return the neighbors of the given node.
     #[inline]
     pub fn neighbors(&self, node: NodeId) -> &[NodeId] {
-        match self {
-            GraphBuilder {
-                inner: GraphBuilderEnum::Sequential(builder),
-                ..
-            } => builder.neighbors(node),
+        match &self.inner {
+            GraphBuilderEnum::Sequential(builder) => builder.neighbors(node),
+            #[cfg(feature = "parallel")]
+            GraphBuilderEnum::Parallel(builder) => builder.neighbors(node),
+        }
+    }
+
+    /// Return the user data attached to the edge between node_a and node_b.
+    #[inline]
+    pub fn edge_data(&self, a: NodeId, b: NodeId) -> Option<u16> {
+        match &self.inner {
+            GraphBuilderEnum::Sequential(builder) => builder.edge_data(a, b),
             #[cfg(feature = "parallel")]
-            GraphBuilder {
-                inner: GraphBuilderEnum::Parallel(builder),
-                ..
-            } => builder.neighbors(node),
-            GraphBuilder {
-                inner: GraphBuilderEnum::None,
-                ..
-            } => &[],
+            GraphBuilderEnum::Parallel(builder) => builder.edge_data(a, b),
+        }
+    }
+}
+
+/// Call `f` with each edge of the given neighbor lists once, with the lower node first,
+/// and the edge's user data.
+#[cfg(feature = "parallel")]
+fn for_each_edge<NodeId: U16orU32>(
+    neighbors: &[Vec<NodeId>],
+    data: &[Vec<u16>],
+    mut f: impl FnMut(NodeId, NodeId, u16),
+) {
+    for (a, (a_neighbors, a_data)) in neighbors.iter().zip(data).enumerate() {
+        for (&b, &data) in a_neighbors.iter().zip(a_data) {
+            if a < b.as_usize() {
+                f(NodeId::from_usize(a), b, data);
+            }
+        }
+    }
+}
+
+/// Summarize the hubs of a freshly built graph with `summarize`,
+/// if [hub_summaries](GraphBuilder::hub_summaries) was set.
+fn with_hubs<G>(mut graph: G, min_degree: Option<usize>, summarize: fn(&mut G, usize)) -> G {
+    if let Some(min_degree) = min_degree {
+        summarize(&mut graph, min_degree);
+    }
+    graph
+}
+
+/// Return a neighbor of `curr` to move to toward `dest` without precomputed paths,
+/// found with a breadth-first search of at most `hop_budget` hops from `curr`, and at least one.
+///
+/// If `dest` is within the budget, this is the first hop of a shortest path to it.
+/// If the search runs out of budget first, this is the first hop toward the searched node with
+/// the lowest estimate of the `heuristic`, or toward the last node reached without one.
+/// `None` is returned if `curr` is `dest`, or if the search reached every node it could without
+/// finding `dest`, so there is no path.
+pub(crate) fn greedy_neighbor_to<'a, NodeId: U16orU32 + 'a>(
+    neighbors: impl Fn(NodeId) -> &'a [NodeId],
+    curr: NodeId,
+    dest: NodeId,
+    hop_budget: usize,
+    heuristic: Option<&dyn Fn(NodeId) -> u64>,
+) -> Option<NodeId> {
+    if curr == dest {
+        return None;
+    }
+
+    // key: node reached so far; value: the neighbor of `curr` it was reached through
+    let mut first_hops = std::collections::HashMap::new();
+    first_hops.insert(curr.as_usize(), curr);
+
+    // the hop toward the searched node with the lowest estimate, or the last one reached
+    let mut best: Option<(u64, NodeId)> = None;
+    let mut frontier = vec![curr];
+
+    for _ in 0..hop_budget.max(1) {
+        let mut next_frontier = Vec::new();
+
+        for &node in &frontier {
+            let hop = first_hops[&node.as_usize()];
+
+            for &neighbor in neighbors(node) {
+                if first_hops.contains_key(&neighbor.as_usize()) {
+                    continue;
+                }
+
+                let hop = if node == curr { neighbor } else { hop };
+                if neighbor == dest {
+                    return Some(hop);
+                }
+
+                let estimate = heuristic.map_or(0, |h| h(neighbor));
+                if heuristic.is_none() || best.map_or(true, |(lowest, _)| estimate < lowest) {
+                    best = Some((estimate, hop));
+                }
+
+                first_hops.insert(neighbor.as_usize(), hop);
+                next_frontier.push(neighbor);
+            }
+        }
+
+        // every node connected to `curr` was searched
+        if next_frontier.is_empty() {
+            return None;
+        }
+        frontier = next_frontier;
+    }
+
+    best.map(|(_, hop)| hop)
+}
+
+/// Return the distance of every node to `dest`, or `u32::MAX` if it cannot reach `dest`.
+fn bfs_distances<NodeId: U16orU32>(neighbors: &[Vec<NodeId>], dest: usize) -> Vec<u32> {
+    let mut dist = vec![u32::MAX; neighbors.len()];
+    let mut queue = std::collections::VecDeque::new();
+
+    dist[dest] = 0;
+    queue.push_back(dest);
+
+    while let Some(node) = queue.pop_front() {
+        for &neighbor in &neighbors[node] {
+            let neighbor = neighbor.as_usize();
+            if dist[neighbor] == u32::MAX {
+                dist[neighbor] = dist[node] + 1;
+                queue.push_back(neighbor);
+            }
         }
     }
+
+    dist
 }
 
 /// Either u16 or u32.
@@ -537,7 +1859,16 @@ mod sealed {
     use super::*;
 
     pub trait Sealed:
-        Ord + Eq + Clone + Copy + std::hash::Hash + Send + Sync + fmt::Display + fmt::Debug
+        Ord
+        + Eq
+        + Clone
+        + Copy
+        + std::hash::Hash
+        + Send
+        + Sync
+        + Into<u64>
+        + fmt::Display
+        + fmt::Debug
     {
     }
     impl Sealed for u16 {}
@@ -576,6 +1907,980 @@ mod sealed {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_topology_hash() {
+        let edges = [(0u16, 1u16), (1, 2), (2, 3), (3, 0), (1, 3)];
+
+        let mut forward = Graph::builder(4);
+        for (a, b) in edges {
+            forward.connect(a, b);
+        }
+
+        // same edges, reversed order and flipped endpoints
+        let mut backward = Graph::builder(4);
+        for (a, b) in edges.into_iter().rev() {
+            backward.connect(b, a);
+        }
+
+        let hash = forward.topology_hash();
+        assert_eq!(backward.topology_hash(), hash);
+        assert_eq!(forward.build().topology_hash(), hash);
+        assert_eq!(backward.build().topology_hash(), hash);
+
+        let mut prim = crate::Graph16::builder(4);
+        for (a, b) in edges {
+            prim.connect(a as u8, b as u8);
+        }
+        assert_eq!(prim.build().topology_hash(), hash);
+
+        // different edges or node count change the hash
+        let mut builder = Graph::builder(4);
+        for (a, b) in &edges[1..] {
+            builder.connect(*a, *b);
+        }
+        assert_ne!(builder.topology_hash(), hash);
+
+        let mut builder = Graph::builder(5);
+        for (a, b) in edges {
+            builder.connect(a, b);
+        }
+        assert_ne!(builder.topology_hash(), hash);
+    }
+
+    #[test]
+    fn test_min_cut_estimate() {
+        // two open 3x3 rooms, 0..9 and 9..18, joined by a single door 5 -- 12;
+        // node 18 is isolated
+        let mut builder = Graph::builder(19);
+        for room in [0u16, 9] {
+            for i in 0..9 {
+                if i % 3 != 2 {
+                    builder.connect(room + i, room + i + 1);
+                }
+                if i < 6 {
+                    builder.connect(room + i, room + i + 3);
+                }
+            }
+        }
+        builder.connect(5, 12);
+        let graph = builder.build();
+
+        assert_eq!(graph.min_cut_estimate(0, 8, 10), 2);
+        assert_eq!(graph.min_cut_estimate(8, 0, 10), 2);
+        assert_eq!(graph.min_cut_estimate(0, 17, 10), 1);
+        assert_eq!(graph.min_cut_estimate(0, 0, 10), 0);
+        assert_eq!(graph.min_cut_estimate(0, 18, 10), 0);
+        assert_eq!(graph.min_cut_estimate(0, 8, 0), 0);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_build_yielding() {
+        use std::{
+            future::Future,
+            sync::{
+                atomic::{AtomicUsize, Ordering},
+                Arc,
+            },
+            task::{Context, Poll, Wake, Waker},
+        };
+
+        struct CountWakes(AtomicUsize);
+
+        impl Wake for CountWakes {
+            fn wake(self: Arc<Self>) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        // a corridor takes a round per node to build
+        let corridor = |multi_threaded: bool| {
+            let mut builder = Graph::builder(40).multi_threaded(multi_threaded);
+            for i in 0..39u16 {
+                builder.connect(i, i + 1);
+            }
+            builder
+        };
+
+        for multi_threaded in [false, true] {
+            let expected = corridor(multi_threaded).build();
+
+            let mut calls = 0;
+            let graph = corridor(multi_threaded).build_yielding(5, || calls += 1);
+            assert!(calls >= 5, "{calls} calls");
+            for dest in 0..40 {
+                assert_eq!(graph.neighbor_to(0, dest), expected.neighbor_to(0, dest));
+            }
+
+            let mut never = 0;
+            corridor(multi_threaded).build_yielding(0, || never += 1);
+            assert_eq!(never, 0);
+
+            // every pending poll wakes the task, so an executor would poll it again
+            let wakes = Arc::new(CountWakes(AtomicUsize::new(0)));
+            let waker = Waker::from(wakes.clone());
+            let mut cx = Context::from_waker(&waker);
+            let mut fut = Box::pin(corridor(multi_threaded).build_async(5));
+
+            let mut pending = 0;
+            let graph = loop {
+                match fut.as_mut().poll(&mut cx) {
+                    Poll::Ready(graph) => break graph,
+                    Poll::Pending => pending += 1,
+                }
+            };
+            assert_eq!(pending, calls);
+            assert_eq!(wakes.0.load(Ordering::Relaxed), pending);
+            assert_eq!(graph.path_to(0, 39).count(), 40);
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_fork_shares_unchanged_edges() {
+        for multi_threaded in [false, true] {
+            // two separate 4x4 grids: nodes 0..16 and 16..32
+            let mut builder = Graph::builder(32).multi_threaded(multi_threaded);
+            for offset in [0, 16] {
+                for i in 0..16 {
+                    if i % 4 != 3 {
+                        builder.connect(offset + i, offset + i + 1);
+                    }
+                    if i < 12 {
+                        builder.connect(offset + i, offset + i + 4);
+                    }
+                }
+            }
+            let graph = builder.build();
+
+            // a variant with a passage closed in the second grid only
+            let mut builder = graph.fork().into_builder();
+            builder.disconnect(16, 17);
+            let variant = builder.build();
+
+            let shared = |a: u16, b: u16| match (&graph, &variant) {
+                (Graph::Sequential(graph), Graph::Sequential(variant)) => std::ptr::eq(
+                    &*graph.edges.get((a, b)).unwrap(),
+                    &*variant.edges.get((a, b)).unwrap(),
+                ),
+                (Graph::Parallel(graph), Graph::Parallel(variant)) => {
+                    std::sync::Arc::ptr_eq(&graph.edges[&(a, b)], &variant.edges[&(a, b)])
+                }
+                _ => unreachable!(),
+            };
+
+            // the first grid's shortest paths did not change, so its bits are still shared
+            let edges = graph.clone().into_seq().edges;
+            assert!(edges
+                .iter()
+                .filter(|((a, _), _)| *a < 16)
+                .all(|((a, b), _)| shared(a, b)));
+            assert!(variant
+                .clone()
+                .into_seq()
+                .edges
+                .iter()
+                .any(|((a, b), _)| !shared(a, b)));
+
+            assert_eq!(graph.neighbor_to(16, 17), Some(17));
+            assert_eq!(variant.neighbor_to(16, 17), Some(20));
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_into_builder_keeps_stable_order() {
+        let mut builder = Graph::builder(4).multi_threaded(true).stable_order(true);
+        builder.connect(0u16, 1);
+        builder.connect(1, 2);
+        let graph = builder.build();
+
+        let Graph::Parallel(par) = &graph else {
+            unreachable!()
+        };
+        assert!(par.stable_order);
+
+        // the setting survives a rebuild after edits
+        let mut builder = graph.into_builder();
+        assert!(builder.stable_order);
+        builder.connect(2, 3);
+        let graph = builder.build();
+
+        let Graph::Parallel(par) = &graph else {
+            unreachable!()
+        };
+        assert!(par.stable_order);
+        assert_eq!(graph.neighbor_to(0, 3), Some(1));
+
+        // a graph built without it is rebuilt without it
+        let mut builder = Graph::builder(4).multi_threaded(true);
+        builder.connect(0u16, 1);
+        assert!(!builder.build().into_builder().stable_order);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_into_builder_keeps_parallel_setup() {
+        let mut builder = Graph::builder(4).multi_threaded(false).parallel_setup(true);
+        builder.connect(0u16, 1);
+        builder.connect(1, 2);
+        let graph = builder.build();
+
+        let Graph::Sequential(seq) = &graph else {
+            unreachable!()
+        };
+        assert!(seq.parallel_setup);
+
+        // the setting survives a rebuild after edits
+        let mut builder = graph.into_builder();
+        assert!(builder.parallel_setup);
+        builder.connect(2, 3);
+        let graph = builder.build();
+
+        let Graph::Sequential(seq) = &graph else {
+            unreachable!()
+        };
+        assert!(seq.parallel_setup);
+        assert_eq!(graph.neighbor_to(0, 3), Some(1));
+
+        // a graph built without it is rebuilt without it
+        let mut builder = Graph::builder(4).multi_threaded(false);
+        builder.connect(0u16, 1);
+        assert!(!builder.build().into_builder().parallel_setup);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_clone_shares_graph() {
+        for multi_threaded in [false, true] {
+            let mut builder = Graph::builder(4).multi_threaded(multi_threaded);
+            builder.connect(0u16, 1);
+            builder.connect(1, 2);
+            builder.connect(2, 3);
+            let graph = builder.build();
+            let copy = graph.clone();
+
+            match (&graph, &copy) {
+                (Graph::Sequential(a), Graph::Sequential(b)) => {
+                    assert!(std::sync::Arc::ptr_eq(&a.edges, &b.edges));
+                }
+                (Graph::Parallel(a), Graph::Parallel(b)) => {
+                    assert!(std::sync::Arc::ptr_eq(&a.edges, &b.edges));
+                }
+                _ => unreachable!(),
+            }
+
+            // editing a shared graph leaves its clones untouched
+            let mut builder = copy.into_builder();
+            builder.disconnect(1, 2);
+            let edited = builder.build();
+
+            assert_eq!(graph.neighbors(1), &[0, 2]);
+            assert_eq!(graph.path_to(0, 3).collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+            assert_eq!(edited.neighbors(1), &[0]);
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_planned_backend() {
+        // automatic choice: the plan is what the build ends up with
+        let mut builder = Graph::<u16>::builder(4);
+        let plan = builder.planned_backend();
+        assert_eq!(plan.multi_threaded, None);
+        builder.connect(0, 1);
+        assert_eq!(builder.planned_backend(), plan);
+        assert_eq!(builder.build().backend(), plan.backend);
+
+        for (multi_threaded, backend) in [(false, Backend::Sequential), (true, Backend::Parallel)] {
+            let builder = Graph::<u16>::builder(4).multi_threaded(multi_threaded);
+            assert_eq!(builder.planned_backend().backend, backend);
+
+            let graph = builder.build();
+            assert_eq!(graph.backend(), backend);
+            assert_eq!(graph.into_builder().planned_backend().backend, backend);
+        }
+    }
+
+    #[cfg(any(debug_assertions, feature = "strict-checks"))]
+    #[test]
+    #[should_panic(expected = "neighbor_to: `dest` 10 is out of range for a graph of 4 nodes")]
+    fn test_check_nodes() {
+        let graph = crate::build([(0u16, 1), (1, 2), (2, 3)], 4);
+        assert_eq!(graph.neighbor_to(0, 3), Some(1));
+
+        graph.neighbor_to(0, 10);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_multi_threaded_after_connect() {
+        for (first, second) in [(false, true), (true, false)] {
+            let mut builder = Graph::<u16>::builder(3).multi_threaded(first);
+            builder.connect_with_data(0, 1, 7);
+            builder.connect(1, 2);
+
+            // the edges move over to the other backend's builder
+            let builder = builder.multi_threaded(second);
+            assert_eq!(builder.edges_len(), 2);
+            assert_eq!(builder.edge_data(1, 0), Some(7));
+
+            let graph = builder.build();
+            assert_eq!(graph.backend() == Backend::Parallel, second);
+            assert_eq!(graph.path_to(0, 2).collect::<Vec<_>>(), vec![0, 1, 2]);
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_edge_data() {
+        for multi_threaded in [false, true] {
+            let mut builder = Graph::builder(5).multi_threaded(multi_threaded);
+            builder.connect_with_data(0u16, 1, 10);
+            builder.connect_with_data(1, 2, 12);
+            builder.connect_with_data(2, 3, 23);
+            builder.connect_with_data(3, 4, 34);
+            builder.connect(0, 2);
+            // reconnecting keeps the data; connecting with data replaces it
+            builder.connect(1, 0);
+            builder.connect_with_data(3, 2, 32);
+            builder.disconnect(1, 2);
+            assert_eq!(builder.neighbors(0), &[1, 2]);
+
+            let graph = builder.build();
+            assert_eq!(graph.edge_data(1, 0), Some(10));
+            assert_eq!(graph.edge_data(0, 2), Some(0));
+            assert_eq!(graph.edge_data(2, 3), Some(32));
+            assert_eq!(graph.edge_data(1, 2), None);
+            assert_eq!(graph.path_to(1, 3).collect::<Vec<_>>(), vec![1, 0, 2, 3]);
+
+            // the data stays with its edge through edits and resizes
+            let mut builder = graph.into_builder();
+            builder.disconnect(0, 1);
+            builder.resize(4);
+            let graph = builder.build();
+            assert_eq!(graph.edge_data(0, 2), Some(0));
+            assert_eq!(graph.edge_data(3, 2), Some(32));
+            assert_eq!(graph.edge_data(3, 4), None);
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_retain_dests() {
+        for multi_threaded in [false, true] {
+            // 10x10 grid
+            let mut builder = Graph::builder(100).multi_threaded(multi_threaded);
+            for y in 0..10u16 {
+                for x in 0..10 {
+                    if x < 9 {
+                        builder.connect(y * 10 + x, y * 10 + x + 1);
+                    }
+                    if y < 9 {
+                        builder.connect(y * 10 + x, (y + 1) * 10 + x);
+                    }
+                }
+            }
+            let graph = builder.build();
+
+            let mut dests = BitVec::ZERO;
+            for dest in [7, 42, 64, 99] {
+                dests.set_bit(dest, true);
+            }
+            let mut retained = graph.fork();
+            retained.retain_dests(&dests);
+
+            // narrowing again keeps only the intersection
+            let mut narrowed = retained.clone();
+            let mut dests = BitVec::ZERO;
+            for dest in [42, 99, 50] {
+                dests.set_bit(dest, true);
+            }
+            narrowed.retain_dests(&dests);
+
+            for curr in 0..100u16 {
+                for dest in 0..100u16 {
+                    let expected = graph.neighbors_to(curr, dest).collect::<Vec<_>>();
+
+                    let actual = retained.neighbors_to(curr, dest).collect::<Vec<_>>();
+                    if [7, 42, 64, 99].contains(&dest) {
+                        assert_eq!(actual, expected, "{curr} -> {dest}");
+                    } else {
+                        assert!(actual.is_empty(), "{curr} -> {dest}");
+                    }
+
+                    let actual = narrowed.neighbors_to(curr, dest).collect::<Vec<_>>();
+                    if [42, 99].contains(&dest) {
+                        assert_eq!(actual, expected, "{curr} -> {dest}");
+                    } else {
+                        assert!(actual.is_empty(), "{curr} -> {dest}");
+                    }
+                }
+            }
+
+            // rebuilding brings back every destination
+            let rebuilt = narrowed.into_builder().build();
+            for curr in 0..100u16 {
+                for dest in 0..100u16 {
+                    assert_eq!(
+                        rebuilt.neighbors_to(curr, dest).collect::<Vec<_>>(),
+                        graph.neighbors_to(curr, dest).collect::<Vec<_>>(),
+                        "{curr} -> {dest}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_build_seq_and_par() {
+        for multi_threaded in [false, true] {
+            let builder = || {
+                let mut builder = Graph::builder(4).multi_threaded(multi_threaded);
+                builder.connect_with_data(0u16, 1, 1);
+                builder.connect(1, 2);
+                builder.connect_with_data(2, 3, 23);
+                builder
+            };
+
+            let seq: sequential::SeqGraph<u16> = builder().build_seq();
+            let par: parallel::ParaGraph<u16> = builder().build_par();
+
+            for graph in [Graph::Sequential(seq), Graph::Parallel(par)] {
+                assert_eq!(graph.path_to(0, 3).collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+                assert_eq!(graph.edge_data(0, 1), Some(1));
+                assert_eq!(graph.edge_data(3, 2), Some(23));
+                assert_eq!(graph.edges_len(), 3);
+            }
+        }
+
+        // no edges added yet
+        assert_eq!(Graph::<u16>::builder(3).build_par().nodes_len(), 3);
+        assert_eq!(Graph::<u16>::builder(3).build_seq().nodes_len(), 3);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_into_seq_and_par() {
+        // 5x5 grid with a hole in the middle
+        let mut builder = Graph::<u16>::builder(25);
+        for y in 0..5 {
+            for x in 0..5 {
+                let node = y * 5 + x;
+                if x < 4 && node != 12 && node + 1 != 12 {
+                    builder.connect_with_data(node, node + 1, node);
+                }
+                if y < 4 && node != 12 && node + 5 != 12 {
+                    builder.connect(node, node + 5);
+                }
+            }
+        }
+        let seq = builder.build_seq();
+
+        let hops = |graph: &Graph<u16>| {
+            let mut hops = Vec::new();
+            for curr in 0..25 {
+                for dest in 0..25 {
+                    hops.push(graph.neighbors_to(curr, dest).collect::<Vec<_>>());
+                }
+            }
+            hops
+        };
+        let expected = hops(&Graph::Sequential(seq.clone()));
+
+        let par: parallel::ParaGraph<u16> = seq.into();
+        let par = Graph::Parallel(par);
+        assert_eq!(hops(&par), expected);
+        assert_eq!(par.edge_data(3, 4), Some(3));
+
+        let mut seq = Graph::Sequential(par.into_seq());
+        assert_eq!(hops(&seq), expected);
+        assert_eq!(seq.edge_data(3, 4), Some(3));
+
+        // retained destinations are carried over
+        let mut dests = BitVec::ZERO;
+        dests.set_bit(24, true);
+        seq.retain_dests(&dests);
+        let par = Graph::<u16>::Parallel(seq.into_par());
+        assert_eq!(par.path_to(0, 24).count(), 9);
+        assert_eq!(par.neighbor_to(24, 0), None);
+        let seq = Graph::Sequential(par.into_seq());
+        assert_eq!(seq.path_to(0, 24).count(), 9);
+        assert_eq!(seq.neighbor_to(24, 0), None);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_retarget_after_edits() {
+        // 6x6 grid; edits keep it a grid, so no edge ties in distance to a destination
+        let (w, h) = (6u16, 6u16);
+        let mut edges = Vec::new();
+        for y in 0..h {
+            for x in 0..w {
+                let i = y * w + x;
+                if x < w - 1 {
+                    edges.push((i, i + 1));
+                }
+                if y < h - 1 {
+                    edges.push((i, i + w));
+                }
+            }
+        }
+
+        let edits = [
+            // wall off the corner 0, then open a passage elsewhere
+            EdgeEdit::Disconnect(0, 1),
+            EdgeEdit::Disconnect(6, 0),
+            EdgeEdit::Disconnect(14, 15),
+            EdgeEdit::Disconnect(20, 21),
+            EdgeEdit::Connect(0, 1),
+        ];
+
+        for (multi_threaded, retain) in [(false, false), (true, false), (false, true), (true, true)]
+        {
+            let mut builder = Graph::builder((w * h) as usize).multi_threaded(multi_threaded);
+            for &(a, b) in &edges {
+                builder.connect(a, b);
+            }
+            let mut graph = builder.build();
+
+            let mut dests = BitVec::ZERO;
+            for dest in [0, 15, 35] {
+                dests.set_bit(dest, true);
+            }
+            if retain {
+                graph.retain_dests(&dests);
+            }
+
+            graph.retarget_after_edits(&edits, &dests);
+            assert_eq!(graph.edges_len(), edges.len() - 3);
+            assert_eq!(graph.neighbors(0), &[1]);
+
+            let neighbors = (0..w * h)
+                .map(|node| graph.neighbors(node).to_vec())
+                .collect::<Vec<_>>();
+
+            for dest in [0u16, 15, 35] {
+                let dist = bfs_distances(&neighbors, dest as usize);
+
+                for curr in (0..w * h).filter(|&curr| curr != dest) {
+                    let mut expected = graph
+                        .neighbors(curr)
+                        .iter()
+                        .copied()
+                        .filter(|&n| dist[n as usize] + 1 == dist[curr as usize])
+                        .collect::<Vec<_>>();
+                    let mut actual = graph.neighbors_to(curr, dest).collect::<Vec<_>>();
+                    expected.sort();
+                    actual.sort();
+                    assert_eq!(actual, expected, "{curr} -> {dest}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_diff_flow() {
+        // two corridors 0..5 and 5..10, and the same with a door between 4 and 5
+        let mut builder = Graph::<u16>::builder(10);
+        for i in (0..4).chain(5..9) {
+            builder.connect(i, i + 1);
+        }
+        let closed = builder.build();
+
+        let mut builder = closed.clone().into_builder();
+        builder.connect(4, 5);
+        let open = builder.build();
+
+        assert!(closed.diff_flow(0, &closed).is_empty());
+        assert_eq!(closed.diff_flow(0, &open), vec![5, 6, 7, 8, 9]);
+        assert_eq!(open.diff_flow(0, &closed), vec![5, 6, 7, 8, 9]);
+        assert_eq!(closed.diff_flow(9, &open), vec![0, 1, 2, 3, 4]);
+
+        // extra nodes without edges have no path in either graph
+        let mut builder = Graph::<u16>::builder(12);
+        for i in 0..9 {
+            builder.connect(i, i + 1);
+        }
+        let bigger = builder.build();
+        assert!(open.diff_flow(0, &bigger).is_empty());
+        assert!(bigger.diff_flow(11, &open).is_empty());
+
+        // a ring with a shortcut through the middle
+        let mut builder = Graph::<u16>::builder(9);
+        for i in 0..8 {
+            builder.connect(i, (i + 1) % 8);
+        }
+        let ring = builder.build();
+        let mut builder = ring.clone().into_builder();
+        builder.connect(0, 8);
+        builder.connect(8, 4);
+        let shortcut = builder.build();
+
+        for dest in 0..8 {
+            let expected = (0..9)
+                .filter(|&n| ring.neighbor_to(n, dest) != shortcut.neighbor_to(n, dest))
+                .collect::<Vec<_>>();
+            assert_eq!(ring.diff_flow(dest, &shortcut), expected);
+        }
+    }
+
+    #[test]
+    fn test_shortest_path_tree() {
+        // 5x5 grid with a wall in the middle column, open at the bottom, and an isolated corner
+        let (w, h) = (5u16, 5u16);
+        let wall = |x: u16, y: u16| x == 2 && y < 4;
+        let mut builder = Graph::<u16>::builder((w * h) as usize);
+        let mut neighbors = vec![Vec::new(); (w * h) as usize];
+        for y in 0..h {
+            for x in 0..w {
+                let node = y * w + x;
+                if wall(x, y) || node == 4 {
+                    continue;
+                }
+                if x + 1 < w && !wall(x + 1, y) && node + 1 != 4 {
+                    builder.connect(node, node + 1);
+                    neighbors[node as usize].push(node + 1);
+                    neighbors[node as usize + 1].push(node);
+                }
+                if y + 1 < h && !wall(x, y + 1) {
+                    builder.connect(node, node + w);
+                    neighbors[node as usize].push(node + w);
+                    neighbors[(node + w) as usize].push(node);
+                }
+            }
+        }
+        let graph = builder.build();
+
+        for root in [0, 12, 20, 24] {
+            let dist = bfs_distances(&neighbors, root as usize);
+            let parents = graph.shortest_path_tree(root);
+
+            for node in 0..w * h {
+                let parent = parents[node as usize];
+                if node == root || dist[node as usize] == u32::MAX {
+                    assert_eq!(parent, None);
+                    continue;
+                }
+
+                let parent = parent.unwrap();
+                assert!(graph.neighbors(node).contains(&parent));
+                assert_eq!(dist[parent as usize] + 1, dist[node as usize]);
+                // a grid has no edges between equally far nodes, so the tree follows neighbor_to
+                assert_eq!(Some(parent), graph.neighbor_to(node, root));
+            }
+        }
+
+        // the wall and the corner have no edges, so they are their own trees
+        assert!(graph.shortest_path_tree(4).iter().all(Option::is_none));
+
+        // in an odd cycle, the edge opposite the root joins two equally far nodes
+        let mut builder = Graph::<u16>::builder(5);
+        for i in 0..5 {
+            builder.connect(i, (i + 1) % 5);
+        }
+        let graph = builder.build();
+        for root in 0..5 {
+            let parents = graph.shortest_path_tree(root);
+            for step in 1..=2 {
+                // the nodes `step` away from the root on either side
+                for node in [(root + step) % 5, (root + 5 - step) % 5] {
+                    let parent = parents[node as usize].unwrap();
+                    assert!(parent == (node + 1) % 5 || parent == (node + 4) % 5);
+                    let parent_step = (parent + 5 - root) % 5;
+                    assert_eq!(parent_step.min(5 - parent_step), step - 1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_neighbor_to_avoiding_edges() {
+        // 4x4 grid
+        let mut builder = Graph::<u16>::builder(16);
+        for y in 0..4 {
+            for x in 0..4 {
+                let node = y * 4 + x;
+                if x < 3 {
+                    builder.connect(node, node + 1);
+                }
+                if y < 3 {
+                    builder.connect(node, node + 4);
+                }
+            }
+        }
+        let graph = builder.build();
+
+        // wall off the bottom row except at the right edge, and block 0 -- 1
+        let forbidden = HashSet::from([(8, 12), (13, 9), (10, 14), (0, 1)]);
+        let neighbors = (0..16u16)
+            .map(|a| {
+                graph
+                    .neighbors(a)
+                    .iter()
+                    .copied()
+                    .filter(|&b| !forbidden.contains(&(a, b)) && !forbidden.contains(&(b, a)))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        for dest in 0..16u16 {
+            let dist = bfs_distances(&neighbors, dest.as_usize());
+
+            for start in 0..16u16 {
+                let mut curr = start;
+                let mut hops = 0;
+                while let Some(next) = graph.neighbor_to_avoiding_edges(curr, dest, &forbidden) {
+                    assert!(neighbors[curr.as_usize()].contains(&next));
+                    curr = next;
+                    hops += 1;
+                }
+                assert_eq!(curr, dest);
+                assert_eq!(hops, dist[start.as_usize()]);
+            }
+        }
+
+        let isolated = HashSet::from([(0, 1), (0, 4)]);
+        assert_eq!(graph.neighbor_to_avoiding_edges(0, 5, &isolated), None);
+        assert_eq!(graph.neighbor_to_avoiding_edges(5, 0, &isolated), None);
+        assert_eq!(graph.neighbor_to_avoiding_edges(5, 5, &isolated), None);
+        assert_eq!(
+            graph.neighbor_to_avoiding_edges(0, 5, &HashSet::new()),
+            graph.neighbor_to(0, 5)
+        );
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        // 3x3 grid, plus an edge 4 -- 8 that is only listed on node 8
+        let grid = [
+            (0, 1),
+            (1, 2),
+            (3, 4),
+            (4, 5),
+            (6, 7),
+            (7, 8),
+            (0, 3),
+            (3, 6),
+            (1, 4),
+            (4, 7),
+            (2, 5),
+            (5, 8),
+        ];
+
+        macro_rules! corrupted {
+            ($builder:expr, $insert_stale:expr) => {{
+                let mut builder = $builder;
+                for (a, b) in grid {
+                    builder.connect(a, b);
+                }
+                builder.connect_with_data(0, 1, 7);
+
+                // a duplicate without data, a self-loop, an out-of-range node and a one-sided edge
+                builder.nodes.inner[1].push(0);
+                builder.nodes.inner[4].push(4);
+                builder.nodes.inner[2].push(9);
+                builder.nodes.inner[8].push(4);
+                builder.nodes.data[8].push(0);
+
+                // a key that is not an edge, and one that is not in ascending order
+                $insert_stale(&mut builder.edges, (0, 8));
+                $insert_stale(&mut builder.edges, (8, 5));
+                builder
+            }};
+        }
+
+        let mut builder = sequential::SeqGraph::builder(9);
+        for (a, b) in grid {
+            builder.connect(a, b);
+        }
+        builder.connect(4, 8);
+        let expected = Graph::Sequential(builder.build());
+
+        let assert_healed = |graph: Graph<u16>| {
+            for curr in 0..9 {
+                assert_eq!(graph.neighbors(curr).len(), expected.neighbors(curr).len());
+
+                for dest in 0..9 {
+                    let mut next = graph.neighbors_to(curr, dest).collect::<Vec<_>>();
+                    next.sort();
+                    let mut expected = expected.neighbors_to(curr, dest).collect::<Vec<_>>();
+                    expected.sort();
+                    assert_eq!(next, expected, "next hops from {curr} to {dest}");
+                }
+            }
+            assert_eq!(graph.edge_data(1, 0), Some(7));
+            assert_eq!(graph.edge_data(4, 8), Some(0));
+        };
+
+        let builder = corrupted!(
+            sequential::SeqGraph::builder(9),
+            |edges: &mut store::HashMapStore<u16>, ab| {
+                store::EdgeStore::insert(edges, ab, BitVec::ones(9))
+            }
+        );
+        assert_healed(Graph::Sequential(builder.build()));
+
+        #[cfg(feature = "parallel")]
+        {
+            let builder = corrupted!(
+                parallel::ParaGraph::builder(9),
+                |edges: &mut parallel::Edges<u16>, ab| edges.insert(ab, BitVec::ones(9), 9)
+            );
+            assert_healed(Graph::Parallel(builder.build()));
+        }
+    }
+
+    #[test]
+    fn test_hub_summaries() {
+        // a town square with 4 districts of 70 houses each, and an alley between two districts
+        let town = || {
+            let mut builder = GraphBuilder::<u16>::new(285);
+            for district in 1..=4u16 {
+                builder.connect(0, district);
+                for house in 0..70 {
+                    builder.connect(district, 5 + (district - 1) * 70 + house);
+                }
+            }
+            builder.connect(5, 75);
+            builder
+        };
+
+        let assert_same = |graph: &Graph<u16>, plain: &Graph<u16>| {
+            assert!(graph.hubs().unwrap().hubs_len() > 0);
+            assert!(plain.hubs().is_none());
+
+            for curr in 0..285 {
+                for dest in 0..285 {
+                    assert!(graph
+                        .neighbors_to(curr, dest)
+                        .eq(plain.neighbors_to(curr, dest)));
+                    assert!(graph.path_to(curr, dest).eq(plain.path_to(curr, dest)));
+                }
+            }
+        };
+
+        let plain = Graph::Sequential(town().build_seq());
+        let summarized = Graph::Sequential(town().hub_summaries(50).build_seq());
+        assert_eq!(summarized.hubs().unwrap().hubs_len(), 4);
+        assert_same(&summarized, &plain);
+
+        #[cfg(feature = "parallel")]
+        {
+            let plain = Graph::Parallel(town().build_par());
+            let summarized = Graph::Parallel(town().hub_summaries(50).build_par());
+            assert_same(&summarized, &plain);
+        }
+
+        // the summary follows the graph's edits and retained destinations
+        let mut dests = BitVec::ZERO;
+        for dest in [0, 5, 70, 75, 200, 284] {
+            dests.set_bit(dest, true);
+        }
+        let edits = [EdgeEdit::Connect(70, 200), EdgeEdit::Disconnect(0, 4)];
+
+        let (mut summarized, mut plain) = (summarized, plain);
+        summarized.retarget_after_edits(&edits, &dests);
+        plain.retarget_after_edits(&edits, &dests);
+        summarized.retain_dests(&dests);
+        plain.retain_dests(&dests);
+        assert_same(&summarized, &plain);
+
+        let rebuilt = summarized.into_builder().build();
+        assert!(rebuilt.hubs().is_some());
+    }
+
+    #[test]
+    fn test_ready_dests() {
+        // a corridor 0 - 1 - 2 - 3 - 4 - 5
+        let corridor = || {
+            let mut builder = GraphBuilder::<u16>::new(6);
+            for i in 0..5 {
+                builder.connect(i, i + 1);
+            }
+            builder
+        };
+        let bits = |dests: &[usize]| {
+            let mut bits = BitVec::ZERO;
+            for &dest in dests {
+                bits.set_bit(dest, true);
+            }
+            bits
+        };
+        let ready = |graph: &Graph<u16>| graph.ready_dests().iter_ones().collect::<Vec<_>>();
+
+        #[cfg(feature = "parallel")]
+        let graphs = vec![
+            Graph::Sequential(corridor().build_seq()),
+            Graph::Parallel(corridor().build_par()),
+        ];
+        #[cfg(not(feature = "parallel"))]
+        let graphs = vec![Graph::Sequential(corridor().build_seq())];
+
+        for mut graph in graphs {
+            assert_eq!(ready(&graph), vec![0, 1, 2, 3, 4, 5]);
+
+            graph.retain_dests(&bits(&[0, 3, 5]));
+            assert_eq!(ready(&graph), vec![0, 3, 5]);
+            assert!(graph.dest_ready(3));
+            assert!(!graph.dest_ready(2));
+
+            // without edits, no bits go stale; dropped destinations stay dropped
+            graph.retarget_after_edits(&[], &bits(&[1, 5]));
+            assert_eq!(ready(&graph), vec![0, 3, 5]);
+
+            // the shortcut leaves the bits of 0 and 3 stale, until they are retargeted too
+            graph.retarget_after_edits(&[EdgeEdit::Connect(0, 5)], &bits(&[5]));
+            assert_eq!(ready(&graph), vec![5]);
+            assert_eq!(graph.neighbor_to_or_greedy(4, 0, 2), Some(5));
+            graph.retarget_after_edits(&[], &bits(&[0]));
+            assert_eq!(ready(&graph), vec![0, 5]);
+            assert_eq!(graph.neighbor_to_or_greedy(4, 0, 0), Some(5));
+
+            assert_eq!(ready(&graph.into_builder().build()), vec![0, 1, 2, 3, 4, 5]);
+        }
+    }
+
+    #[test]
+    fn test_greedy_neighbor_to() {
+        // a corridor 0 - 1 - 2 - 3 - 4 - 5 - 6, and a separate pair 7 - 8
+        let mut neighbors = vec![Vec::new(); 9];
+        for (a, b) in [(0u16, 1u16), (1, 2), (2, 3), (3, 4), (4, 5), (5, 6), (7, 8)] {
+            neighbors[a as usize].push(b);
+            neighbors[b as usize].push(a);
+        }
+        let greedy = |curr: u16, dest: u16, hop_budget, heuristic: Option<&dyn Fn(u16) -> u64>| {
+            greedy_neighbor_to(
+                |n: u16| &neighbors[n as usize],
+                curr,
+                dest,
+                hop_budget,
+                heuristic,
+            )
+        };
+
+        // within the budget: the first hop of a shortest path
+        assert_eq!(greedy(3, 0, 3, None), Some(2));
+        assert_eq!(greedy(3, 3, 3, None), None);
+
+        // out of budget: toward the last node reached, 5
+        assert_eq!(greedy(3, 0, 2, None), Some(4));
+        // with no budget, one hop is still searched
+        assert_eq!(greedy(3, 0, 0, None), Some(4));
+
+        // with a heuristic: toward the searched node with the lowest estimate,
+        // even if it is not lower than curr's
+        let toward_zero = |n: u16| n as u64;
+        assert_eq!(greedy(3, 0, 2, Some(&toward_zero)), Some(2));
+        let flat = |_: u16| 5;
+        assert_eq!(greedy(2, 0, 1, Some(&flat)), Some(1));
+
+        // every reachable node was searched: no path
+        assert_eq!(greedy(7, 0, 5, None), None);
+        assert_eq!(greedy(7, 0, 5, Some(&toward_zero)), None);
+    }
+
     #[ignore]
     #[test]
     fn test_graph() {
@@ -1,15 +1,41 @@
-use super::U16orU32;
+use super::{
+    bfs_distances,
+    dests::{self, DestColumns},
+    hubs::HubSummary,
+    sequential::{self, SeqGraph},
+    stats::record_map_grow,
+    store::EdgeStore,
+    yielding::{should_yield, YieldNow},
+    EdgeEdit, U16orU32,
+};
 use crate::{
-    bitvec::{AtomicBitVec, BitVec},
+    bitvec::{AtomicBitVec, BitVec, IterOnes},
     edge_id,
 };
 use rayon::prelude::*;
-use std::{collections::HashMap, fmt::Debug};
+use std::{borrow::Cow, collections::HashMap, fmt::Debug, sync::Arc};
 
-#[derive(Debug)]
+/// Graph built by [ParaGraphBuilder].
+///
+/// A built graph is read-only, so its nodes and edges are shared behind an [Arc];
+/// cloning the graph is cheap and does not copy any bit vectors.
+///
+/// Each edge's bits are kept behind their own [Arc] as well, so a graph rebuilt from a clone
+/// shares the bits that came out the same with the graph it was cloned from.
+#[derive(Debug, Clone)]
 pub struct ParaGraph<NodeId: U16orU32 = u16> {
-    pub nodes: Nodes<NodeId>,
-    pub edges: HashMap<(NodeId, NodeId), AtomicBitVec>,
+    pub nodes: Arc<Nodes<NodeId>>,
+    pub edges: Arc<HashMap<(NodeId, NodeId), Arc<AtomicBitVec>>>,
+    /// Columns of the retained destinations, if the graph was narrowed down with
+    /// [retain_dests](Self::retain_dests); `None` if every node is a destination.
+    pub dests: Option<Arc<DestColumns>>,
+    /// Edge summaries of high-degree nodes, if enabled with [summarize_hubs](Self::summarize_hubs).
+    pub hubs: Option<Arc<HubSummary>>,
+    /// Destinations whose bits are up to date; see [ready_dests](Self::ready_dests).
+    pub ready: Arc<BitVec>,
+    /// Whether the graph was built with [stable_order](ParaGraphBuilder::stable_order);
+    /// [into_builder](Self::into_builder) keeps the setting for the next build.
+    pub stable_order: bool,
 }
 
 impl<NodeId: U16orU32> ParaGraph<NodeId> {
@@ -33,18 +59,189 @@ impl<NodeId: U16orU32> ParaGraph<NodeId> {
     /// like resizing nodes or adding/removing edges.
     ///
     /// Then you can build the graph again.
+    ///
+    /// If this graph is still shared with other clones, its nodes are copied first.
+    /// The bits of the edges that are shared with other graphs are kept aside,
+    /// and the rebuilt graph shares the ones that come out the same instead of keeping a copy.
+    ///
+    /// No computed paths are carried over: the builder holds the edges as if they were just connected,
+    /// so the rebuilt graph is the same as one built from scratch with the same edges.
+    /// This also means a graph narrowed down with [retain_dests](Self::retain_dests)
+    /// has every destination again once rebuilt.
     #[inline]
     pub fn into_builder(self) -> ParaGraphBuilder<NodeId> {
-        ParaGraphBuilder {
-            edge_masks: Edges {
-                inner: self
-                    .edges
-                    .iter()
-                    .map(|(k, _)| (*k, AtomicBitVec::zeros(self.nodes.len())))
-                    .collect(),
-            },
-            edges: Edges { inner: self.edges },
-            nodes: self.nodes,
+        let shared = if self.dests.is_some() {
+            // packed bits can't be compared with the rebuilt ones
+            HashMap::new()
+        } else {
+            let mut edges = Arc::try_unwrap(self.edges).unwrap_or_else(|edges| (*edges).clone());
+            edges.retain(|_, bits| Arc::strong_count(bits) > 1);
+            edges
+        };
+
+        let mut builder = ParaGraphBuilder {
+            edge_masks: Edges::new(),
+            edges: Edges::new(),
+            shared,
+            nodes: Arc::try_unwrap(self.nodes).unwrap_or_else(|nodes| (*nodes).clone()),
+            stable_order: self.stable_order,
+        };
+        builder.reset_computed();
+        builder
+    }
+
+    /// Drop the bits of all destinations except `dests`, to save memory in a special-purpose graph;
+    /// e.g. a strategic AI that only ever paths to a few objective nodes.
+    ///
+    /// The bits of the retained destinations are packed together,
+    /// so each edge takes one bit per retained destination instead of one per node.
+    /// Queries to retained destinations work as before;
+    /// queries to any other destination return `None`, as if there were no path.
+    ///
+    /// Call this on a clone to keep the full graph available elsewhere.
+    /// Calling it again narrows the retained destinations further.
+    pub fn retain_dests(&mut self, dests: &BitVec) {
+        let prev = self.dests.as_deref();
+        let columns = DestColumns::narrow(prev, dests);
+
+        let edges = self
+            .edges
+            .iter()
+            .map(|(k, bits)| {
+                let packed = columns.pack(prev, &bits.into_bitvec());
+                (
+                    *k,
+                    Arc::new(AtomicBitVec::from_bitvec(&packed, columns.len())),
+                )
+            })
+            .collect();
+
+        self.edges = Arc::new(edges);
+        self.dests = Some(Arc::new(columns));
+        self.ready = Arc::new(dests::intersect(&self.ready, dests));
+        self.refresh_hubs();
+    }
+
+    /// Apply the given edge edits in place, and recompute the bits of only the destinations `dests`
+    /// with a plain BFS from each of them.
+    ///
+    /// Queries to `dests` are up to date afterwards;
+    /// the bits of all other destinations are left as they were, and may be wrong
+    /// until the graph is rebuilt.
+    /// Destinations dropped with [retain_dests](Self::retain_dests) are skipped.
+    ///
+    /// See [SeqGraph::retarget_after_edits](super::sequential::SeqGraph::retarget_after_edits).
+    pub fn retarget_after_edits(&mut self, edits: &[EdgeEdit<NodeId>], dests: &BitVec) {
+        let bits_len = self
+            .dests
+            .as_deref()
+            .map_or(self.nodes.len(), DestColumns::len);
+
+        let nodes = Arc::make_mut(&mut self.nodes);
+        let edges = Arc::make_mut(&mut self.edges);
+
+        for edit in edits {
+            match *edit {
+                EdgeEdit::Connect(a, b) => {
+                    nodes.connect(a, b);
+                    edges
+                        .entry(edge_id(a, b))
+                        .or_insert_with(|| Arc::new(AtomicBitVec::zeros(bits_len)));
+                }
+                EdgeEdit::Disconnect(a, b) => {
+                    nodes.disconnect(a, b);
+                    edges.remove(&edge_id(a, b));
+                }
+            }
+        }
+
+        let mut retargeted = BitVec::ZERO;
+
+        for dest in dests.iter_ones().take_while(|&dest| dest < nodes.len()) {
+            let Some(index) = self.dests.as_deref().map_or(Some(dest), |d| d.column(dest)) else {
+                continue;
+            };
+            retargeted.set_bit(dest, true);
+
+            let dist = bfs_distances(&nodes.inner, dest);
+
+            for (&(a, b), bits) in edges.iter_mut() {
+                // from the lower node's perspective, the edge leads toward dest;
+                // bits shared with other graphs are copied before they are written
+                Arc::make_mut(bits).set_bit(index, dist[b.as_usize()] < dist[a.as_usize()]);
+            }
+        }
+
+        // after an edit, only the retargeted destinations are up to date
+        if edits.is_empty() {
+            retargeted.bitor_assign(&self.ready);
+        }
+        self.ready = Arc::new(retargeted);
+
+        self.refresh_hubs();
+    }
+
+    /// Return whether the bits of the given destination are up to date,
+    /// so that queries to it return its shortest paths.
+    ///
+    /// See [ready_dests](Self::ready_dests).
+    #[inline]
+    pub fn dest_ready(&self, dest: NodeId) -> bool {
+        self.ready.get_bit(dest.as_usize())
+    }
+
+    /// Return the destinations whose bits are up to date.
+    ///
+    /// Every node is ready after a build. Destinations dropped with [retain_dests](Self::retain_dests)
+    /// are not ready, and after [retarget_after_edits](Self::retarget_after_edits) with any edits,
+    /// only the retargeted destinations are ready.
+    #[inline]
+    pub fn ready_dests(&self) -> &BitVec {
+        &self.ready
+    }
+
+    /// Summarize the edges of every node with at least `min_degree` neighbors,
+    /// so that queries from those nodes only check the edges that may lead to the destination.
+    ///
+    /// See [SeqGraph::summarize_hubs](super::sequential::SeqGraph::summarize_hubs).
+    pub fn summarize_hubs(&mut self, min_degree: usize) {
+        let columns = self
+            .dests
+            .as_deref()
+            .map_or(self.nodes.len(), DestColumns::len);
+        let edges = &*self.edges;
+
+        let hubs = HubSummary::new(&self.nodes.inner, columns, min_degree, |a, b| {
+            edges
+                .get(&(a, b))
+                .map(|bits| Cow::Owned(bits.into_bitvec()))
+        });
+        self.hubs = Some(Arc::new(hubs));
+    }
+
+    /// Summarize the hubs again with the same minimum degree, if they are summarized.
+    fn refresh_hubs(&mut self) {
+        if let Some(min_degree) = self.hubs.as_deref().map(HubSummary::min_degree) {
+            self.summarize_hubs(min_degree);
+        }
+    }
+
+    /// Return the positions of `curr`'s neighbors to check for the given bit index,
+    /// or `None` if `curr` is not a summarized hub and every neighbor must be checked.
+    #[inline]
+    fn hub_candidates(&self, curr: NodeId, dest_index: usize) -> Option<IterOnes<'_>> {
+        self.hubs
+            .as_deref()?
+            .candidates(curr.as_usize(), dest_index)
+    }
+
+    /// Return the bit index of the given destination in the edges' bits,
+    /// or `None` if the destination was dropped with [retain_dests](Self::retain_dests).
+    #[inline]
+    fn dest_index(&self, dest: NodeId) -> Option<usize> {
+        match &self.dests {
+            Some(dests) => dests.column(dest.as_usize()),
+            None => Some(dest.as_usize()),
         }
     }
 
@@ -65,6 +262,7 @@ impl<NodeId: U16orU32> ParaGraph<NodeId> {
     /// or [neighbors_to](Self::neighbors_to) to get all neighboring nodes.
     #[inline]
     pub fn neighbor_to(&self, curr: NodeId, dest: NodeId) -> Option<NodeId> {
+        check_nodes!("neighbor_to", self.nodes_len(), curr, dest);
         self.neighbors_to(curr, dest).next()
     }
 
@@ -88,6 +286,7 @@ impl<NodeId: U16orU32> ParaGraph<NodeId> {
         dest: NodeId,
         f: impl Fn(NodeId) -> bool,
     ) -> Option<NodeId> {
+        check_nodes!("neighbor_to_with", self.nodes_len(), curr, dest);
         self.neighbors_to(curr, dest).find(|&n| f(n))
     }
 
@@ -97,11 +296,17 @@ impl<NodeId: U16orU32> ParaGraph<NodeId> {
     /// The nodes will be returned in the same order for the same inputs. However, the ordering of the nodes is not guaranteed.
     #[inline]
     pub fn neighbors_to(&self, curr: NodeId, dest: NodeId) -> NeighborsToIter<'_, NodeId> {
+        check_nodes!("neighbors_to", self.nodes_len(), curr, dest);
+        let dest_index = self.dest_index(dest);
+        let candidates = dest_index.and_then(|index| self.hub_candidates(curr, index));
+
         NeighborsToIter {
             graph: self,
             neighbors: self.nodes.neighbors(curr).iter(),
+            candidates,
             curr,
             dest,
+            dest_index,
         }
     }
 
@@ -115,26 +320,64 @@ impl<NodeId: U16orU32> ParaGraph<NodeId> {
     /// If there is no path, the list will be empty.
     #[inline]
     pub fn path_to(&self, curr: NodeId, dest: NodeId) -> PathIter<'_, NodeId> {
+        check_nodes!("path_to", self.nodes_len(), curr, dest);
         PathIter {
             map: self,
             curr,
             dest,
+            dest_index: self.dest_index(dest),
+            prev: None,
             init: false,
         }
     }
 
+    /// Given a current node and a source node,
+    /// return the node right before `curr` on a shortest path from `src` to `curr`.
+    ///
+    /// The graph is undirected, so this is the neighbor of `curr` that is one step closer to `src`;
+    /// calling it repeatedly retraces the way back to `src`.
+    ///
+    /// `None` is returned when:
+    /// - `curr` and `src` are the same node
+    /// - there is no path from `src` to `curr`
+    #[inline]
+    pub fn prev_node(&self, curr: NodeId, src: NodeId) -> Option<NodeId> {
+        check_nodes!("prev_node", self.nodes_len(), curr, src);
+        self.neighbor_to(curr, src)
+    }
+
     /// Check if there is a path from the current node to the destination node.
     #[inline]
     pub fn path_exists(&self, curr: NodeId, dest: NodeId) -> bool {
+        check_nodes!("path_exists", self.nodes_len(), curr, dest);
         self.neighbor_to(curr, dest).is_some()
     }
 
     /// Return a list of all neighboring nodes of the given node.
     #[inline]
     pub fn neighbors(&self, node: NodeId) -> &[NodeId] {
+        check_nodes!("neighbors", self.nodes_len(), node);
         self.nodes.neighbors(node)
     }
 
+    /// Return the user data attached to the edge between node_a and node_b.
+    ///
+    /// `None` is returned when there is no edge between the two nodes.
+    #[inline]
+    pub fn edge_data(&self, a: NodeId, b: NodeId) -> Option<u16> {
+        self.nodes.data(a, b)
+    }
+
+    /// Return a hash of this graph's topology: the number of nodes and the set of edges.
+    ///
+    /// The hash does not depend on the order edges were added in or on the internal layout,
+    /// so it can be used as a cache key to check whether a map has changed since it was last built.
+    /// The same topology gives the same hash for every graph type and builder.
+    #[inline]
+    pub fn topology_hash(&self) -> u64 {
+        self.nodes.topology_hash()
+    }
+
     /// Return the number of nodes in this graph.
     #[inline]
     pub fn nodes_len(&self) -> usize {
@@ -148,12 +391,81 @@ impl<NodeId: U16orU32> ParaGraph<NodeId> {
     }
 }
 
+/// Copy a [SeqGraph]'s plain bit vectors into atomic ones.
+///
+/// The paths are not computed again, so the converted graph answers every query the same way;
+/// retained destinations of [retain_dests](SeqGraph::retain_dests) are kept as well.
+impl<NodeId: U16orU32, S: EdgeStore<NodeId>> From<SeqGraph<NodeId, S>> for ParaGraph<NodeId> {
+    fn from(graph: SeqGraph<NodeId, S>) -> Self {
+        let bits_len = graph
+            .dests
+            .as_ref()
+            .map_or(graph.nodes.len(), |dests| dests.len());
+
+        let edges = graph
+            .edges
+            .iter()
+            .map(|(k, bits)| (k, Arc::new(AtomicBitVec::from_bitvec(&bits, bits_len))))
+            .collect();
+
+        let nodes = Arc::try_unwrap(graph.nodes).unwrap_or_else(|nodes| (*nodes).clone());
+
+        ParaGraph {
+            nodes: Arc::new(Nodes {
+                inner: nodes.inner,
+                data: nodes.data,
+            }),
+            edges: Arc::new(edges),
+            dests: graph.dests,
+            hubs: graph.hubs,
+            ready: graph.ready,
+            stable_order: false,
+        }
+    }
+}
+
+/// Copy a [ParaGraph]'s atomic bit vectors into plain ones, in the store `S`.
+///
+/// The paths are not computed again, so the converted graph answers every query the same way;
+/// retained destinations of [retain_dests](ParaGraph::retain_dests) are kept as well.
+/// This is handy to build a graph in parallel on a machine with many cores,
+/// and ship the plain representation.
+impl<NodeId: U16orU32, S: EdgeStore<NodeId>> From<ParaGraph<NodeId>> for SeqGraph<NodeId, S> {
+    fn from(graph: ParaGraph<NodeId>) -> Self {
+        let mut edges = S::default();
+        for (k, bits) in graph.edges.iter() {
+            edges.insert(*k, bits.into_bitvec());
+        }
+        edges.compact();
+
+        let nodes = Arc::try_unwrap(graph.nodes).unwrap_or_else(|nodes| (*nodes).clone());
+
+        SeqGraph {
+            nodes: Arc::new(sequential::Nodes {
+                inner: nodes.inner,
+                data: nodes.data,
+            }),
+            edges: Arc::new(edges),
+            dests: graph.dests,
+            hubs: graph.hubs,
+            ready: graph.ready,
+            parallel_setup: false,
+        }
+    }
+}
+
 /// An iterator that returns a path from the current node to the destination node.
+///
+/// The destination's bit index is resolved once for the whole path,
+/// and the edge back to the previous node is skipped at each step,
+/// since it always leads away from the destination.
 #[derive(Debug)]
 pub struct PathIter<'a, NodeId: U16orU32> {
     map: &'a ParaGraph<NodeId>,
     curr: NodeId,
     dest: NodeId,
+    dest_index: Option<usize>,
+    prev: Option<NodeId>,
     init: bool,
 }
 
@@ -166,13 +478,41 @@ impl<NodeId: U16orU32> Iterator for PathIter<'_, NodeId> {
             return Some(self.curr);
         }
 
-        let Some(next) = self.map.neighbor_to(self.curr, self.dest) else {
+        if self.curr == self.dest {
             return None;
-        };
+        }
+
+        let dest_index = self.dest_index?;
+        let curr = self.curr;
+        let neighbors = self.map.nodes.neighbors(curr);
 
-        self.curr = next;
+        let mut all = 0..neighbors.len();
+        let mut candidates = self.map.hub_candidates(curr, dest_index);
+        let positions = std::iter::from_fn(|| match &mut candidates {
+            Some(candidates) => candidates.next(),
+            None => all.next(),
+        });
 
-        Some(next)
+        for neighbor in positions.map(|i| neighbors[i]) {
+            if Some(neighbor) == self.prev {
+                continue;
+            }
+
+            let bit = self
+                .map
+                .edges
+                .get(&edge_id(curr, neighbor))?
+                .get_bit(dest_index);
+            let bit = if curr > neighbor { !bit } else { bit };
+
+            if bit {
+                self.prev = Some(curr);
+                self.curr = neighbor;
+                return Some(neighbor);
+            }
+        }
+
+        None
     }
 }
 
@@ -182,7 +522,10 @@ pub struct NeighborsToIter<'a, NodeId: U16orU32> {
     graph: &'a ParaGraph<NodeId>,
     curr: NodeId,
     dest: NodeId,
+    dest_index: Option<usize>,
     neighbors: std::slice::Iter<'a, NodeId>,
+    /// positions in `neighbors` of the edges to check, if `curr` is a summarized hub
+    candidates: Option<IterOnes<'a>>,
 }
 
 impl<NodeId: U16orU32> Iterator for NeighborsToIter<'_, NodeId> {
@@ -194,20 +537,25 @@ impl<NodeId: U16orU32> Iterator for NeighborsToIter<'_, NodeId> {
             return None;
         }
 
-        while let Some(&neighbor) = self.neighbors.next() {
+        let dest_index = self.dest_index?;
+
+        loop {
+            let neighbor = match &mut self.candidates {
+                Some(candidates) => self.graph.nodes.neighbors(self.curr)[candidates.next()?],
+                None => *self.neighbors.next()?,
+            };
+
             let bit = self
                 .graph
                 .edges
                 .get(&edge_id(self.curr, neighbor))?
-                .get_bit(self.dest.as_usize());
+                .get_bit(dest_index);
             let bit = if self.curr > neighbor { !bit } else { bit };
 
             if bit {
                 return Some(neighbor);
             }
         }
-
-        None
     }
 }
 
@@ -227,6 +575,13 @@ pub struct ParaGraphBuilder<NodeId: U16orU32> {
     /// key: edge_id
     /// value: for each edge, bit is set to 1 if the node with the bit location is computed for this edge
     pub edge_masks: Edges<NodeId>,
+
+    /// bits of the graph this builder was converted from that are shared with other graphs;
+    /// the built graph reuses the ones whose bits come out the same
+    pub shared: HashMap<(NodeId, NodeId), Arc<AtomicBitVec>>,
+
+    /// process each round's nodes in index order; see [stable_order](Self::stable_order)
+    pub stable_order: bool,
 }
 
 impl<NodeId: U16orU32> ParaGraphBuilder<NodeId> {
@@ -237,53 +592,121 @@ impl<NodeId: U16orU32> ParaGraphBuilder<NodeId> {
             nodes: Nodes::new(nodes_len),
             edges: Edges::new(),
             edge_masks: Edges::new(),
+            shared: HashMap::new(),
+            stable_order: false,
         }
     }
 
+    /// Process the nodes of each gossip round in index order.
+    ///
+    /// Each round's worklist is the nodes that were not done at the start of the round;
+    /// the done-nodes bits are only updated once the round's workers are merged.
+    /// By default, the worklist is read lazily and handed out to threads with `par_bridge`,
+    /// so how it is cut into chunks depends on which thread asks first.
+    /// With `stable_order(true)`, the worklist is collected first and split by index
+    /// with an indexed parallel iterator, so every run cuts it into the same chunks.
+    ///
+    /// This trades collecting the worklist for chunks that don't change between runs,
+    /// which helps when debugging convergence issues.
+    /// The built graph is the same either way.
+    #[inline]
+    pub fn stable_order(mut self, stable_order: bool) -> Self {
+        self.stable_order = stable_order;
+        self
+    }
+
     /// Resize the graph to the given number of nodes.
     ///
     /// All edges that are connected to nodes that are removed will also be removed.
+    ///
+    /// The edges' atomic bit vectors are fixed-size, so they are resized here as well.
     pub fn resize(&mut self, nodes_len: usize) {
-        let should_truncate = nodes_len < self.nodes.len();
-
         self.nodes.resize(nodes_len);
-
-        if should_truncate {
-            self.edges.truncate(nodes_len);
-            self.edge_masks.truncate(nodes_len);
-        }
+        self.edges.resize(nodes_len);
+        self.edge_masks.resize(nodes_len);
     }
 
     /// Add an edge between node_a and node_b
     pub fn connect(&mut self, a: NodeId, b: NodeId) {
         self.nodes.connect(a, b);
+        self.reset_edge(edge_id(a, b));
+    }
+
+    /// Forget all computed paths, so the next build computes every path from scratch.
+    ///
+    /// Every edge of `nodes` is reset to the state of a freshly connected edge,
+    /// and entries of `edges` and `edge_masks` that are not edges of `nodes` are dropped.
+    /// [ParaGraph::into_builder] calls this, so rebuilding after edits gives the same paths
+    /// as building the edited graph from scratch.
+    pub fn reset_computed(&mut self) {
+        self.edges = Edges::new();
+        self.edge_masks = Edges::new();
+
+        let edges = (0..self.nodes.len())
+            .map(NodeId::from_usize)
+            .flat_map(|a| self.nodes.neighbors(a).iter().map(move |&b| (a, b)))
+            .filter(|(a, b)| a < b)
+            .collect::<Vec<_>>();
+
+        for ab in edges {
+            self.reset_edge(ab);
+        }
+    }
+
+    /// Set the bits of the edge to those of a freshly connected edge.
+    #[inline]
+    fn reset_edge(&mut self, ab: (NodeId, NodeId)) {
+        let nodes_len = self.nodes.len();
 
         // edge value is flipped to b -> a, which means from node b's perspective, this edge is:
         // - gets further away from b
         // - shortest path to a
         // - gets further away from all other nodes
-        let val = if a > b { a } else { b };
+        let edge = AtomicBitVec::one(ab.1.as_usize(), nodes_len);
+        self.edges.inner.insert(ab, edge);
 
-        let ab = edge_id(a, b);
+        let mask = AtomicBitVec::one(ab.1.as_usize(), nodes_len);
+        mask.set_bit(ab.0.as_usize(), true);
+        self.edge_masks.inner.insert(ab, mask);
+    }
 
-        if let Some(edge) = self.edges.inner.get_mut(&ab) {
-            edge.set_bit(val.as_usize(), true);
-        } else {
-            let edge = AtomicBitVec::one(val.as_usize(), self.nodes.len());
+    /// Repair the builder before a build, so edits made through the public fields
+    /// cannot stall the gossip.
+    ///
+    /// The neighbor lists are canonicalized, entries of `edges` and `edge_masks` whose key
+    /// is not an ascending edge of `nodes` are dropped,
+    /// and edges missing from either map are reset to a freshly connected edge.
+    fn canonicalize(&mut self) {
+        self.nodes.canonicalize();
+
+        let nodes = &self.nodes;
+        let is_edge = |&(a, b): &(NodeId, NodeId)| a < b && nodes.position(a, b).is_some();
+
+        self.edges.inner.retain(|ab, _| is_edge(ab));
+        self.edge_masks.inner.retain(|ab, _| is_edge(ab));
+
+        let missing = (0..self.nodes.len())
+            .map(NodeId::from_usize)
+            .flat_map(|a| self.nodes.neighbors(a).iter().map(move |&b| (a, b)))
+            .filter(|&(a, b)| a < b)
+            .filter(|ab| {
+                !self.edges.inner.contains_key(ab) || !self.edge_masks.inner.contains_key(ab)
+            })
+            .collect::<Vec<_>>();
 
-            self.edges.inner.insert(ab, edge);
+        for ab in missing {
+            self.reset_edge(ab);
         }
+    }
 
-        if let Some(edge) = self.edge_masks.inner.get_mut(&ab) {
-            edge.set_bit(a.as_usize(), true);
-            edge.set_bit(b.as_usize(), true);
-        } else {
-            let edge = AtomicBitVec::zeros(self.nodes.len());
-            edge.set_bit(a.as_usize(), true);
-            edge.set_bit(b.as_usize(), true);
-
-            self.edge_masks.inner.insert(ab, edge);
-        }
+    /// Add an edge between node_a and node_b, with user data attached to it.
+    ///
+    /// The data is not used for pathfinding; it can be read back with `edge_data`.
+    /// If the edge already exists, its data is replaced.
+    #[inline]
+    pub fn connect_with_data(&mut self, a: NodeId, b: NodeId, data: u16) {
+        self.connect(a, b);
+        self.nodes.set_data(a, b, data);
     }
 
     /// Remove an edge between node_a and node_b
@@ -300,88 +723,159 @@ impl<NodeId: U16orU32> ParaGraphBuilder<NodeId> {
 
     /// Build the ParaGraph from the current state of the builder.
     pub fn build(self) -> ParaGraph<NodeId> {
-        let Self {
-            nodes,
-            edges,
-            edge_masks,
-            ..
-        } = self;
+        let gossip = Gossip::new(self);
+        while !gossip.round() {}
+        gossip.finish()
+    }
 
-        let chunk_size = 8;
+    /// Build the ParaGraph, calling `yield_fn` after every `every_n_rounds` rounds of gossip.
+    ///
+    /// This lets a long build report progress, or check on other work, while it runs.
+    /// `every_n_rounds` of `0` never calls `yield_fn`.
+    pub fn build_yielding(
+        self,
+        every_n_rounds: usize,
+        mut yield_fn: impl FnMut(),
+    ) -> ParaGraph<NodeId> {
+        let gossip = Gossip::new(self);
+        let mut rounds = 0;
+        while !gossip.round() {
+            rounds += 1;
+            if should_yield(rounds, every_n_rounds) {
+                yield_fn();
+            }
+        }
+        gossip.finish()
+    }
 
-        // (neighbors at current depth, neighbors at previous depths)
-        let neighbors_at_depth: Vec<(AtomicBitVec, AtomicBitVec)> = nodes
-            .inner
-            .par_iter()
-            .enumerate()
-            .map(|(i, e)| {
-                let neighbors = AtomicBitVec::zeros(nodes.len());
-                for n in e {
-                    neighbors.set_bit(n.as_usize(), true);
-                }
-                (neighbors, AtomicBitVec::one(i, nodes.len()))
-            })
-            .collect();
+    /// Build the ParaGraph inside an async task,
+    /// yielding to the executor after every `every_n_rounds` rounds of gossip.
+    ///
+    /// Each round still runs on the Rayon thread pool, and the task waits for it;
+    /// yielding in between lets the other tasks of the executor's thread run.
+    /// `every_n_rounds` of `0` never yields.
+    pub async fn build_async(self, every_n_rounds: usize) -> ParaGraph<NodeId> {
+        let gossip = Gossip::new(self);
+        let mut rounds = 0;
+        while !gossip.round() {
+            rounds += 1;
+            if should_yield(rounds, every_n_rounds) {
+                YieldNow::default().await;
+            }
+        }
+        gossip.finish()
+    }
 
-        let active_neighbors_mask = AtomicBitVec::zeros(nodes.len());
+    /// Return a hash of this graph's topology: the number of nodes and the set of edges.
+    ///
+    /// The hash does not depend on the order edges were added in or on the internal layout,
+    /// so it can be used as a cache key to check whether a map has changed since it was last built.
+    /// The same topology gives the same hash for every graph type and builder.
+    #[inline]
+    pub fn topology_hash(&self) -> u64 {
+        self.nodes.topology_hash()
+    }
 
-        // each rooom's bit is set to 1 if all its edges are done computed
-        let done_nodes = AtomicBitVec::zeros(nodes.len());
+    /// Return the number of nodes in this graph.
+    #[inline]
+    pub fn nodes_len(&self) -> usize {
+        self.nodes.len()
+    }
 
-        let full_mask = BitVec::ones(nodes.len());
+    /// Return the number of edges in this graph.
+    #[inline]
+    pub fn edges_len(&self) -> usize {
+        self.edges.inner.len()
+    }
 
-        nodes
-            .inner
-            .par_iter()
-            .enumerate()
-            .chunks(chunk_size)
-            .for_each(|nodes| {
-                for (a, a_neighbors) in nodes {
-                    // setup
-                    let mut neighbor_upserts: Vec<(BitVec, BitVec)> =
-                        vec![(BitVec::ZERO, BitVec::ZERO); a_neighbors.len()];
+    /// Return the neighbors of the given node.
+    #[inline]
+    pub fn neighbors(&self, node: NodeId) -> &[NodeId] {
+        self.nodes.neighbors(node)
+    }
 
-                    let a = NodeId::from_usize(a);
+    /// Return the user data attached to the edge between node_a and node_b.
+    #[inline]
+    pub fn edge_data(&self, a: NodeId, b: NodeId) -> Option<u16> {
+        self.nodes.data(a, b)
+    }
+}
 
-                    // for each edge in this node
-                    // set the bit value for a and b as 1
-                    for (i, b) in a_neighbors.iter().cloned().enumerate() {
-                        let b_usize = b.as_usize();
+/// Number of nodes a task processes at a time during a build.
+const CHUNK_SIZE: usize = 8;
 
-                        let mut val = true;
+/// State of a [ParaGraphBuilder] build between its rounds of gossip.
+///
+/// A build runs in the same phases as a sequential build, with the nodes of each phase
+/// processed in parallel.
+struct Gossip<NodeId: U16orU32> {
+    nodes: Nodes<NodeId>,
+    edges: Edges<NodeId>,
+    edge_masks: Edges<NodeId>,
+    shared: HashMap<(NodeId, NodeId), Arc<AtomicBitVec>>,
+    stable_order: bool,
+
+    /// (neighbors at current depth, neighbors at previous depths)
+    neighbors_at_depth: Vec<(AtomicBitVec, AtomicBitVec)>,
+
+    /// each rooom's bit is set to 1 if all its edges are done computed
+    done_nodes: AtomicBitVec,
+
+    full_mask: BitVec,
+}
 
-                        // edge value is flipped to b -> a, which means from node b's perspective, this edge is:
-                        // - gets further away from b
-                        // - shortest path to a
-                        // - gets further away from all other nodes
-                        if a > b {
-                            val = false;
-                        }
+impl<NodeId: U16orU32> Gossip<NodeId> {
+    /// Set up the edges of every node with their direct neighbors.
+    fn new(mut builder: ParaGraphBuilder<NodeId>) -> Self {
+        builder.canonicalize();
 
-                        // for all other edges in this node, set the value for this node bit as 0
-                        for (j, c) in a_neighbors.iter().cloned().enumerate() {
-                            if i == j {
-                                continue;
-                            }
+        let ParaGraphBuilder {
+            nodes,
+            edges,
+            edge_masks,
+            shared,
+            stable_order,
+        } = builder;
+
+        let gossip = Gossip {
+            neighbors_at_depth: initial_frontiers(&nodes.inner),
+            // each rooom's bit is set to 1 if all its edges are done computed
+            done_nodes: AtomicBitVec::zeros(nodes.len()),
+            full_mask: BitVec::ones(nodes.len()),
+            nodes,
+            edges,
+            edge_masks,
+            shared,
+            stable_order,
+        };
 
-                            // if both b and c are in the same corner (tl or br)
-                            // flip the bit
-                            let should_set = if (a > b) == (a > c) { !val } else { val };
+        gossip.setup();
+        gossip
+    }
 
-                            let (upsert, computed) = &mut neighbor_upserts[j];
-                            if should_set {
-                                upsert.set_bit(b_usize, true);
-                            }
-                            computed.set_bit(b_usize, true);
-                        }
-                    }
+    /// Setup phase: set the bits of every edge for the direct neighbors of its nodes.
+    fn setup(&self) {
+        let Gossip {
+            nodes,
+            edges,
+            edge_masks,
+            ..
+        } = self;
+
+        nodes
+            .inner
+            .par_iter()
+            .enumerate()
+            .chunks(CHUNK_SIZE)
+            .for_each(|nodes| {
+                for (a, a_neighbors) in nodes {
+                    let upserts = sequential::setup_upserts(a, a_neighbors);
+                    let a = NodeId::from_usize(a);
 
                     // apply computed values
-                    for (b, upserts) in a_neighbors.iter().zip(neighbor_upserts.drain(..)) {
+                    for (b, (upsert, computed)) in a_neighbors.iter().zip(upserts) {
                         let ab = edge_id(a, *b);
 
-                        let (upsert, computed) = upserts;
-
                         if !computed.is_zero() {
                             if !upsert.is_zero() {
                                 edges.update(ab, upsert);
@@ -391,188 +885,273 @@ impl<NodeId: U16orU32> ParaGraphBuilder<NodeId> {
                     }
                 }
             });
+    }
 
-        loop {
-            // iterate through all undone nodes
-            done_nodes
+    /// Run a round of gossip, and return whether every node is done.
+    fn round(&self) -> bool {
+        let (done, active_neighbors_mask) = self.gossip();
+
+        self.done_nodes.bitor_assign(&done);
+
+        if self.done_nodes.eq(&self.full_mask) {
+            return true;
+        }
+
+        self.advance_frontiers(&active_neighbors_mask);
+        false
+    }
+
+    /// Gossip phase of a round: every node that is not done gossips its neighbors' frontiers
+    /// to its other edges.
+    ///
+    /// Return the nodes that had nothing left to gossip, and are done,
+    /// with the neighbors whose frontiers were gossiped, whose frontiers are advanced next.
+    ///
+    /// Each worker collects both into its own masks, which are merged once at the end of the round;
+    /// so the workers never write to a shared mask, and don't contend on its cache lines.
+    fn gossip(&self) -> (BitVec, BitVec) {
+        // (done nodes, active neighbors) found by a worker
+        let masks = || (BitVec::ZERO, BitVec::ZERO);
+        let merge = |(mut done, mut active): (BitVec, BitVec), (d, a): (BitVec, BitVec)| {
+            done.bitor_assign(&d);
+            active.bitor_assign(&a);
+            (done, active)
+        };
+
+        // iterate through all undone nodes
+        let gossip = |(mut done, mut active): (BitVec, BitVec), e: &[usize]| {
+            for &a in e {
+                if a >= self.nodes.len() {
+                    break;
+                }
+
+                let a_active_neighbors_mask = self.gossip_node(a);
+
+                // if none of a's neighbors are active, then a is done
+                if a_active_neighbors_mask.is_zero() {
+                    done.set_bit(a, true);
+                }
+
+                active.bitor_assign(&a_active_neighbors_mask);
+            }
+
+            (done, active)
+        };
+
+        if self.stable_order {
+            let worklist = self.done_nodes.iter_zeros().collect::<Vec<_>>();
+            worklist
+                .par_chunks(CHUNK_SIZE)
+                .fold(masks, gossip)
+                .reduce(masks, merge)
+        } else {
+            self.done_nodes
                 .iter_zeros()
-                .chunks(chunk_size)
+                .chunks(CHUNK_SIZE)
                 .par_bridge()
-                .for_each(|e| {
-                    for a in e {
-                        if a >= nodes.len() {
-                            break;
-                        }
+                .fold(masks, |masks, e| gossip(masks, &e))
+                .reduce(masks, merge)
+        }
+    }
 
-                        let a_usize = a;
-                        let a = NodeId::from_usize(a);
+    /// Gossip the frontiers of node `a`'s neighbors to `a`'s other edges,
+    /// and return the neighbors that had a frontier to gossip;
+    /// none if all of `a`'s edges are already computed.
+    fn gossip_node(&self, a: usize) -> BitVec {
+        let Gossip {
+            nodes,
+            edges,
+            edge_masks,
+            neighbors_at_depth,
+            full_mask,
+            ..
+        } = self;
 
-                        let a_neighbors = nodes.neighbors(a);
+        let a_usize = a;
+        let a = NodeId::from_usize(a);
 
-                        let mut neighbor_upserts: Vec<(BitVec, BitVec)> =
-                            vec![(BitVec::ZERO, BitVec::ZERO); a_neighbors.len()];
+        let a_neighbors = nodes.neighbors(a);
 
-                        // collect all nodes that need to update their neighbors to next depth
-                        let mut a_active_neighbors_mask = BitVec::ZERO;
+        let mut neighbor_upserts: Vec<(BitVec, BitVec)> =
+            vec![(BitVec::ZERO, BitVec::ZERO); a_neighbors.len()];
 
-                        // get all neighbors' masks
-                        // so we can just reuse it
-                        let mut a_neighbor_masks = Vec::with_capacity(a_neighbors.len());
+        // collect all nodes that need to update their neighbors to next depth
+        let mut a_active_neighbors_mask = BitVec::ZERO;
 
-                        for b in a_neighbors.iter().copied() {
-                            let mask = edge_masks.get(edge_id(a, b)).unwrap();
+        // get all neighbors' masks
+        // so we can just reuse it
+        let mut a_neighbor_masks = Vec::with_capacity(a_neighbors.len());
 
-                            if mask.eq(&full_mask) {
-                                a_neighbor_masks.push(None);
-                            } else {
-                                a_neighbor_masks.push(Some(mask));
-                            }
-                        }
+        for b in a_neighbors.iter().copied() {
+            let mask = edge_masks.get(edge_id(a, b)).unwrap();
 
-                        // if all edges are computed, skip
-                        if a_neighbor_masks.iter().all(Option::is_none) {
-                            done_nodes.set_bit(a_usize, true);
+            if mask.eq(full_mask) {
+                a_neighbor_masks.push(None);
+            } else {
+                a_neighbor_masks.push(Some(mask));
+            }
+        }
 
-                            continue;
-                        }
+        // if all edges are computed, skip
+        if a_neighbor_masks.iter().all(Option::is_none) {
+            return a_active_neighbors_mask;
+        }
 
-                        for (i, b) in a_neighbors.iter().copied().enumerate() {
-                            let b_usize = b.as_usize();
+        for (i, b) in a_neighbors.iter().copied().enumerate() {
+            let b_usize = b.as_usize();
 
-                            // b's neighbors' bits to gossip from edge a->b to other edges
-                            let mut b_neighbor_mask_at_d =
-                                neighbors_at_depth[b_usize].0.into_bitvec();
+            // b's neighbors' bits to gossip from edge a->b to other edges
+            let mut b_neighbor_mask_at_d = neighbors_at_depth[b_usize].0.into_bitvec();
 
-                            b_neighbor_mask_at_d.set_bit(a_usize, false);
+            b_neighbor_mask_at_d.set_bit(a_usize, false);
 
-                            // if no neighbors to gossip at this depth, skip
-                            if b_neighbor_mask_at_d.is_zero() {
-                                continue;
-                            }
+            // if no neighbors to gossip at this depth, skip
+            if b_neighbor_mask_at_d.is_zero() {
+                continue;
+            }
 
-                            a_active_neighbors_mask.set_bit(b_usize, true);
+            a_active_neighbors_mask.set_bit(b_usize, true);
 
-                            let ab = edge_id(a, b);
+            let ab = edge_id(a, b);
 
-                            let val = edges.get(ab).unwrap().into_bitvec();
+            let val = edges.get(ab).unwrap().into_bitvec();
 
-                            // gossip to other edges about its neighbors at current depth
-                            for (j, c) in a_neighbors.iter().copied().enumerate() {
-                                // skip if same neighbor
-                                if i == j {
-                                    continue;
-                                }
+            // gossip to other edges about its neighbors at current depth
+            for (j, c) in a_neighbors.iter().copied().enumerate() {
+                // skip if same neighbor
+                if i == j {
+                    continue;
+                }
 
-                                let Some(mask_ac) = a_neighbor_masks[j] else {
-                                    continue;
-                                };
+                let Some(mask_ac) = a_neighbor_masks[j] else {
+                    continue;
+                };
 
-                                let mut compute_mask = b_neighbor_mask_at_d.clone();
-                                // dont set bits that are already computed
-                                compute_mask.bitand_not_assign(&mask_ac.into_bitvec());
+                let mut compute_mask = b_neighbor_mask_at_d.clone();
+                // dont set bits that are already computed
+                compute_mask.bitand_not_assign(&mask_ac.into_bitvec());
 
-                                // if all bits are already computed, skip
-                                if compute_mask.is_zero() {
-                                    continue;
-                                }
+                // if all bits are already computed, skip
+                if compute_mask.is_zero() {
+                    continue;
+                }
 
-                                let (upsert, computed) = &mut neighbor_upserts[j];
+                let (upsert, computed) = &mut neighbor_upserts[j];
 
-                                // if both b and c are in the same corner (tl or br)
-                                // flip the bit
-                                if (a > b) == (a > c) {
-                                    upsert.bitor_not_and_assign(&val, &compute_mask);
-                                } else {
-                                    upsert.bitor_and_assign(&val, &compute_mask);
-                                };
+                // if both b and c are in the same corner (tl or br)
+                // flip the bit
+                if (a > b) == (a > c) {
+                    upsert.bitor_not_and_assign(&val, &compute_mask);
+                } else {
+                    upsert.bitor_and_assign(&val, &compute_mask);
+                };
 
-                                computed.bitor_assign(&compute_mask);
-                            }
-                        }
+                computed.bitor_assign(&compute_mask);
+            }
+        }
 
-                        // if all edges are computed or none of a's neighbors are active,
-                        // then a is done
-                        if a_active_neighbors_mask.is_zero() {
-                            done_nodes.set_bit(a_usize, true);
-                        } else {
-                            for (b, upserts) in
-                                a_neighbors.iter().copied().zip(neighbor_upserts.drain(..))
-                            {
-                                let ab = edge_id(a, b);
-
-                                let (upsert, computed) = upserts;
-
-                                if !computed.is_zero() {
-                                    if !upsert.is_zero() {
-                                        edges.update(ab, upsert);
-                                    }
-                                    edge_masks.update(ab, computed);
-                                }
-                            }
-                        }
+        // the upserts are only applied if a is not done
+        if !a_active_neighbors_mask.is_zero() {
+            for (b, upserts) in a_neighbors.iter().copied().zip(neighbor_upserts.drain(..)) {
+                let ab = edge_id(a, b);
 
-                        active_neighbors_mask.bitor_assign(&a_active_neighbors_mask);
-                    }
-                });
+                let (upsert, computed) = upserts;
 
-            if done_nodes.eq(&full_mask) {
-                break;
+                if !computed.is_zero() {
+                    if !upsert.is_zero() {
+                        edges.update(ab, upsert);
+                    }
+                    edge_masks.update(ab, computed);
+                }
             }
+        }
 
-            active_neighbors_mask
-                .iter_ones()
-                .chunks(chunk_size)
-                .par_bridge()
-                .for_each(|e| {
-                    for a in e {
-                        let (a_neighbors_at_depth, prev_neighbors) = &neighbors_at_depth[a];
+        a_active_neighbors_mask
+    }
 
-                        if a_neighbors_at_depth.is_zero() {
-                            continue;
-                        }
+    /// Frontier phase of a round: advance the frontier of every node in `active_neighbors_mask`
+    /// to the nodes one hop further, that are not in any of its previous frontiers.
+    fn advance_frontiers(&self, active_neighbors_mask: &BitVec) {
+        let Gossip {
+            nodes,
+            neighbors_at_depth,
+            ..
+        } = self;
 
-                        // add previous neighbors to prev neighbors
-                        prev_neighbors.bitor_assign_atomic(&a_neighbors_at_depth);
+        let deepen = |e: &[usize]| {
+            for &a in e {
+                let (a_neighbors_at_depth, prev_neighbors) = &neighbors_at_depth[a];
 
-                        let mut new_neighbors = BitVec::ZERO;
-                        for b in a_neighbors_at_depth.iter_ones() {
-                            for c in nodes.neighbors(NodeId::from_usize(b)) {
-                                new_neighbors.set_bit(c.as_usize(), true);
-                            }
-                        }
+                if a_neighbors_at_depth.is_zero() {
+                    continue;
+                }
 
-                        // new neighbors at this depth without the previous neighbors
-                        new_neighbors.bitand_not_assign_atomic(prev_neighbors);
-                        a_neighbors_at_depth.assign_from(&new_neighbors);
+                // add previous neighbors to prev neighbors
+                prev_neighbors.bitor_assign_atomic(a_neighbors_at_depth);
+
+                let mut new_neighbors = BitVec::ZERO;
+                for b in a_neighbors_at_depth.iter_ones() {
+                    for c in nodes.neighbors(NodeId::from_usize(b)) {
+                        new_neighbors.set_bit(c.as_usize(), true);
                     }
-                });
+                }
 
-            active_neighbors_mask.clear();
-        }
+                // new neighbors at this depth without the previous neighbors
+                new_neighbors.bitand_not_assign_atomic(prev_neighbors);
+                a_neighbors_at_depth.assign_from(&new_neighbors);
+            }
+        };
 
-        ParaGraph {
-            nodes,
-            edges: edges.inner,
-        }
+        // each node only updates its own neighbors, so the order doesn't matter here
+        let worklist = active_neighbors_mask.iter_ones().collect::<Vec<_>>();
+        worklist.par_chunks(CHUNK_SIZE).for_each(deepen);
     }
 
-    /// Return the number of nodes in this graph.
-    #[inline]
-    pub fn nodes_len(&self) -> usize {
-        self.nodes.len()
-    }
+    /// Finalize phase: return the built graph.
+    fn finish(self) -> ParaGraph<NodeId> {
+        let shared = &self.shared;
+        let edges = self
+            .edges
+            .inner
+            .into_par_iter()
+            .map(|(k, bits)| match shared.get(&k) {
+                Some(prev)
+                    if prev.0.len() == bits.0.len()
+                        && prev.into_bitvec().eq(&bits.into_bitvec()) =>
+                {
+                    (k, prev.clone())
+                }
+                _ => (k, Arc::new(bits)),
+            })
+            .collect();
 
-    /// Return the number of edges in this graph.
-    #[inline]
-    pub fn edges_len(&self) -> usize {
-        self.edges.inner.len()
+        ParaGraph {
+            ready: Arc::new(BitVec::ones(self.nodes.len())),
+            nodes: Arc::new(self.nodes),
+            edges: Arc::new(edges),
+            dests: None,
+            hubs: None,
+            stable_order: self.stable_order,
+        }
     }
+}
 
-    /// Return the neighbors of the given node.
-    #[inline]
-    pub fn neighbors(&self, node: NodeId) -> &[NodeId] {
-        self.nodes.neighbors(node)
-    }
+/// Return the initial `(frontier, previous frontiers)` of every node:
+/// its direct neighbors, and itself.
+fn initial_frontiers<NodeId: U16orU32>(
+    neighbors: &[Vec<NodeId>],
+) -> Vec<(AtomicBitVec, AtomicBitVec)> {
+    neighbors
+        .par_iter()
+        .enumerate()
+        .map(|(i, e)| {
+            let frontier = AtomicBitVec::zeros(neighbors.len());
+            for n in e {
+                frontier.set_bit(n.as_usize(), true);
+            }
+            (frontier, AtomicBitVec::one(i, neighbors.len()))
+        })
+        .collect()
 }
 
 /// Map of nodes and their neighbors.
@@ -583,6 +1162,8 @@ impl<NodeId: U16orU32> ParaGraphBuilder<NodeId> {
 #[derive(Debug, Clone)]
 pub struct Nodes<NodeId: U16orU32> {
     pub inner: Vec<Vec<NodeId>>,
+    /// User data of each edge, at the same positions as the neighbors in `inner`.
+    pub data: Vec<Vec<u16>>,
 }
 
 impl<NodeId: U16orU32> Nodes<NodeId> {
@@ -590,6 +1171,7 @@ impl<NodeId: U16orU32> Nodes<NodeId> {
     pub fn new(nodes_len: usize) -> Self {
         Self {
             inner: vec![vec![]; nodes_len],
+            data: vec![vec![]; nodes_len],
         }
     }
 
@@ -597,12 +1179,21 @@ impl<NodeId: U16orU32> Nodes<NodeId> {
     pub fn resize(&mut self, nodes_len: usize) {
         let prev_len = self.inner.len();
         self.inner.resize(nodes_len, vec![]);
+        self.data.resize(nodes_len, vec![]);
 
         if nodes_len < prev_len {
             let nodes_len = NodeId::from_usize(nodes_len);
 
-            for neighbors in self.inner.iter_mut() {
-                neighbors.retain(|&i| i < nodes_len);
+            for (neighbors, data) in self.inner.iter_mut().zip(self.data.iter_mut()) {
+                let mut i = 0;
+                while i < neighbors.len() {
+                    if neighbors[i] < nodes_len {
+                        i += 1;
+                    } else {
+                        neighbors.remove(i);
+                        data.remove(i);
+                    }
+                }
             }
         }
     }
@@ -614,16 +1205,86 @@ impl<NodeId: U16orU32> Nodes<NodeId> {
     }
 
     /// Add a edge between node_a and node_b
+    ///
+    /// A new edge starts with the user data `0`; an existing edge keeps its data.
     pub fn connect(&mut self, a: NodeId, b: NodeId) {
         if a == b {
             return;
         }
 
+        // each side is checked on its own, so a one-sided entry is not pushed twice
         if !self.inner[a.as_usize()].contains(&b) {
             self.inner[a.as_usize()].push(b);
+            self.data[a.as_usize()].push(0);
+        }
+
+        if !self.inner[b.as_usize()].contains(&a) {
+            self.inner[b.as_usize()].push(a);
+            self.data[b.as_usize()].push(0);
+        }
+    }
+
+    /// Repair the neighbor lists after they were edited by hand.
+    ///
+    /// Self-loops, out-of-range and duplicate neighbors are dropped,
+    /// and an edge listed on only one of its nodes is added to the other.
+    /// A duplicate keeps the data of its first entry,
+    /// and an added entry gets the data of the other side.
+    pub fn canonicalize(&mut self) {
+        let nodes_len = self.inner.len();
+        self.data.resize(nodes_len, vec![]);
+
+        for (a, (neighbors, data)) in self.inner.iter_mut().zip(self.data.iter_mut()).enumerate() {
+            data.resize(neighbors.len(), 0);
+
+            let mut i = 0;
+            while i < neighbors.len() {
+                let b = neighbors[i];
+                if b.as_usize() == a || b.as_usize() >= nodes_len || neighbors[..i].contains(&b) {
+                    neighbors.remove(i);
+                    data.remove(i);
+                } else {
+                    i += 1;
+                }
+            }
+        }
+
+        for a in 0..nodes_len {
+            for i in 0..self.inner[a].len() {
+                let b = self.inner[a][i].as_usize();
+                let a_id = NodeId::from_usize(a);
+
+                if !self.inner[b].contains(&a_id) {
+                    let data = self.data[a][i];
+                    self.inner[b].push(a_id);
+                    self.data[b].push(data);
+                }
+            }
         }
+    }
+
+    /// Set the user data of the edge between node_a and node_b.
+    ///
+    /// Returns `false` if there is no such edge.
+    pub fn set_data(&mut self, a: NodeId, b: NodeId, data: u16) -> bool {
+        let (Some(i), Some(j)) = (self.position(a, b), self.position(b, a)) else {
+            return false;
+        };
+
+        self.data[a.as_usize()][i] = data;
+        self.data[b.as_usize()][j] = data;
+        true
+    }
 
-        self.inner[b.as_usize()].push(a);
+    /// Get the user data of the edge between node_a and node_b.
+    #[inline]
+    pub fn data(&self, a: NodeId, b: NodeId) -> Option<u16> {
+        self.position(a, b).map(|i| self.data[a.as_usize()][i])
+    }
+
+    #[inline]
+    fn position(&self, a: NodeId, b: NodeId) -> Option<usize> {
+        self.inner.get(a.as_usize())?.iter().position(|&x| x == b)
     }
 
     /// Remove a edge between node_a and node_b
@@ -632,11 +1293,13 @@ impl<NodeId: U16orU32> Nodes<NodeId> {
             return;
         }
 
-        if let Some(index) = self.inner[a.as_usize()].iter().position(|&x| x == b) {
+        if let Some(index) = self.position(a, b) {
             self.inner[a.as_usize()].swap_remove(index);
+            self.data[a.as_usize()].swap_remove(index);
         }
-        if let Some(index) = self.inner[b.as_usize()].iter().position(|&x| x == a) {
+        if let Some(index) = self.position(b, a) {
             self.inner[b.as_usize()].swap_remove(index);
+            self.data[b.as_usize()].swap_remove(index);
         }
     }
 
@@ -644,6 +1307,17 @@ impl<NodeId: U16orU32> Nodes<NodeId> {
     pub fn len(&self) -> usize {
         self.inner.len()
     }
+
+    /// Return a hash of the number of nodes and the set of edges.
+    pub fn topology_hash(&self) -> u64 {
+        let edges = self
+            .inner
+            .iter()
+            .enumerate()
+            .flat_map(|(a, neighbors)| neighbors.iter().map(move |b| (a, b.as_usize())));
+
+        crate::topology_hash(self.len(), edges)
+    }
 }
 
 /// Map of edges and their shortest paths to other nodes.
@@ -683,8 +1357,10 @@ impl<NodeId: U16orU32> Edges<NodeId> {
         if let Some(bits) = self.inner.get_mut(&edge_id) {
             bits.bitor_assign(&val);
         } else {
+            let capacity = self.inner.capacity();
             self.inner
                 .insert(edge_id, AtomicBitVec::from_bitvec(&val, nodes_len));
+            record_map_grow(capacity, self.inner.capacity());
         }
     }
 
@@ -713,12 +1389,104 @@ impl<NodeId: U16orU32> Edges<NodeId> {
             edge.truncate(nodes_len);
         }
     }
+
+    /// Resize every edge's bit vector to the given length of nodes.
+    ///
+    /// Shrinking also removes the edges connected to the removed nodes, same as [truncate](Self::truncate).
+    pub fn resize(&mut self, nodes_len: usize) {
+        self.truncate(nodes_len);
+
+        for edge in self.inner.values_mut() {
+            edge.resize(nodes_len);
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// A `width x height` grid, and the distance of every node to each destination.
+    fn grid(width: usize, height: usize) -> (ParaGraphBuilder<u16>, Vec<Vec<u32>>) {
+        let mut builder = ParaGraphBuilder::new(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let a = (y * width + x) as u16;
+                if x + 1 < width {
+                    builder.connect(a, a + 1);
+                }
+                if y + 1 < height {
+                    builder.connect(a, a + width as u16);
+                }
+            }
+        }
+
+        let dists = (0..width * height)
+            .map(|dest| bfs_distances(&builder.nodes.inner, dest))
+            .collect();
+        (builder, dists)
+    }
+
+    /// Assert that every edge has computed exactly the destinations within `depth` hops of either node,
+    /// and that its bits for them lead toward them.
+    fn assert_edges_within(gossip: &Gossip<u16>, dists: &[Vec<u32>], depth: u32) {
+        for (x, x_neighbors) in gossip.nodes.inner.iter().enumerate() {
+            for &y in x_neighbors.iter().filter(|&&y| x < y as usize) {
+                let ab = (x as u16, y);
+                let mask = gossip.edge_masks.get(ab).unwrap();
+                let bits = gossip.edges.get(ab).unwrap();
+
+                for (dest, dist) in dists.iter().enumerate() {
+                    let (dx, dy) = (dist[x], dist[y as usize]);
+                    assert_eq!(mask.get_bit(dest), dx.min(dy) <= depth, "{ab:?} to {dest}");
+                    if dx.min(dy) <= depth {
+                        assert_eq!(bits.get_bit(dest), dy < dx, "{ab:?} to {dest}");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Assert that the frontier of every node is the nodes exactly `depth` hops away,
+    /// and its previous frontiers are the nodes closer than that.
+    fn assert_frontiers_at(gossip: &Gossip<u16>, dists: &[Vec<u32>], depth: u32) {
+        for (a, (frontier, prev)) in gossip.neighbors_at_depth.iter().enumerate() {
+            for (dest, dist) in dists.iter().enumerate() {
+                assert_eq!(frontier.get_bit(dest), dist[a] == depth, "{a} to {dest}");
+                assert_eq!(prev.get_bit(dest), dist[a] < depth, "{a} to {dest}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_build_phases() {
+        let (builder, dists) = grid(4, 4);
+
+        // setup computes the direct neighbors of both nodes of every edge
+        let gossip = Gossip::new(builder);
+        assert_edges_within(&gossip, &dists, 1);
+        assert_frontiers_at(&gossip, &dists, 1);
+
+        // each round gossips one hop further, and advances every frontier by one hop
+        for depth in 2..=3 {
+            let (done, active) = gossip.gossip();
+            assert_edges_within(&gossip, &dists, depth);
+            assert!(active.eq(&BitVec::ones(16)));
+            assert!(done.is_zero());
+
+            gossip.advance_frontiers(&active);
+            assert_frontiers_at(&gossip, &dists, depth);
+        }
+
+        // the longest path of the grid is 6 hops
+        let mut rounds = 0;
+        while !gossip.round() {
+            rounds += 1;
+        }
+        assert!(rounds <= 4);
+        assert_edges_within(&gossip, &dists, 6);
+    }
+
     #[ignore]
     #[test]
     fn test_para_graph() {
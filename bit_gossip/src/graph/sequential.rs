@@ -1,11 +1,38 @@
-use super::U16orU32;
-use crate::{bitvec::BitVec, edge_id};
-use std::{collections::HashMap, fmt::Debug};
-
+use super::{
+    bfs_distances,
+    dests::{self, DestColumns},
+    hubs::HubSummary,
+    store::{EdgeStore, HashMapStore},
+    yielding::{should_yield, YieldNow},
+    EdgeEdit, U16orU32,
+};
+use crate::{
+    bitvec::{BitVec, IterOnes},
+    edge_id,
+};
+use std::{fmt::Debug, sync::Arc};
+
+/// Graph built by [SeqGraphBuilder].
+///
+/// The edges' bit vectors are kept in the store `S`; see the [store](super::store) module.
+///
+/// A built graph is read-only, so its nodes and edges are shared behind an [Arc];
+/// cloning the graph is cheap and does not copy any bit vectors.
 #[derive(Debug, Clone)]
-pub struct SeqGraph<NodeId: U16orU32 = u16> {
-    pub nodes: Nodes<NodeId>,
-    pub edges: HashMap<(NodeId, NodeId), BitVec>,
+pub struct SeqGraph<NodeId: U16orU32 = u16, S: EdgeStore<NodeId> = HashMapStore<NodeId>> {
+    pub nodes: Arc<Nodes<NodeId>>,
+    pub edges: Arc<S>,
+    /// Columns of the retained destinations, if the graph was narrowed down with
+    /// [retain_dests](Self::retain_dests); `None` if every node is a destination.
+    pub dests: Option<Arc<DestColumns>>,
+    /// Edge summaries of high-degree nodes, if enabled with [summarize_hubs](Self::summarize_hubs).
+    pub hubs: Option<Arc<HubSummary>>,
+    /// Destinations whose bits are up to date; see [ready_dests](Self::ready_dests).
+    pub ready: Arc<BitVec>,
+    /// Whether the graph was built with [parallel_setup](SeqGraphBuilder::parallel_setup);
+    /// [into_builder](Self::into_builder) keeps the setting for the next build.
+    #[cfg(feature = "parallel")]
+    pub parallel_setup: bool,
 }
 
 impl<NodeId: U16orU32> SeqGraph<NodeId> {
@@ -15,12 +42,21 @@ impl<NodeId: U16orU32> SeqGraph<NodeId> {
     /// If you need more nodes, you can specify u32 as the NodeId type, like `SeqGraph::<u32>::builder(100_000)`
     #[inline]
     pub fn builder(nodes_len: usize) -> SeqGraphBuilder<NodeId> {
+        Self::builder_with_store(nodes_len)
+    }
+}
+
+impl<NodeId: U16orU32, S: EdgeStore<NodeId>> SeqGraph<NodeId, S> {
+    /// Create a new SeqGraphBuilder with the given number of nodes,
+    /// storing the edges in the store `S`.
+    #[inline]
+    pub fn builder_with_store(nodes_len: usize) -> SeqGraphBuilder<NodeId, S> {
         debug_assert!(
             nodes_len <= NodeId::MAX_NODES,
             "Number of nodes exceeds the limit; Specify `u32` as the NodeId type, like `SeqGraph::<u32>::builder(100_000)`"
         );
 
-        SeqGraphBuilder::new(nodes_len.min(NodeId::MAX_NODES))
+        SeqGraphBuilder::with_store(nodes_len.min(NodeId::MAX_NODES))
     }
 
     /// Converts this graph into a builder.
@@ -29,15 +65,193 @@ impl<NodeId: U16orU32> SeqGraph<NodeId> {
     /// like resizing nodes or adding/removing edges.
     ///
     /// Then you can build the graph again.
+    ///
+    /// If this graph is still shared with other clones, its nodes are copied first.
+    ///
+    /// No computed paths are carried over: the builder holds the edges as if they were just connected,
+    /// so the rebuilt graph is the same as one built from scratch with the same edges.
+    /// This also means a graph narrowed down with [retain_dests](Self::retain_dests)
+    /// has every destination again once rebuilt.
     #[inline]
-    pub fn into_builder(self) -> SeqGraphBuilder<NodeId> {
-        SeqGraphBuilder {
-            edge_masks: Edges {
-                inner: self.edges.iter().map(|(k, _)| (*k, BitVec::ZERO)).collect(),
-            },
-            edges: Edges { inner: self.edges },
-            nodes: self.nodes,
+    pub fn into_builder(self) -> SeqGraphBuilder<NodeId, S> {
+        let shared = if self.dests.is_some() {
+            // packed bits can't be compared with the rebuilt ones
+            S::default()
+        } else {
+            Arc::try_unwrap(self.edges)
+                .unwrap_or_else(|edges| (*edges).clone())
+                .into_shared()
+        };
+
+        let mut builder = SeqGraphBuilder {
+            edge_masks: S::default(),
+            edges: S::default(),
+            shared,
+            nodes: Arc::try_unwrap(self.nodes).unwrap_or_else(|nodes| (*nodes).clone()),
+            #[cfg(feature = "parallel")]
+            parallel_setup: self.parallel_setup,
+        };
+        builder.reset_computed();
+        builder
+    }
+
+    /// Drop the bits of all destinations except `dests`, to save memory in a special-purpose graph;
+    /// e.g. a strategic AI that only ever paths to a few objective nodes.
+    ///
+    /// The bits of the retained destinations are packed together,
+    /// so each edge takes one bit per retained destination instead of one per node.
+    /// Queries to retained destinations work as before;
+    /// queries to any other destination return `None`, as if there were no path.
+    ///
+    /// Call this on a clone to keep the full graph available elsewhere.
+    /// Calling it again narrows the retained destinations further.
+    pub fn retain_dests(&mut self, dests: &BitVec) {
+        let prev = self.dests.as_deref();
+        let columns = DestColumns::narrow(prev, dests);
+
+        let mut edges = S::default();
+        for (k, bits) in self.edges.iter() {
+            edges.insert(k, columns.pack(prev, &bits));
+        }
+        edges.compact();
+
+        self.edges = Arc::new(edges);
+        self.dests = Some(Arc::new(columns));
+        self.ready = Arc::new(dests::intersect(&self.ready, dests));
+        self.refresh_hubs();
+    }
+
+    /// Apply the given edge edits in place, and recompute the bits of only the destinations `dests`
+    /// with a plain BFS from each of them.
+    ///
+    /// This is meant for graphs where only a handful of destinations are ever queried,
+    /// like a base or a spawn point, to update them instantly on a door-open event
+    /// instead of rebuilding the whole graph.
+    ///
+    /// Queries to `dests` are up to date afterwards;
+    /// the bits of all other destinations are left as they were, and may be wrong
+    /// until the graph is rebuilt.
+    /// Destinations dropped with [retain_dests](Self::retain_dests) are skipped.
+    ///
+    /// Only the edge bits that actually change are written.
+    pub fn retarget_after_edits(&mut self, edits: &[EdgeEdit<NodeId>], dests: &BitVec) {
+        let nodes = Arc::make_mut(&mut self.nodes);
+        let edges = Arc::make_mut(&mut self.edges);
+
+        for edit in edits {
+            match *edit {
+                EdgeEdit::Connect(a, b) => {
+                    nodes.connect(a, b);
+                    if !edges.contains(edge_id(a, b)) {
+                        edges.insert(edge_id(a, b), BitVec::ZERO);
+                    }
+                }
+                EdgeEdit::Disconnect(a, b) => {
+                    nodes.disconnect(a, b);
+                    edges.remove(edge_id(a, b));
+                }
+            }
+        }
+
+        let mut retargeted = BitVec::ZERO;
+
+        for dest in dests.iter_ones().take_while(|&dest| dest < nodes.len()) {
+            let Some(index) = self.dests.as_deref().map_or(Some(dest), |d| d.column(dest)) else {
+                continue;
+            };
+            retargeted.set_bit(dest, true);
+
+            let dist = bfs_distances(&nodes.inner, dest);
+
+            for (a, a_neighbors) in nodes.inner.iter().enumerate() {
+                for &b in a_neighbors.iter().filter(|b| a < b.as_usize()) {
+                    let ab = (NodeId::from_usize(a), b);
+
+                    // from the lower node's perspective, the edge leads toward dest
+                    let val = dist[b.as_usize()] < dist[a];
+
+                    if edges.get_bit(ab, index) != Some(val) {
+                        let mut bits = edges.remove(ab).unwrap_or(BitVec::ZERO);
+                        bits.set_bit(index, val);
+                        edges.insert(ab, bits);
+                    }
+                }
+            }
         }
+        edges.compact();
+
+        // after an edit, only the retargeted destinations are up to date
+        if edits.is_empty() {
+            retargeted.bitor_assign(&self.ready);
+        }
+        self.ready = Arc::new(retargeted);
+
+        self.refresh_hubs();
+    }
+
+    /// Return whether the bits of the given destination are up to date,
+    /// so that queries to it return its shortest paths.
+    ///
+    /// See [ready_dests](Self::ready_dests).
+    #[inline]
+    pub fn dest_ready(&self, dest: NodeId) -> bool {
+        self.ready.get_bit(dest.as_usize())
+    }
+
+    /// Return the destinations whose bits are up to date.
+    ///
+    /// Every node is ready after a build. Destinations dropped with [retain_dests](Self::retain_dests)
+    /// are not ready, and after [retarget_after_edits](Self::retarget_after_edits) with any edits,
+    /// only the retargeted destinations are ready.
+    #[inline]
+    pub fn ready_dests(&self) -> &BitVec {
+        &self.ready
+    }
+
+    /// Summarize the edges of every node with at least `min_degree` neighbors,
+    /// so that queries from those nodes only check the edges that may lead to the destination,
+    /// instead of every edge; see the [hubs](super::hubs) module.
+    ///
+    /// This pays off on star-like graphs, where a few nodes have hundreds of neighbors or more.
+    /// The summary is kept up to date by [retain_dests](Self::retain_dests)
+    /// and [retarget_after_edits](Self::retarget_after_edits).
+    pub fn summarize_hubs(&mut self, min_degree: usize) {
+        let columns = self
+            .dests
+            .as_deref()
+            .map_or(self.nodes.len(), DestColumns::len);
+        let edges = &*self.edges;
+
+        let hubs = HubSummary::new(&self.nodes.inner, columns, min_degree, |a, b| {
+            edges.get((a, b))
+        });
+        self.hubs = Some(Arc::new(hubs));
+    }
+
+    /// Summarize the hubs again with the same minimum degree, if they are summarized.
+    fn refresh_hubs(&mut self) {
+        if let Some(min_degree) = self.hubs.as_deref().map(HubSummary::min_degree) {
+            self.summarize_hubs(min_degree);
+        }
+    }
+
+    /// Return the bit index of the given destination in the edges' bits,
+    /// or `None` if the destination was dropped with [retain_dests](Self::retain_dests).
+    #[inline]
+    fn dest_index(&self, dest: NodeId) -> Option<usize> {
+        match &self.dests {
+            Some(dests) => dests.column(dest.as_usize()),
+            None => Some(dest.as_usize()),
+        }
+    }
+
+    /// Return the positions of `curr`'s neighbors to check for the given bit index,
+    /// or `None` if `curr` is not a summarized hub and every neighbor must be checked.
+    #[inline]
+    fn hub_candidates(&self, curr: NodeId, dest_index: usize) -> Option<IterOnes<'_>> {
+        self.hubs
+            .as_deref()?
+            .candidates(curr.as_usize(), dest_index)
     }
 
     /// Given a current node and a destination node,
@@ -57,6 +271,7 @@ impl<NodeId: U16orU32> SeqGraph<NodeId> {
     /// or [neighbors_to](Self::neighbors_to) to get all neighboring nodes.
     #[inline]
     pub fn neighbor_to(&self, curr: NodeId, dest: NodeId) -> Option<NodeId> {
+        check_nodes!("neighbor_to", self.nodes_len(), curr, dest);
         self.neighbors_to(curr, dest).next()
     }
 
@@ -80,6 +295,7 @@ impl<NodeId: U16orU32> SeqGraph<NodeId> {
         dest: NodeId,
         f: impl Fn(NodeId) -> bool,
     ) -> Option<NodeId> {
+        check_nodes!("neighbor_to_with", self.nodes_len(), curr, dest);
         self.neighbors_to(curr, dest).find(|&n| f(n))
     }
 
@@ -88,12 +304,18 @@ impl<NodeId: U16orU32> SeqGraph<NodeId> {
     ///
     /// The nodes will be returned in the same order for the same inputs. However, the ordering of the nodes is not guaranteed.
     #[inline]
-    pub fn neighbors_to(&self, curr: NodeId, dest: NodeId) -> NeighborsToIter<'_, NodeId> {
+    pub fn neighbors_to(&self, curr: NodeId, dest: NodeId) -> NeighborsToIter<'_, NodeId, S> {
+        check_nodes!("neighbors_to", self.nodes_len(), curr, dest);
+        let dest_index = self.dest_index(dest);
+        let candidates = dest_index.and_then(|index| self.hub_candidates(curr, index));
+
         NeighborsToIter {
             graph: self,
             neighbors: self.nodes.neighbors(curr).iter(),
+            candidates,
             curr,
             dest,
+            dest_index,
         }
     }
 
@@ -106,27 +328,65 @@ impl<NodeId: U16orU32> SeqGraph<NodeId> {
     ///
     /// If there is no path, the list will be empty.
     #[inline]
-    pub fn path_to(&self, curr: NodeId, dest: NodeId) -> PathIter<'_, NodeId> {
+    pub fn path_to(&self, curr: NodeId, dest: NodeId) -> PathIter<'_, NodeId, S> {
+        check_nodes!("path_to", self.nodes_len(), curr, dest);
         PathIter {
             map: self,
             curr,
             dest,
+            dest_index: self.dest_index(dest),
+            prev: None,
             init: false,
         }
     }
 
+    /// Given a current node and a source node,
+    /// return the node right before `curr` on a shortest path from `src` to `curr`.
+    ///
+    /// The graph is undirected, so this is the neighbor of `curr` that is one step closer to `src`;
+    /// calling it repeatedly retraces the way back to `src`.
+    ///
+    /// `None` is returned when:
+    /// - `curr` and `src` are the same node
+    /// - there is no path from `src` to `curr`
+    #[inline]
+    pub fn prev_node(&self, curr: NodeId, src: NodeId) -> Option<NodeId> {
+        check_nodes!("prev_node", self.nodes_len(), curr, src);
+        self.neighbor_to(curr, src)
+    }
+
     /// Check if there is a path from the current node to the destination node.
     #[inline]
     pub fn path_exists(&self, curr: NodeId, dest: NodeId) -> bool {
+        check_nodes!("path_exists", self.nodes_len(), curr, dest);
         self.neighbor_to(curr, dest).is_some()
     }
 
     /// Return a list of all neighboring nodes of the given node.
     #[inline]
     pub fn neighbors(&self, node: NodeId) -> &[NodeId] {
+        check_nodes!("neighbors", self.nodes_len(), node);
         self.nodes.neighbors(node)
     }
 
+    /// Return the user data attached to the edge between node_a and node_b.
+    ///
+    /// `None` is returned when there is no edge between the two nodes.
+    #[inline]
+    pub fn edge_data(&self, a: NodeId, b: NodeId) -> Option<u16> {
+        self.nodes.data(a, b)
+    }
+
+    /// Return a hash of this graph's topology: the number of nodes and the set of edges.
+    ///
+    /// The hash does not depend on the order edges were added in or on the internal layout,
+    /// so it can be used as a cache key to check whether a map has changed since it was last built.
+    /// The same topology gives the same hash for every graph type and builder.
+    #[inline]
+    pub fn topology_hash(&self) -> u64 {
+        self.nodes.topology_hash()
+    }
+
     /// Return the number of nodes in this graph.
     #[inline]
     pub fn nodes_len(&self) -> usize {
@@ -141,15 +401,21 @@ impl<NodeId: U16orU32> SeqGraph<NodeId> {
 }
 
 /// An iterator that returns a path from the current node to the destination node.
+///
+/// The destination's bit index is resolved once for the whole path,
+/// and the edge back to the previous node is skipped at each step,
+/// since it always leads away from the destination.
 #[derive(Debug)]
-pub struct PathIter<'a, NodeId: U16orU32> {
-    map: &'a SeqGraph<NodeId>,
+pub struct PathIter<'a, NodeId: U16orU32, S: EdgeStore<NodeId> = HashMapStore<NodeId>> {
+    map: &'a SeqGraph<NodeId, S>,
     curr: NodeId,
     dest: NodeId,
+    dest_index: Option<usize>,
+    prev: Option<NodeId>,
     init: bool,
 }
 
-impl<NodeId: U16orU32> Iterator for PathIter<'_, NodeId> {
+impl<NodeId: U16orU32, S: EdgeStore<NodeId>> Iterator for PathIter<'_, NodeId, S> {
     type Item = NodeId;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -162,26 +428,52 @@ impl<NodeId: U16orU32> Iterator for PathIter<'_, NodeId> {
             return Some(self.curr);
         }
 
-        let Some(next) = self.map.neighbor_to(self.curr, self.dest) else {
-            return None;
-        };
+        let dest_index = self.dest_index?;
+        let curr = self.curr;
+        let neighbors = self.map.nodes.neighbors(curr);
 
-        self.curr = next;
+        let mut all = 0..neighbors.len();
+        let mut candidates = self.map.hub_candidates(curr, dest_index);
+        let positions = std::iter::from_fn(|| match &mut candidates {
+            Some(candidates) => candidates.next(),
+            None => all.next(),
+        });
 
-        Some(next)
+        for neighbor in positions.map(|i| neighbors[i]) {
+            if Some(neighbor) == self.prev {
+                continue;
+            }
+
+            let bit = self
+                .map
+                .edges
+                .get_bit(edge_id(curr, neighbor), dest_index)?;
+            let bit = if curr > neighbor { !bit } else { bit };
+
+            if bit {
+                self.prev = Some(curr);
+                self.curr = neighbor;
+                return Some(neighbor);
+            }
+        }
+
+        None
     }
 }
 
 /// An iterator that returns neighboring nodes that are shortest paths to the destination node.
 #[derive(Debug)]
-pub struct NeighborsToIter<'a, NodeId: U16orU32> {
-    graph: &'a SeqGraph<NodeId>,
+pub struct NeighborsToIter<'a, NodeId: U16orU32, S: EdgeStore<NodeId> = HashMapStore<NodeId>> {
+    graph: &'a SeqGraph<NodeId, S>,
     curr: NodeId,
     dest: NodeId,
+    dest_index: Option<usize>,
     neighbors: std::slice::Iter<'a, NodeId>,
+    /// positions in `neighbors` of the edges to check, if `curr` is a summarized hub
+    candidates: Option<IterOnes<'a>>,
 }
 
-impl<NodeId: U16orU32> Iterator for NeighborsToIter<'_, NodeId> {
+impl<NodeId: U16orU32, S: EdgeStore<NodeId>> Iterator for NeighborsToIter<'_, NodeId, S> {
     type Item = NodeId;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -189,26 +481,32 @@ impl<NodeId: U16orU32> Iterator for NeighborsToIter<'_, NodeId> {
             return None;
         }
 
-        while let Some(&neighbor) = self.neighbors.next() {
+        let dest_index = self.dest_index?;
+
+        loop {
+            let neighbor = match &mut self.candidates {
+                Some(candidates) => self.graph.nodes.neighbors(self.curr)[candidates.next()?],
+                None => *self.neighbors.next()?,
+            };
+
             let bit = self
                 .graph
                 .edges
-                .get(&edge_id(self.curr, neighbor))?
-                .get_bit(self.dest.as_usize());
+                .get_bit(edge_id(self.curr, neighbor), dest_index)?;
             let bit = if self.curr > neighbor { !bit } else { bit };
 
             if bit {
                 return Some(neighbor);
             }
         }
-
-        None
     }
 }
 
 /// A builder for creating a [SeqGraph].
+///
+/// The edges' bit vectors are kept in the store `S`; see the [store](super::store) module.
 #[derive(Debug, Clone)]
-pub struct SeqGraphBuilder<NodeId: U16orU32> {
+pub struct SeqGraphBuilder<NodeId: U16orU32, S: EdgeStore<NodeId> = HashMapStore<NodeId>> {
     /// key: node_id
     ///
     /// value: neighbors of node
@@ -218,25 +516,60 @@ pub struct SeqGraphBuilder<NodeId: U16orU32> {
     ///
     /// value: for each bit, if this edge is the shortest path
     /// to that bit location's node, bit is set to 1
-    pub edges: Edges<NodeId>,
+    pub edges: S,
 
     /// key: edge_id
     ///
     /// value: for each edge, bit is set to 1 if the node is computed
-    pub edge_masks: Edges<NodeId>,
+    pub edge_masks: S,
+
+    /// bits of the graph this builder was converted from that are shared with other graphs;
+    /// the built graph shares the bits that are equal to them, instead of keeping a copy
+    pub shared: S,
+
+    /// run the initial per-node setup pass on multiple threads;
+    /// see [parallel_setup](Self::parallel_setup)
+    #[cfg(feature = "parallel")]
+    pub parallel_setup: bool,
 }
 
 impl<NodeId: U16orU32> SeqGraphBuilder<NodeId> {
     /// Create a new SeqGraphBuilder with the given number of nodes.
     #[inline]
     pub fn new(nodes_len: usize) -> Self {
+        Self::with_store(nodes_len)
+    }
+}
+
+impl<NodeId: U16orU32, S: EdgeStore<NodeId>> SeqGraphBuilder<NodeId, S> {
+    /// Create a new SeqGraphBuilder with the given number of nodes,
+    /// storing the edges in the store `S`.
+    #[inline]
+    pub fn with_store(nodes_len: usize) -> Self {
         Self {
             nodes: Nodes::new(nodes_len),
-            edges: Edges::new(),
-            edge_masks: Edges::new(),
+            edges: S::default(),
+            edge_masks: S::default(),
+            shared: S::default(),
+            #[cfg(feature = "parallel")]
+            parallel_setup: false,
         }
     }
 
+    /// Run the initial per-node setup pass of the build on multiple threads.
+    ///
+    /// The setup pass computes each edge's bits for its nodes' direct neighbors,
+    /// which is independent for every node; the rest of the build stays sequential,
+    /// and the result is the same [SeqGraph].
+    /// This cuts the startup latency of big graphs,
+    /// at the cost of holding the setup results of all nodes at once before they are merged.
+    #[cfg(feature = "parallel")]
+    #[inline]
+    pub fn parallel_setup(mut self, parallel_setup: bool) -> Self {
+        self.parallel_setup = parallel_setup;
+        self
+    }
+
     /// Resize the graph to the given number of nodes.
     ///
     /// All edges that are connected to nodes that are removed will also be removed.
@@ -255,30 +588,94 @@ impl<NodeId: U16orU32> SeqGraphBuilder<NodeId> {
     #[inline]
     pub fn connect(&mut self, a: NodeId, b: NodeId) {
         self.nodes.connect(a, b);
+        self.reset_edge(edge_id(a, b));
+    }
+
+    /// Forget all computed paths, so the next build computes every path from scratch.
+    ///
+    /// Every edge of `nodes` is reset to the state of a freshly connected edge,
+    /// and entries of `edges` and `edge_masks` that are not edges of `nodes` are dropped.
+    /// [SeqGraph::into_builder] calls this, so rebuilding after edits gives the same paths
+    /// as building the edited graph from scratch.
+    pub fn reset_computed(&mut self) {
+        self.edges = S::default();
+        self.edge_masks = S::default();
+
+        let edges = (0..self.nodes.len())
+            .map(NodeId::from_usize)
+            .flat_map(|a| self.nodes.neighbors(a).iter().map(move |&b| (a, b)))
+            .filter(|(a, b)| a < b)
+            .collect::<Vec<_>>();
+
+        for ab in edges {
+            self.reset_edge(ab);
+        }
+    }
 
+    /// Set the bits of the edge to those of a freshly connected edge.
+    #[inline]
+    fn reset_edge(&mut self, ab: (NodeId, NodeId)) {
         // edge value is flipped to b -> a, which means from node b's perspective, this edge is:
         // - gets further away from b
         // - shortest path to a
         // - gets further away from all other nodes
-        let val = if a > b { a } else { b };
+        self.edges.insert(ab, BitVec::one(ab.1.as_usize()));
 
-        let ab = edge_id(a, b);
+        let mut mask = BitVec::one(ab.1.as_usize());
+        mask.set_bit(ab.0.as_usize(), true);
+        self.edge_masks.insert(ab, mask);
+    }
 
-        if let Some(edge) = self.edges.inner.get_mut(&ab) {
-            edge.set_bit(val.as_usize(), true);
-        } else {
-            let edge = BitVec::one(val.as_usize());
-            self.edges.inner.insert(ab, edge);
+    /// Repair the builder before a build, so edits made through the public fields
+    /// cannot stall the gossip.
+    ///
+    /// The neighbor lists are canonicalized, entries of `edges` and `edge_masks` whose key
+    /// is not an ascending edge of `nodes` are dropped,
+    /// and edges missing from either store are reset to a freshly connected edge.
+    fn canonicalize(&mut self) {
+        self.nodes.canonicalize();
+
+        let nodes = &self.nodes;
+        let is_edge = |(a, b): (NodeId, NodeId)| a < b && nodes.position(a, b).is_some();
+
+        let stale = self
+            .edges
+            .iter()
+            .chain(self.edge_masks.iter())
+            .map(|(ab, _)| ab)
+            .filter(|&ab| !is_edge(ab))
+            .collect::<Vec<_>>();
+
+        for ab in stale {
+            self.edges.remove(ab);
+            self.edge_masks.remove(ab);
         }
 
-        if let Some(edge) = self.edge_masks.inner.get_mut(&ab) {
-            edge.set_bit(a.as_usize(), true);
-            edge.set_bit(b.as_usize(), true);
-        } else {
-            let mut edge = BitVec::one(a.max(b).as_usize());
-            edge.set_bit(a.min(b).as_usize(), true);
-            self.edge_masks.inner.insert(ab, edge);
+        let missing = (0..self.nodes.len())
+            .map(NodeId::from_usize)
+            .flat_map(|a| self.nodes.neighbors(a).iter().map(move |&b| (a, b)))
+            .filter(|&(a, b)| a < b)
+            .filter(|&ab| !self.edges.contains(ab) || !self.edge_masks.contains(ab))
+            .collect::<Vec<_>>();
+
+        for ab in missing {
+            self.edges.remove(ab);
+            self.edge_masks.remove(ab);
+            self.reset_edge(ab);
         }
+
+        self.edges.compact();
+        self.edge_masks.compact();
+    }
+
+    /// Add an edge between node_a and node_b, with user data attached to it.
+    ///
+    /// The data is not used for pathfinding; it can be read back with `edge_data`.
+    /// If the edge already exists, its data is replaced.
+    #[inline]
+    pub fn connect_with_data(&mut self, a: NodeId, b: NodeId, data: u16) {
+        self.connect(a, b);
+        self.nodes.set_data(a, b, data);
     }
 
     #[inline]
@@ -288,296 +685,504 @@ impl<NodeId: U16orU32> SeqGraphBuilder<NodeId> {
 
         let ab = edge_id(a, b);
 
-        if self.edge_masks.inner.remove(&ab).is_some() {
-            self.edges.inner.remove(&ab);
+        if self.edge_masks.remove(ab).is_some() {
+            self.edges.remove(ab);
         }
     }
 
     #[inline]
-    pub fn build(self) -> SeqGraph<NodeId> {
-        let Self {
-            nodes,
-            mut edges,
-            mut edge_masks,
-            ..
-        } = self;
+    pub fn build(self) -> SeqGraph<NodeId, S> {
+        let mut gossip = Gossip::new(self);
+        while !gossip.round() {}
+        gossip.finish()
+    }
 
-        // (neighbors at current depth, neighbors at previous depths)
-        let mut neighbors_at_depth: Vec<(BitVec, BitVec)> = nodes
-            .inner
-            .iter()
-            .enumerate()
-            .map(|(i, e)| {
-                let mut neighbors = BitVec::ZERO;
-                for n in e {
-                    neighbors.set_bit(n.as_usize(), true);
-                }
-                (neighbors, BitVec::one(i))
-            })
-            .collect();
+    /// Build the graph, calling `yield_fn` after every `every_n_rounds` rounds of gossip.
+    ///
+    /// This lets a long build report progress, or check on other work, while it runs.
+    /// `every_n_rounds` of `0` never calls `yield_fn`.
+    pub fn build_yielding(
+        self,
+        every_n_rounds: usize,
+        mut yield_fn: impl FnMut(),
+    ) -> SeqGraph<NodeId, S> {
+        let mut gossip = Gossip::new(self);
+        let mut rounds = 0;
+        while !gossip.round() {
+            rounds += 1;
+            if should_yield(rounds, every_n_rounds) {
+                yield_fn();
+            }
+        }
+        gossip.finish()
+    }
 
-        let mut active_neighbors_mask = BitVec::ZERO;
+    /// Build the graph inside an async task,
+    /// yielding to the executor after every `every_n_rounds` rounds of gossip.
+    ///
+    /// A build is CPU-bound and takes up to seconds for large graphs;
+    /// yielding lets the other tasks of the executor's thread run in between,
+    /// without spawning a dedicated thread for the build.
+    /// `every_n_rounds` of `0` never yields.
+    pub async fn build_async(self, every_n_rounds: usize) -> SeqGraph<NodeId, S> {
+        let mut gossip = Gossip::new(self);
+        let mut rounds = 0;
+        while !gossip.round() {
+            rounds += 1;
+            if should_yield(rounds, every_n_rounds) {
+                YieldNow::default().await;
+            }
+        }
+        gossip.finish()
+    }
 
-        // each rooom's bit is set to 1 if all its edges are done computed
-        let mut done_nodes = BitVec::ZERO;
+    /// Return a hash of this graph's topology: the number of nodes and the set of edges.
+    ///
+    /// The hash does not depend on the order edges were added in or on the internal layout,
+    /// so it can be used as a cache key to check whether a map has changed since it was last built.
+    /// The same topology gives the same hash for every graph type and builder.
+    #[inline]
+    pub fn topology_hash(&self) -> u64 {
+        self.nodes.topology_hash()
+    }
 
-        let full_mask = BitVec::ones(nodes.len());
+    /// Return the number of nodes in this graph.
+    #[inline]
+    pub fn nodes_len(&self) -> usize {
+        self.nodes.len()
+    }
 
-        let mut neighbor_upserts: Vec<(BitVec, BitVec, BitVec)> = Vec::new();
+    /// Return the number of edges in this graph.
+    #[inline]
+    pub fn edges_len(&self) -> usize {
+        self.edges.len()
+    }
 
-        for (a, a_neighbors) in nodes.inner.iter().enumerate() {
-            // setup
-            // clear upserts
-            neighbor_upserts.iter_mut().for_each(|(e1, e2, e3)| {
-                e1.clear();
-                e2.clear();
-                e3.clear();
-            });
-            if neighbor_upserts.len() < a_neighbors.len() {
-                neighbor_upserts.resize(
-                    a_neighbors.len(),
-                    (BitVec::ZERO, BitVec::ZERO, BitVec::ZERO),
-                );
-            }
+    /// Return the neighbors of the given node.
+    #[inline]
+    pub fn neighbors(&self, node: NodeId) -> &[NodeId] {
+        self.nodes.neighbors(node)
+    }
 
-            // for each edge in this node
-            // set the bit value for a and b as 1
-            for (i, b) in a_neighbors.iter().enumerate() {
-                let b = b.as_usize();
+    /// Return the user data attached to the edge between node_a and node_b.
+    #[inline]
+    pub fn edge_data(&self, a: NodeId, b: NodeId) -> Option<u16> {
+        self.nodes.data(a, b)
+    }
+}
 
-                let mut val = true;
+/// State of a [SeqGraphBuilder] build between its rounds of gossip.
+///
+/// A build runs in phases: [new](Self::new) sets up the edges of every node with its direct neighbors,
+/// then each [round](Self::round) gossips the current frontier of every node across its neighbors' edges
+/// and advances the frontiers one hop further, until every node is done;
+/// [finish](Self::finish) turns the state into the built graph.
+struct Gossip<NodeId: U16orU32, S: EdgeStore<NodeId>> {
+    nodes: Nodes<NodeId>,
+    edges: S,
+    edge_masks: S,
+    shared: S,
+    #[cfg(feature = "parallel")]
+    parallel_setup: bool,
 
-                // edge value is flipped to b -> a, which means from node b's perspective, this edge is:
-                // - gets further away from b
-                // - shortest path to a
-                // - gets further away from all other nodes
-                if a > b {
-                    val = false;
-                }
+    /// (neighbors at current depth, neighbors at previous depths)
+    neighbors_at_depth: Vec<(BitVec, BitVec)>,
 
-                // for all other edges in this node, set the value for this node bit as 0
-                for (j, c) in a_neighbors.iter().enumerate() {
-                    if i == j {
-                        continue;
-                    }
+    /// each rooom's bit is set to 1 if all its edges are done computed
+    done_nodes: BitVec,
 
-                    // if both b and c are in the same corner (tl or br)
-                    // flip the bit
-                    let should_set = if (a > b) == (a > c.as_usize()) {
-                        !val
-                    } else {
-                        val
-                    };
+    full_mask: BitVec,
 
-                    let (upsert, computed, _) = &mut neighbor_upserts[j];
-                    if should_set {
-                        upsert.set_bit(b, true);
-                    }
-                    computed.set_bit(b, true);
-                }
-            }
+    neighbor_upserts: Vec<(BitVec, BitVec, BitVec)>,
 
-            let a = NodeId::from_usize(a);
+    set_done_list: Vec<NodeId>,
+}
 
-            // apply computed values
-            for (b, upserts) in a_neighbors.into_iter().zip(neighbor_upserts.drain(..)) {
-                let ab = edge_id(a, *b);
+impl<NodeId: U16orU32, S: EdgeStore<NodeId>> Gossip<NodeId, S> {
+    /// Set up the edges of every node with their direct neighbors.
+    fn new(mut builder: SeqGraphBuilder<NodeId, S>) -> Self {
+        builder.canonicalize();
 
-                let (upsert, computed, _) = upserts;
+        let SeqGraphBuilder {
+            nodes,
+            edges,
+            edge_masks,
+            shared,
+            #[cfg(feature = "parallel")]
+            parallel_setup,
+        } = builder;
+
+        #[cfg(not(feature = "parallel"))]
+        let parallel_setup = false;
+
+        let mut gossip = Gossip {
+            neighbors_at_depth: initial_frontiers(&nodes.inner),
+            done_nodes: BitVec::ZERO,
+            full_mask: BitVec::ones(nodes.len()),
+            neighbor_upserts: Vec::new(),
+            set_done_list: Vec::new(),
+            nodes,
+            edges,
+            edge_masks,
+            shared,
+            #[cfg(feature = "parallel")]
+            parallel_setup,
+        };
 
-                if !computed.is_zero() {
-                    if !upsert.is_zero() {
-                        edges.insert(ab, upsert);
-                    }
-                    edge_masks.insert(ab, computed);
-                }
+        gossip.setup(parallel_setup);
+        gossip
+    }
+
+    /// Setup phase: set the bits of every edge for the direct neighbors of its nodes,
+    /// with the per-node passes run on multiple threads if `parallel`.
+    fn setup(&mut self, parallel: bool) {
+        let Gossip {
+            nodes,
+            edges,
+            edge_masks,
+            ..
+        } = self;
+
+        #[cfg(feature = "parallel")]
+        if parallel {
+            use rayon::prelude::*;
+
+            let upserts: Vec<Vec<(BitVec, BitVec)>> = nodes
+                .inner
+                .par_iter()
+                .enumerate()
+                .map(|(a, a_neighbors)| setup_upserts(a, a_neighbors))
+                .collect();
+
+            for (a, upserts) in upserts.into_iter().enumerate() {
+                apply_upserts(edges, edge_masks, nodes, a, upserts);
             }
+            return;
         }
+        #[cfg(not(feature = "parallel"))]
+        let _ = parallel;
 
-        let mut set_done_list = Vec::new();
+        for (a, a_neighbors) in nodes.inner.iter().enumerate() {
+            let upserts = setup_upserts(a, a_neighbors);
+            apply_upserts(edges, edge_masks, nodes, a, upserts);
+        }
+    }
 
-        loop {
-            // iterate through all undone nodes
-            for a in done_nodes.iter_zeros() {
-                if a >= nodes.len() {
-                    break;
-                }
+    /// Run a round of gossip, and return whether every node is done.
+    fn round(&mut self) -> bool {
+        let active_neighbors_mask = self.gossip();
 
-                let a_usize = a;
-                let a = NodeId::from_usize(a);
-
-                let a_neighbors = nodes.neighbors(a);
-
-                // clear upserts
-                neighbor_upserts.iter_mut().for_each(|(e1, e2, e3)| {
-                    e1.clear();
-                    e2.clear();
-                    e3.clear();
-                });
-                if neighbor_upserts.len() < a_neighbors.len() {
-                    neighbor_upserts.resize(
-                        a_neighbors.len(),
-                        (BitVec::ZERO, BitVec::ZERO, BitVec::ZERO),
-                    );
-                }
+        if self.done_nodes.eq(&self.full_mask) {
+            return true;
+        }
 
-                // collect all nodes that need to update their neighbors to next depth
-                let mut a_active_neighbors_mask = BitVec::ZERO;
+        self.advance_frontiers(&active_neighbors_mask);
+        false
+    }
 
-                // are all edges computed for this node?
-                let mut all_edges_done = true;
+    /// Gossip phase of a round: every node that is not done gossips its neighbors' frontiers
+    /// to its other edges, and is marked done if it had nothing left to gossip.
+    ///
+    /// Return the neighbors whose frontiers were gossiped, whose frontiers are advanced next.
+    fn gossip(&mut self) -> BitVec {
+        let done_nodes = std::mem::replace(&mut self.done_nodes, BitVec::ZERO);
+        let mut active_neighbors_mask = BitVec::ZERO;
 
-                // get all neighbors' masks
-                // so we can just reuse it
-                for (i, b) in a_neighbors.iter().enumerate() {
-                    let mask = edge_masks.get(edge_id(a, *b)).unwrap();
-                    neighbor_upserts[i].2 = mask.clone();
+        // iterate through all undone nodes
+        for a in done_nodes.iter_zeros() {
+            if a >= self.nodes.len() {
+                break;
+            }
 
-                    if !mask.eq(&full_mask) {
-                        all_edges_done = false;
-                    }
-                }
+            let a_active_neighbors_mask = self.gossip_node(a);
 
-                if all_edges_done {
-                    set_done_list.push(a);
+            // if none of a's neighbors are active, then a is done
+            if a_active_neighbors_mask.is_zero() {
+                self.set_done_list.push(NodeId::from_usize(a));
+            }
 
-                    continue;
-                }
+            active_neighbors_mask.bitor_assign(&a_active_neighbors_mask);
+        }
 
-                for (i, b) in a_neighbors.iter().copied().enumerate() {
-                    let b_usize = b.as_usize();
+        self.done_nodes = done_nodes;
+        for a in self.set_done_list.drain(..) {
+            self.done_nodes.set_bit(a.as_usize(), true);
+        }
 
-                    // neighbors' bits to gossip from edge a->b to other edges
-                    let mut neighbors_mask = neighbors_at_depth[b_usize].0.clone();
+        active_neighbors_mask
+    }
 
-                    neighbors_mask.set_bit(a_usize, false);
+    /// Gossip the frontiers of node `a`'s neighbors to `a`'s other edges,
+    /// and return the neighbors that had a frontier to gossip;
+    /// none if all of `a`'s edges are already computed.
+    fn gossip_node(&mut self, a: usize) -> BitVec {
+        let Gossip {
+            nodes,
+            edges,
+            edge_masks,
+            neighbors_at_depth,
+            full_mask,
+            neighbor_upserts,
+            ..
+        } = self;
 
-                    // if no neighbors to gossip at this depth, skip
-                    if neighbors_mask.is_zero() {
-                        continue;
-                    }
+        let a_usize = a;
+        let a = NodeId::from_usize(a);
+
+        let a_neighbors = nodes.neighbors(a);
+
+        // clear upserts
+        neighbor_upserts.iter_mut().for_each(|(e1, e2, e3)| {
+            e1.clear();
+            e2.clear();
+            e3.clear();
+        });
+        if neighbor_upserts.len() < a_neighbors.len() {
+            neighbor_upserts.resize(
+                a_neighbors.len(),
+                (BitVec::ZERO, BitVec::ZERO, BitVec::ZERO),
+            );
+        }
 
-                    a_active_neighbors_mask.set_bit(b_usize, true);
+        // collect all nodes that need to update their neighbors to next depth
+        let mut a_active_neighbors_mask = BitVec::ZERO;
 
-                    let ab = edge_id(a, b);
+        // are all edges computed for this node?
+        let mut all_edges_done = true;
 
-                    let val = edges.get(ab).unwrap();
+        // get all neighbors' masks
+        // so we can just reuse it
+        for (i, b) in a_neighbors.iter().enumerate() {
+            let mask = edge_masks.get(edge_id(a, *b)).unwrap().into_owned();
 
-                    // gossip to other edges about its neighbors at current depth
-                    for (j, c) in a_neighbors.iter().copied().enumerate() {
-                        // skip if same neighbor
-                        if i == j {
-                            continue;
-                        }
+            if !mask.eq(full_mask) {
+                all_edges_done = false;
+            }
 
-                        let mask_ac = &neighbor_upserts[j].2;
-                        if mask_ac.eq(&full_mask) {
-                            continue;
-                        }
-                        all_edges_done = false;
+            neighbor_upserts[i].2 = mask;
+        }
 
-                        let mut compute_mask = neighbors_mask.clone();
-                        // dont set bits that are already computed
-                        compute_mask.bitand_not_assign(&mask_ac);
+        if all_edges_done {
+            return a_active_neighbors_mask;
+        }
 
-                        // if all bits are already computed, skip
-                        if compute_mask.is_zero() {
-                            continue;
-                        }
+        for (i, b) in a_neighbors.iter().copied().enumerate() {
+            let b_usize = b.as_usize();
 
-                        let (upsert, computed, _) = &mut neighbor_upserts[j];
+            // neighbors' bits to gossip from edge a->b to other edges
+            let mut neighbors_mask = neighbors_at_depth[b_usize].0.clone();
 
-                        // if both b and c are in the same corner (tl or br)
-                        // flip the bit
-                        if (a_usize > b_usize) == (a_usize > c.as_usize()) {
-                            upsert.bitor_not_and_assign(val, &compute_mask);
-                        } else {
-                            upsert.bitor_and_assign(val, &compute_mask);
-                        };
+            neighbors_mask.set_bit(a_usize, false);
 
-                        computed.bitor_assign(&compute_mask);
-                    }
-                }
+            // if no neighbors to gossip at this depth, skip
+            if neighbors_mask.is_zero() {
+                continue;
+            }
 
-                // if all edges are computed or none of a's neighbors are active,
-                // then a is done
-                if all_edges_done || a_active_neighbors_mask.is_zero() {
-                    set_done_list.push(a);
-                } else {
-                    for (b, upserts) in a_neighbors.iter().copied().zip(neighbor_upserts.drain(..))
-                    {
-                        let ab = edge_id(a, b);
-
-                        let (upsert, computed, _) = upserts;
-
-                        if !computed.is_zero() {
-                            if !upsert.is_zero() {
-                                edges.insert(ab, upsert);
-                            }
-                            edge_masks.insert(ab, computed);
-                        }
-                    }
-                }
+            a_active_neighbors_mask.set_bit(b_usize, true);
 
-                active_neighbors_mask.bitor_assign(&a_active_neighbors_mask);
-            }
+            let ab = edge_id(a, b);
 
-            for a in &set_done_list {
-                done_nodes.set_bit(a.as_usize(), true);
-            }
-            set_done_list.clear();
+            let val = edges.get(ab).unwrap();
 
-            if done_nodes.eq(&full_mask) {
-                break;
-            }
+            // gossip to other edges about its neighbors at current depth
+            for (j, c) in a_neighbors.iter().copied().enumerate() {
+                // skip if same neighbor
+                if i == j {
+                    continue;
+                }
+
+                let mask_ac = &neighbor_upserts[j].2;
+                if mask_ac.eq(full_mask) {
+                    continue;
+                }
 
-            for a in active_neighbors_mask.iter_ones() {
-                let (a_neighbors_at_depth, prev_neighbors) = &mut neighbors_at_depth[a];
+                let mut compute_mask = neighbors_mask.clone();
+                // dont set bits that are already computed
+                compute_mask.bitand_not_assign(mask_ac);
 
-                if a_neighbors_at_depth.is_zero() {
+                // if all bits are already computed, skip
+                if compute_mask.is_zero() {
                     continue;
                 }
 
-                // add previous neighbors to prev neighbors
-                prev_neighbors.bitor_assign(&a_neighbors_at_depth);
+                let (upsert, computed, _) = &mut neighbor_upserts[j];
+
+                // if both b and c are in the same corner (tl or br)
+                // flip the bit
+                if (a_usize > b_usize) == (a_usize > c.as_usize()) {
+                    upsert.bitor_not_and_assign(&val, &compute_mask);
+                } else {
+                    upsert.bitor_and_assign(&val, &compute_mask);
+                };
+
+                computed.bitor_assign(&compute_mask);
+            }
+        }
+
+        // the upserts are only applied if a is not done
+        if !a_active_neighbors_mask.is_zero() {
+            for (b, upserts) in a_neighbors.iter().copied().zip(neighbor_upserts.drain(..)) {
+                let ab = edge_id(a, b);
+
+                let (upsert, computed, _) = upserts;
 
-                let mut new_neighbors = BitVec::ZERO;
-                for b in a_neighbors_at_depth.iter_ones() {
-                    for c in nodes.neighbors(NodeId::from_usize(b)) {
-                        new_neighbors.set_bit(c.as_usize(), true);
+                if !computed.is_zero() {
+                    if !upsert.is_zero() {
+                        edges.insert(ab, upsert);
                     }
+                    edge_masks.insert(ab, computed);
                 }
+            }
+        }
 
-                // new neighbors at this depth without the previous neighbors
-                new_neighbors.bitand_not_assign(&prev_neighbors);
-                *a_neighbors_at_depth = new_neighbors;
+        a_active_neighbors_mask
+    }
+
+    /// Frontier phase of a round: advance the frontier of every node in `active_neighbors_mask`
+    /// to the nodes one hop further, that are not in any of its previous frontiers.
+    fn advance_frontiers(&mut self, active_neighbors_mask: &BitVec) {
+        let Gossip {
+            nodes,
+            neighbors_at_depth,
+            ..
+        } = self;
+
+        for a in active_neighbors_mask.iter_ones() {
+            let (a_neighbors_at_depth, prev_neighbors) = &mut neighbors_at_depth[a];
+
+            if a_neighbors_at_depth.is_zero() {
+                continue;
+            }
+
+            // add previous neighbors to prev neighbors
+            prev_neighbors.bitor_assign(a_neighbors_at_depth);
+
+            let mut new_neighbors = BitVec::ZERO;
+            for b in a_neighbors_at_depth.iter_ones() {
+                for c in nodes.neighbors(NodeId::from_usize(b)) {
+                    new_neighbors.set_bit(c.as_usize(), true);
+                }
             }
 
-            active_neighbors_mask.clear();
+            // new neighbors at this depth without the previous neighbors
+            new_neighbors.bitand_not_assign(prev_neighbors);
+            *a_neighbors_at_depth = new_neighbors;
         }
+    }
 
-        SeqGraph {
+    /// Finalize phase: share the bits that are equal to the ones of the graph the builder came from,
+    /// and return the built graph.
+    fn finish(self) -> SeqGraph<NodeId, S> {
+        let Gossip {
             nodes,
-            edges: edges.inner,
+            mut edges,
+            shared,
+            #[cfg(feature = "parallel")]
+            parallel_setup,
+            ..
+        } = self;
+
+        edges.share_equal(&shared);
+
+        SeqGraph {
+            ready: Arc::new(BitVec::ones(nodes.len())),
+            nodes: Arc::new(nodes),
+            edges: Arc::new(edges),
+            dests: None,
+            hubs: None,
+            #[cfg(feature = "parallel")]
+            parallel_setup,
         }
     }
+}
 
-    /// Return the number of nodes in this graph.
-    #[inline]
-    pub fn nodes_len(&self) -> usize {
-        self.nodes.len()
-    }
+/// Return the initial `(frontier, previous frontiers)` of every node:
+/// its direct neighbors, and itself.
+fn initial_frontiers<NodeId: U16orU32>(neighbors: &[Vec<NodeId>]) -> Vec<(BitVec, BitVec)> {
+    neighbors
+        .iter()
+        .enumerate()
+        .map(|(i, e)| {
+            let mut neighbors = BitVec::ZERO;
+            for n in e {
+                neighbors.set_bit(n.as_usize(), true);
+            }
+            (neighbors, BitVec::one(i))
+        })
+        .collect()
+}
 
-    /// Return the number of edges in this graph.
-    #[inline]
-    pub fn edges_len(&self) -> usize {
-        self.edges.inner.len()
+/// Setup pass of the build for node `a`: from `a`'s direct neighbors alone,
+/// compute the `(bits, computed mask)` to merge into each of `a`'s edges,
+/// in the same order as `a_neighbors`.
+pub(super) fn setup_upserts<NodeId: U16orU32>(
+    a: usize,
+    a_neighbors: &[NodeId],
+) -> Vec<(BitVec, BitVec)> {
+    let mut neighbor_upserts = vec![(BitVec::ZERO, BitVec::ZERO); a_neighbors.len()];
+
+    // for each edge in this node
+    // set the bit value for a and b as 1
+    for (i, b) in a_neighbors.iter().enumerate() {
+        let b = b.as_usize();
+
+        let mut val = true;
+
+        // edge value is flipped to b -> a, which means from node b's perspective, this edge is:
+        // - gets further away from b
+        // - shortest path to a
+        // - gets further away from all other nodes
+        if a > b {
+            val = false;
+        }
+
+        // for all other edges in this node, set the value for this node bit as 0
+        for (j, c) in a_neighbors.iter().enumerate() {
+            if i == j {
+                continue;
+            }
+
+            // if both b and c are in the same corner (tl or br)
+            // flip the bit
+            let should_set = if (a > b) == (a > c.as_usize()) {
+                !val
+            } else {
+                val
+            };
+
+            let (upsert, computed) = &mut neighbor_upserts[j];
+            if should_set {
+                upsert.set_bit(b, true);
+            }
+            computed.set_bit(b, true);
+        }
     }
 
-    /// Return the neighbors of the given node.
-    #[inline]
-    pub fn neighbors(&self, node: NodeId) -> &[NodeId] {
-        self.nodes.neighbors(node)
+    neighbor_upserts
+}
+
+/// Merge the results of [setup_upserts] for node `a` into the edges.
+fn apply_upserts<NodeId: U16orU32, S: EdgeStore<NodeId>>(
+    edges: &mut S,
+    edge_masks: &mut S,
+    nodes: &Nodes<NodeId>,
+    a: usize,
+    upserts: Vec<(BitVec, BitVec)>,
+) {
+    let a_neighbors = &nodes.inner[a];
+    let a = NodeId::from_usize(a);
+
+    // apply computed values
+    for (b, (upsert, computed)) in a_neighbors.iter().zip(upserts) {
+        let ab = edge_id(a, *b);
+
+        if !computed.is_zero() {
+            if !upsert.is_zero() {
+                edges.insert(ab, upsert);
+            }
+            edge_masks.insert(ab, computed);
+        }
     }
 }
 
@@ -589,6 +1194,8 @@ impl<NodeId: U16orU32> SeqGraphBuilder<NodeId> {
 #[derive(Debug, Clone)]
 pub struct Nodes<NodeId: U16orU32> {
     pub inner: Vec<Vec<NodeId>>,
+    /// User data of each edge, at the same positions as the neighbors in `inner`.
+    pub data: Vec<Vec<u16>>,
 }
 
 impl<NodeId: U16orU32> Nodes<NodeId> {
@@ -596,6 +1203,7 @@ impl<NodeId: U16orU32> Nodes<NodeId> {
     pub fn new(nodes_len: usize) -> Self {
         Self {
             inner: vec![vec![]; nodes_len],
+            data: vec![vec![]; nodes_len],
         }
     }
 
@@ -603,12 +1211,21 @@ impl<NodeId: U16orU32> Nodes<NodeId> {
     pub fn resize(&mut self, nodes_len: usize) {
         let prev_len = self.inner.len();
         self.inner.resize(nodes_len, vec![]);
+        self.data.resize(nodes_len, vec![]);
 
         if nodes_len < prev_len {
             let nodes_len = NodeId::from_usize(nodes_len);
 
-            for neighbors in self.inner.iter_mut() {
-                neighbors.retain(|&i| i < nodes_len);
+            for (neighbors, data) in self.inner.iter_mut().zip(self.data.iter_mut()) {
+                let mut i = 0;
+                while i < neighbors.len() {
+                    if neighbors[i] < nodes_len {
+                        i += 1;
+                    } else {
+                        neighbors.remove(i);
+                        data.remove(i);
+                    }
+                }
             }
         }
     }
@@ -620,17 +1237,87 @@ impl<NodeId: U16orU32> Nodes<NodeId> {
     }
 
     /// Add a edge between node_a and node_b
+    ///
+    /// A new edge starts with the user data `0`; an existing edge keeps its data.
     #[inline]
     pub fn connect(&mut self, a: NodeId, b: NodeId) {
         if a == b {
             return;
         }
 
+        // each side is checked on its own, so a one-sided entry is not pushed twice
         if !self.inner[a.as_usize()].contains(&b) {
             self.inner[a.as_usize()].push(b);
+            self.data[a.as_usize()].push(0);
+        }
+
+        if !self.inner[b.as_usize()].contains(&a) {
+            self.inner[b.as_usize()].push(a);
+            self.data[b.as_usize()].push(0);
+        }
+    }
+
+    /// Repair the neighbor lists after they were edited by hand.
+    ///
+    /// Self-loops, out-of-range and duplicate neighbors are dropped,
+    /// and an edge listed on only one of its nodes is added to the other.
+    /// A duplicate keeps the data of its first entry,
+    /// and an added entry gets the data of the other side.
+    pub fn canonicalize(&mut self) {
+        let nodes_len = self.inner.len();
+        self.data.resize(nodes_len, vec![]);
+
+        for (a, (neighbors, data)) in self.inner.iter_mut().zip(self.data.iter_mut()).enumerate() {
+            data.resize(neighbors.len(), 0);
+
+            let mut i = 0;
+            while i < neighbors.len() {
+                let b = neighbors[i];
+                if b.as_usize() == a || b.as_usize() >= nodes_len || neighbors[..i].contains(&b) {
+                    neighbors.remove(i);
+                    data.remove(i);
+                } else {
+                    i += 1;
+                }
+            }
+        }
+
+        for a in 0..nodes_len {
+            for i in 0..self.inner[a].len() {
+                let b = self.inner[a][i].as_usize();
+                let a_id = NodeId::from_usize(a);
+
+                if !self.inner[b].contains(&a_id) {
+                    let data = self.data[a][i];
+                    self.inner[b].push(a_id);
+                    self.data[b].push(data);
+                }
+            }
         }
+    }
+
+    /// Set the user data of the edge between node_a and node_b.
+    ///
+    /// Returns `false` if there is no such edge.
+    pub fn set_data(&mut self, a: NodeId, b: NodeId, data: u16) -> bool {
+        let (Some(i), Some(j)) = (self.position(a, b), self.position(b, a)) else {
+            return false;
+        };
 
-        self.inner[b.as_usize()].push(a);
+        self.data[a.as_usize()][i] = data;
+        self.data[b.as_usize()][j] = data;
+        true
+    }
+
+    /// Get the user data of the edge between node_a and node_b.
+    #[inline]
+    pub fn data(&self, a: NodeId, b: NodeId) -> Option<u16> {
+        self.position(a, b).map(|i| self.data[a.as_usize()][i])
+    }
+
+    #[inline]
+    fn position(&self, a: NodeId, b: NodeId) -> Option<usize> {
+        self.inner.get(a.as_usize())?.iter().position(|&x| x == b)
     }
 
     /// Remove a edge between node_a and node_b
@@ -640,11 +1327,13 @@ impl<NodeId: U16orU32> Nodes<NodeId> {
             return;
         }
 
-        if let Some(index) = self.inner[a.as_usize()].iter().position(|&x| x == b) {
+        if let Some(index) = self.position(a, b) {
             self.inner[a.as_usize()].swap_remove(index);
+            self.data[a.as_usize()].swap_remove(index);
         }
-        if let Some(index) = self.inner[b.as_usize()].iter().position(|&x| x == a) {
+        if let Some(index) = self.position(b, a) {
             self.inner[b.as_usize()].swap_remove(index);
+            self.data[b.as_usize()].swap_remove(index);
         }
     }
 
@@ -652,71 +1341,111 @@ impl<NodeId: U16orU32> Nodes<NodeId> {
     pub fn len(&self) -> usize {
         self.inner.len()
     }
-}
 
-/// Map of edges and their shortest paths to other nodes.
-///
-/// key: edge_id
-///
-/// value: for each bit, if this edge is the shortest path
-/// to that bit location's node, bit is set to 1
-#[derive(Debug, Clone)]
-pub struct Edges<NodeId: U16orU32> {
-    /// key: edge_id
-    ///
-    /// value: for each bit, if this edge is the shortest path
-    /// to that bit location's node, bit is set to 1
-    inner: HashMap<(NodeId, NodeId), BitVec>,
+    /// Return a hash of the number of nodes and the set of edges.
+    pub fn topology_hash(&self) -> u64 {
+        let edges = self
+            .inner
+            .iter()
+            .enumerate()
+            .flat_map(|(a, neighbors)| neighbors.iter().map(move |b| (a, b.as_usize())));
+
+        crate::topology_hash(self.len(), edges)
+    }
 }
 
-impl<NodeId: U16orU32> Edges<NodeId> {
-    #[inline]
-    fn new() -> Self {
-        Self {
-            inner: HashMap::new(),
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `width x height` grid, and the distance of every node to each destination.
+    fn grid(width: usize, height: usize) -> (SeqGraphBuilder<u16>, Vec<Vec<u32>>) {
+        let mut builder = SeqGraphBuilder::new(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let a = (y * width + x) as u16;
+                if x + 1 < width {
+                    builder.connect(a, a + 1);
+                }
+                if y + 1 < height {
+                    builder.connect(a, a + width as u16);
+                }
+            }
         }
-    }
 
-    /// Return the shortest-paths-indicating bit vector.
-    #[inline]
-    pub fn get(&self, edge_id: (NodeId, NodeId)) -> Option<&BitVec> {
-        self.inner.get(&edge_id)
+        let dists = (0..width * height)
+            .map(|dest| bfs_distances(&builder.nodes.inner, dest))
+            .collect();
+        (builder, dists)
     }
 
-    /// Insert a new edge with its shortest paths.
-    ///
-    /// If the edge already exists, the shortest paths will be merged.
-    #[inline]
-    pub fn insert(&mut self, edge_id: (NodeId, NodeId), val: BitVec) {
-        if let Some(bits) = self.inner.get_mut(&edge_id) {
-            bits.bitor_assign(&val);
-        } else {
-            self.inner.insert(edge_id, val);
+    /// Assert that every edge has computed exactly the destinations within `depth` hops of either node,
+    /// and that its bits for them lead toward them.
+    fn assert_edges_within(
+        gossip: &Gossip<u16, HashMapStore<u16>>,
+        dists: &[Vec<u32>],
+        depth: u32,
+    ) {
+        for (x, x_neighbors) in gossip.nodes.inner.iter().enumerate() {
+            for &y in x_neighbors.iter().filter(|&&y| x < y as usize) {
+                let ab = (x as u16, y);
+                let mask = gossip.edge_masks.get(ab).unwrap();
+                let bits = gossip.edges.get(ab).unwrap();
+
+                for (dest, dist) in dists.iter().enumerate() {
+                    let (dx, dy) = (dist[x], dist[y as usize]);
+                    assert_eq!(mask.get_bit(dest), dx.min(dy) <= depth, "{ab:?} to {dest}");
+                    if dx.min(dy) <= depth {
+                        assert_eq!(bits.get_bit(dest), dy < dx, "{ab:?} to {dest}");
+                    }
+                }
+            }
         }
     }
 
-    /// Truncate the edges to the given length of nodes.
-    pub fn truncate(&mut self, nodes_len: usize) {
-        let keys_to_remove = self
-            .inner
-            .keys()
-            .filter(|&(a, b)| a.as_usize() >= nodes_len || b.as_usize() >= nodes_len)
-            .cloned()
-            .collect::<Vec<_>>();
+    /// Assert that the frontier of every node is the nodes exactly `depth` hops away,
+    /// and its previous frontiers are the nodes closer than that.
+    fn assert_frontiers_at(
+        gossip: &Gossip<u16, HashMapStore<u16>>,
+        dists: &[Vec<u32>],
+        depth: u32,
+    ) {
+        for (a, (frontier, prev)) in gossip.neighbors_at_depth.iter().enumerate() {
+            for (dest, dist) in dists.iter().enumerate() {
+                assert_eq!(frontier.get_bit(dest), dist[a] == depth, "{a} to {dest}");
+                assert_eq!(prev.get_bit(dest), dist[a] < depth, "{a} to {dest}");
+            }
+        }
+    }
 
-        for key in keys_to_remove {
-            self.inner.remove(&key);
+    #[test]
+    fn test_build_phases() {
+        let (builder, dists) = grid(4, 4);
+
+        // setup computes the direct neighbors of both nodes of every edge
+        let mut gossip = Gossip::new(builder);
+        assert_edges_within(&gossip, &dists, 1);
+        assert_frontiers_at(&gossip, &dists, 1);
+
+        // each round gossips one hop further, and advances every frontier by one hop
+        for depth in 2..=3 {
+            let active = gossip.gossip();
+            assert_edges_within(&gossip, &dists, depth);
+            assert!(active.eq(&BitVec::ones(16)));
+            assert!(gossip.done_nodes.is_zero());
+
+            gossip.advance_frontiers(&active);
+            assert_frontiers_at(&gossip, &dists, depth);
         }
 
-        for edge in self.inner.values_mut() {
-            edge.truncate(nodes_len);
+        // the longest path of the grid is 6 hops
+        let mut rounds = 0;
+        while !gossip.round() {
+            rounds += 1;
         }
+        assert!(rounds <= 4);
+        assert_edges_within(&gossip, &dists, 6);
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
     #[ignore]
     #[test]
@@ -0,0 +1,769 @@
+//! destination-sharded graphs, for maps whose bits don't fit in memory.
+//!
+//! A built graph keeps one bit per destination node in each edge, so its size grows with
+//! `nodes × edges`. [GraphBuilder::build_sharded](super::GraphBuilder::build_sharded) splits the
+//! destination columns into shards, computes one shard at a time, and writes each to its own file.
+//! The returned [ShardedGraph] keeps only the topology in memory, and loads the shard of a
+//! query's destination when it is needed, keeping the few most recently used ones resident.
+//!
+//! Each shard is computed with a breadth-first search from every destination in it,
+//! so only the bits of a single shard are in memory at a time during the build.
+//!
+//! ```
+//! use bit_gossip::Graph;
+//!
+//! // a corridor of 100 nodes, in 4 shards of 25 destinations
+//! let mut builder = Graph::builder(100);
+//! for i in 0..99u16 {
+//!     builder.connect(i, i + 1);
+//! }
+//! let graph = builder.build_sharded(4).unwrap().with_resident_shards(1);
+//!
+//! assert_eq!(graph.neighbor_to(0, 99), Some(1));
+//! assert_eq!(graph.neighbor_to(50, 10), Some(49));
+//! assert_eq!(graph.path_to(2, 0).collect::<Vec<_>>(), vec![2, 1, 0]);
+//! ```
+
+use super::{bfs_distances, greedy_neighbor_to, U16orU32};
+use crate::bitvec::BitVec;
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, PoisonError, TryLockError,
+    },
+};
+
+/// Number of shards kept in memory by default.
+const DEFAULT_RESIDENT_SHARDS: usize = 2;
+
+/// Graph whose shortest paths are split by destination into shard files,
+/// built by [GraphBuilder::build_sharded](super::GraphBuilder::build_sharded).
+///
+/// Queries take `&self` and can be made from multiple threads;
+/// a query whose destination's shard is not resident reads it from its file first.
+///
+/// The shard files are removed when the graph is dropped.
+pub struct ShardedGraph<NodeId: U16orU32 = u16> {
+    neighbors: Vec<Vec<NodeId>>,
+    /// index of each neighbor's edge in a row of the shard files,
+    /// at the same positions as the neighbors in `neighbors`
+    edge_ids: Vec<Vec<u32>>,
+    /// connected component of each node; nodes in different components have no path
+    components: Vec<u32>,
+    edges_len: usize,
+    dests_per_shard: usize,
+    files: Vec<PathBuf>,
+    /// directory created for the shard files, removed along with them
+    temp_dir: Option<PathBuf>,
+    cache: Mutex<ShardCache>,
+    /// notified when a shard that was being read is resident, or its read failed
+    loaded: Condvar,
+}
+
+/// Shards resident in memory, most recently used last.
+struct ShardCache {
+    resident: usize,
+    shards: Vec<(usize, Arc<Vec<u64>>)>,
+    /// shards being read from their files, so other threads wait for them instead of reading them again
+    loading: Vec<usize>,
+    loads: usize,
+}
+
+impl ShardCache {
+    /// Return the shard with the given index if it is resident, and mark it as the most recently used.
+    fn touch(&mut self, index: usize) -> Option<Arc<Vec<u64>>> {
+        let i = self.shards.iter().position(|(s, _)| *s == index)?;
+        let entry = self.shards.remove(i);
+        let shard = entry.1.clone();
+        self.shards.push(entry);
+        Some(shard)
+    }
+}
+
+impl<NodeId: U16orU32> ShardedGraph<NodeId> {
+    /// Compute the shards of the given neighbor lists, and write them to `dir`.
+    ///
+    /// If `temp_dir` is true, `dir` was created for this graph and is removed when it is dropped.
+    pub(crate) fn build(
+        neighbors: Vec<Vec<NodeId>>,
+        shards: usize,
+        dir: &Path,
+        temp_dir: bool,
+    ) -> io::Result<Self> {
+        let nodes_len = neighbors.len();
+        let shards = shards.max(1);
+        let dests_per_shard = ((nodes_len + shards - 1) / shards).max(1);
+        let (edge_ids, edges_len) = number_edges(&neighbors);
+        let components = components(&neighbors);
+
+        let mut graph = Self {
+            neighbors,
+            edge_ids,
+            components,
+            edges_len,
+            dests_per_shard,
+            files: Vec::new(),
+            temp_dir: temp_dir.then(|| dir.to_path_buf()),
+            cache: Mutex::new(ShardCache {
+                resident: DEFAULT_RESIDENT_SHARDS,
+                shards: Vec::new(),
+                loading: Vec::new(),
+                loads: 0,
+            }),
+            loaded: Condvar::new(),
+        };
+
+        // if writing a shard fails, dropping the graph removes the files written so far
+        for first in (0..nodes_len).step_by(dests_per_shard) {
+            let path = dir.join(format!("shard-{}.bin", graph.files.len()));
+            let rows = graph.compute_shard(first..nodes_len.min(first + dests_per_shard));
+
+            graph.files.push(path.clone());
+            let mut file = BufWriter::new(File::create(&path)?);
+            for word in rows {
+                file.write_all(&word.to_le_bytes())?;
+            }
+            file.flush()?;
+        }
+
+        Ok(graph)
+    }
+
+    /// Keep up to `resident` shards in memory; at least one is always kept.
+    ///
+    /// More resident shards mean fewer file reads when queries jump between destinations,
+    /// at the cost of the memory of each shard.
+    pub fn with_resident_shards(self, resident: usize) -> Self {
+        self.cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .resident = resident.max(1);
+        self
+    }
+
+    /// Given a current node and a destination node,
+    /// return the first neighboring node that is the shortest path to the destination node.
+    ///
+    /// `None` is returned when:
+    /// - `curr` and `dest` are the same node
+    /// - `curr` has no path to `dest`
+    ///
+    /// Panics if the shard of `dest` has to be loaded and its file can't be read;
+    /// use [try_neighbor_to](Self::try_neighbor_to) to handle the error instead.
+    #[inline]
+    pub fn neighbor_to(&self, curr: NodeId, dest: NodeId) -> Option<NodeId> {
+        self.try_neighbor_to(curr, dest).unwrap_or_else(|err| {
+            panic!(
+                "failed to load the shard of node {}: {err}",
+                dest.as_usize()
+            )
+        })
+    }
+
+    /// Same as [neighbor_to](Self::neighbor_to),
+    /// but returns an error if the shard of `dest` has to be loaded and its file can't be read.
+    pub fn try_neighbor_to(&self, curr: NodeId, dest: NodeId) -> io::Result<Option<NodeId>> {
+        check_nodes!("neighbor_to", self.nodes_len(), curr, dest);
+
+        if !self.has_path(curr, dest) {
+            return Ok(None);
+        }
+
+        let (shard, row) = self.load(dest)?;
+        let next = self.towards(curr, &shard[row]).next();
+        Ok(next)
+    }
+
+    /// Same as [neighbor_to](Self::neighbor_to) if the shard of `dest` is resident;
+    /// otherwise, instead of reading it from its file, fall back to a breadth-first search
+    /// of at most `hop_budget` hops from `curr`, so agents keep moving while the shard is loaded,
+    /// e.g. with [prefetch](Self::prefetch) on another thread.
+    ///
+    /// This never reads a file, nor waits for another thread that is reading one.
+    /// See [Graph::neighbor_to_or_greedy](super::Graph::neighbor_to_or_greedy).
+    pub fn neighbor_to_or_greedy(
+        &self,
+        curr: NodeId,
+        dest: NodeId,
+        hop_budget: usize,
+    ) -> Option<NodeId> {
+        check_nodes!("neighbor_to_or_greedy", self.nodes_len(), curr, dest);
+
+        if !self.has_path(curr, dest) {
+            return None;
+        }
+
+        match self.resident(dest) {
+            Some((shard, row)) => self.towards(curr, &shard[row]).next(),
+            None => greedy_neighbor_to(|node| self.neighbors(node), curr, dest, hop_budget, None),
+        }
+    }
+
+    /// Return whether the shard of `dest` is resident,
+    /// so that queries to it don't have to read its file.
+    ///
+    /// Like [neighbor_to_or_greedy](Self::neighbor_to_or_greedy), this never waits:
+    /// it returns `false` while the shard is being read, or if another thread is updating
+    /// the resident shards at that moment.
+    pub fn dest_ready(&self, dest: NodeId) -> bool {
+        check_nodes!("dest_ready", self.nodes_len(), dest);
+
+        let (index, _) = self.locate(dest);
+        self.try_cache(|cache| cache.shards.iter().any(|(s, _)| *s == index))
+            .unwrap_or(false)
+    }
+
+    /// Return the destinations whose shards are resident; see [dest_ready](Self::dest_ready).
+    ///
+    /// The shards are loaded and evicted as queries come in,
+    /// so this is a snapshot; it is empty if another thread is updating the resident shards
+    /// at that moment.
+    pub fn ready_dests(&self) -> BitVec {
+        let resident = self
+            .try_cache(|cache| cache.shards.iter().map(|(s, _)| *s).collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        let mut ready = BitVec::ZERO;
+        for index in resident {
+            let start = index * self.dests_per_shard;
+            let end = (start + self.dests_per_shard).min(self.nodes_len());
+            for dest in start..end {
+                ready.set_bit(dest, true);
+            }
+        }
+
+        ready
+    }
+
+    /// Read the shard of `dest` from its file if it is not resident,
+    /// so that the next queries to `dest` don't have to.
+    ///
+    /// This is meant to be called on another thread while queries fall back to
+    /// [neighbor_to_or_greedy](Self::neighbor_to_or_greedy).
+    pub fn prefetch(&self, dest: NodeId) -> io::Result<()> {
+        check_nodes!("prefetch", self.nodes_len(), dest);
+        self.load(dest).map(|_| ())
+    }
+
+    /// Given a current node and a destination node,
+    /// return all neighboring nodes of current that are shortest paths to the destination node.
+    ///
+    /// Panics if the shard of `dest` has to be loaded and its file can't be read.
+    pub fn neighbors_to(&self, curr: NodeId, dest: NodeId) -> std::vec::IntoIter<NodeId> {
+        check_nodes!("neighbors_to", self.nodes_len(), curr, dest);
+
+        if !self.has_path(curr, dest) {
+            return Vec::new().into_iter();
+        }
+
+        let (shard, row) = self.load_or_panic(dest);
+        self.towards(curr, &shard[row])
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// Given a current node and a destination node,
+    /// return a path from the current node to the destination node.
+    ///
+    /// The path is a list of node IDs, starting with current node and ending at the destination node.
+    ///
+    /// If there is no path, the list will be empty.
+    ///
+    /// Panics if the shard of `dest` has to be loaded and its file can't be read.
+    pub fn path_to(&self, curr: NodeId, dest: NodeId) -> std::vec::IntoIter<NodeId> {
+        check_nodes!("path_to", self.nodes_len(), curr, dest);
+
+        if curr == dest {
+            return vec![curr].into_iter();
+        }
+        if !self.has_path(curr, dest) {
+            return Vec::new().into_iter();
+        }
+
+        // every node of the path is in the same row, so it is loaded once
+        let (shard, row) = self.load_or_panic(dest);
+        let row = &shard[row];
+
+        let mut path = vec![curr];
+        let mut node = curr;
+        while node != dest && path.len() <= self.nodes_len() {
+            match self.towards(node, row).next() {
+                Some(next) => node = next,
+                None => return Vec::new().into_iter(),
+            }
+            path.push(node);
+        }
+
+        path.into_iter()
+    }
+
+    /// Return the neighbors of the given node.
+    #[inline]
+    pub fn neighbors(&self, node: NodeId) -> &[NodeId] {
+        &self.neighbors[node.as_usize()]
+    }
+
+    /// Return the number of nodes in this graph.
+    #[inline]
+    pub fn nodes_len(&self) -> usize {
+        self.neighbors.len()
+    }
+
+    /// Return the number of edges in this graph.
+    #[inline]
+    pub fn edges_len(&self) -> usize {
+        self.edges_len
+    }
+
+    /// Return the number of shards.
+    #[inline]
+    pub fn shards_len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Return the number of times a shard was read from its file so far.
+    ///
+    /// If this grows with almost every query, consider keeping more shards resident
+    /// with [with_resident_shards](Self::with_resident_shards).
+    pub fn loads(&self) -> usize {
+        self.cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .loads
+    }
+
+    #[inline]
+    fn has_path(&self, curr: NodeId, dest: NodeId) -> bool {
+        curr != dest && self.components[curr.as_usize()] == self.components[dest.as_usize()]
+    }
+
+    /// Return the neighbors of `curr` that lead toward the destination of the given row.
+    fn towards<'a>(&'a self, curr: NodeId, row: &'a [u64]) -> impl Iterator<Item = NodeId> + 'a {
+        let edge_ids = &self.edge_ids[curr.as_usize()];
+
+        self.neighbors(curr)
+            .iter()
+            .zip(edge_ids)
+            .filter(move |(&neighbor, &edge)| {
+                let bit = row[edge as usize / 64] & (1 << (edge % 64)) != 0;
+
+                // the bits are from the lower node's perspective
+                if curr < neighbor {
+                    bit
+                } else {
+                    !bit
+                }
+            })
+            .map(|(&neighbor, _)| neighbor)
+    }
+
+    fn load_or_panic(&self, dest: NodeId) -> (Arc<Vec<u64>>, Range<usize>) {
+        self.load(dest).unwrap_or_else(|err| {
+            panic!(
+                "failed to load the shard of node {}: {err}",
+                dest.as_usize()
+            )
+        })
+    }
+
+    /// Return the index of the shard of `dest`, and the range of `dest`'s row in it.
+    #[inline]
+    fn locate(&self, dest: NodeId) -> (usize, Range<usize>) {
+        let index = dest.as_usize() / self.dests_per_shard;
+        let words = self.row_words();
+        let start = dest.as_usize() % self.dests_per_shard * words;
+        (index, start..start + words)
+    }
+
+    /// Same as [load](Self::load), but returns `None` instead of reading the shard from its file,
+    /// or waiting for another thread that is reading it.
+    fn resident(&self, dest: NodeId) -> Option<(Arc<Vec<u64>>, Range<usize>)> {
+        let (index, row) = self.locate(dest);
+        let shard = self.try_cache(|cache| cache.touch(index))??;
+        Some((shard, row))
+    }
+
+    /// Run `f` on the cache, or return `None` if another thread is holding it.
+    fn try_cache<T>(&self, f: impl FnOnce(&mut ShardCache) -> T) -> Option<T> {
+        let mut cache = match self.cache.try_lock() {
+            Ok(cache) => cache,
+            Err(TryLockError::Poisoned(err)) => err.into_inner(),
+            Err(TryLockError::WouldBlock) => return None,
+        };
+
+        Some(f(&mut cache))
+    }
+
+    /// Return the words of the shard of `dest`, and the range of `dest`'s row in them;
+    /// the shard is read from its file if it is not resident.
+    ///
+    /// The cache is not locked while the file is read, so queries to resident shards
+    /// don't wait for it; queries to the same shard wait for the read instead of repeating it.
+    fn load(&self, dest: NodeId) -> io::Result<(Arc<Vec<u64>>, Range<usize>)> {
+        let (index, row) = self.locate(dest);
+
+        let mut cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+        loop {
+            if let Some(shard) = cache.touch(index) {
+                return Ok((shard, row));
+            }
+            if !cache.loading.contains(&index) {
+                break;
+            }
+            cache = self
+                .loaded
+                .wait(cache)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        cache.loading.push(index);
+        drop(cache);
+
+        let read = self.read_shard(index);
+
+        let mut cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+        cache.loading.retain(|&s| s != index);
+        if let Ok(shard) = &read {
+            cache.loads += 1;
+            cache.shards.push((index, shard.clone()));
+            while cache.shards.len() > cache.resident {
+                cache.shards.remove(0);
+            }
+        }
+        drop(cache);
+        self.loaded.notify_all();
+
+        // if the read failed, threads waiting for it try to read the shard themselves
+        Ok((read?, row))
+    }
+
+    /// Read the shard with the given index from its file.
+    fn read_shard(&self, index: usize) -> io::Result<Arc<Vec<u64>>> {
+        let words = self.row_words();
+        let bytes = fs::read(&self.files[index])?;
+        let rows_len = self
+            .dests_per_shard
+            .min(self.nodes_len() - index * self.dests_per_shard);
+
+        if bytes.len() != rows_len * words * 8 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "shard file {} has {} bytes, expected {}",
+                    self.files[index].display(),
+                    bytes.len(),
+                    rows_len * words * 8
+                ),
+            ));
+        }
+
+        let shard = bytes
+            .chunks_exact(8)
+            .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+            .collect::<Vec<_>>();
+
+        Ok(Arc::new(shard))
+    }
+
+    /// Return the number of words in a row of a shard; a row has a bit for every edge.
+    #[inline]
+    fn row_words(&self) -> usize {
+        (self.edges_len + 63) / 64
+    }
+
+    /// Compute the rows of the given destinations.
+    ///
+    /// The bit of an edge is set if, from its lower node, the edge is a shortest path to the destination.
+    fn compute_shard(&self, dests: Range<usize>) -> Vec<u64> {
+        let words = self.row_words();
+        let mut rows = vec![0u64; dests.len() * words];
+
+        let compute_row = |(i, row): (usize, &mut [u64])| {
+            let dist = bfs_distances(&self.neighbors, dests.start + i);
+
+            for (a, (a_neighbors, a_edges)) in self.neighbors.iter().zip(&self.edge_ids).enumerate()
+            {
+                for (b, &edge) in a_neighbors.iter().zip(a_edges) {
+                    if a < b.as_usize() && dist[b.as_usize()] < dist[a] {
+                        row[edge as usize / 64] |= 1 << (edge % 64);
+                    }
+                }
+            }
+        };
+
+        // an empty graph has rows of no words
+        if words > 0 {
+            #[cfg(feature = "parallel")]
+            {
+                use rayon::prelude::*;
+                rows.par_chunks_mut(words).enumerate().for_each(compute_row);
+            }
+
+            #[cfg(not(feature = "parallel"))]
+            rows.chunks_mut(words).enumerate().for_each(compute_row);
+        }
+
+        rows
+    }
+}
+
+impl<NodeId: U16orU32> Drop for ShardedGraph<NodeId> {
+    fn drop(&mut self) {
+        for file in &self.files {
+            let _ = fs::remove_file(file);
+        }
+
+        if let Some(dir) = &self.temp_dir {
+            let _ = fs::remove_dir(dir);
+        }
+    }
+}
+
+impl<NodeId: U16orU32> fmt::Debug for ShardedGraph<NodeId> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+
+        f.debug_struct("ShardedGraph")
+            .field("nodes_len", &self.nodes_len())
+            .field("edges_len", &self.edges_len)
+            .field("dests_per_shard", &self.dests_per_shard)
+            .field("files", &self.files)
+            .field(
+                "resident_shards",
+                &cache.shards.iter().map(|(s, _)| *s).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+/// Create a new directory for the shard files of a graph in the system's temporary directory.
+pub(crate) fn temp_dir() -> io::Result<PathBuf> {
+    static NEXT_DIR: AtomicUsize = AtomicUsize::new(0);
+
+    let dir = std::env::temp_dir().join(format!(
+        "bit_gossip-shards-{}-{}",
+        std::process::id(),
+        NEXT_DIR.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// Number the edges in the order of their lower node;
+/// return the number of each neighbor's edge, and the number of edges.
+fn number_edges<NodeId: U16orU32>(neighbors: &[Vec<NodeId>]) -> (Vec<Vec<u32>>, usize) {
+    let mut edge_ids = neighbors
+        .iter()
+        .map(|n| vec![0; n.len()])
+        .collect::<Vec<_>>();
+    let mut edges_len = 0;
+
+    for (a, a_neighbors) in neighbors.iter().enumerate() {
+        for (i, b) in a_neighbors.iter().enumerate() {
+            if a < b.as_usize() {
+                edge_ids[a][i] = edges_len as u32;
+                edges_len += 1;
+            }
+        }
+    }
+
+    for (a, a_neighbors) in neighbors.iter().enumerate() {
+        for (i, b) in a_neighbors.iter().enumerate() {
+            let b = b.as_usize();
+            if a > b {
+                let j = neighbors[b].iter().position(|n| n.as_usize() == a).unwrap();
+                edge_ids[a][i] = edge_ids[b][j];
+            }
+        }
+    }
+
+    (edge_ids, edges_len)
+}
+
+/// Return the connected component of each node, numbered by their lowest node.
+fn components<NodeId: U16orU32>(neighbors: &[Vec<NodeId>]) -> Vec<u32> {
+    let mut components = vec![u32::MAX; neighbors.len()];
+    let mut stack = Vec::new();
+
+    for start in 0..neighbors.len() {
+        if components[start] != u32::MAX {
+            continue;
+        }
+
+        components[start] = start as u32;
+        stack.push(start);
+
+        while let Some(node) = stack.pop() {
+            for neighbor in &neighbors[node] {
+                let neighbor = neighbor.as_usize();
+                if components[neighbor] == u32::MAX {
+                    components[neighbor] = start as u32;
+                    stack.push(neighbor);
+                }
+            }
+        }
+    }
+
+    components
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{maze::build_maze_from_seed, Graph};
+
+    #[test]
+    fn test_sharded_graph() {
+        // a maze with a few walls knocked down, so there are loops; it has no odd cycles,
+        // where the bits of equally far nodes depend on how the graph was built
+        let (width, height) = (16u16, 12u16);
+        let nodes_len = (width * height) as usize;
+        let mut edges = build_maze_from_seed(width, height, [3; 32]);
+        for y in (0..height - 1).step_by(3) {
+            edges.push((y * width + 5, (y + 1) * width + 5));
+        }
+
+        let builder = || {
+            let mut builder = Graph::<u16>::builder(nodes_len);
+            for &(a, b) in &edges {
+                builder.connect(a, b);
+            }
+            builder
+        };
+        let graph = builder().build();
+
+        for shards in [1, 5, 64, nodes_len + 10] {
+            let sharded = builder().build_sharded(shards).unwrap();
+            assert_eq!(sharded.shards_len(), shards.min(nodes_len));
+            assert_eq!(sharded.edges_len(), graph.edges_len());
+
+            for dest in 0..nodes_len as u16 {
+                for curr in 0..nodes_len as u16 {
+                    let mut expected = graph.neighbors_to(curr, dest).collect::<Vec<_>>();
+                    expected.sort();
+                    let mut next = sharded.neighbors_to(curr, dest).collect::<Vec<_>>();
+                    assert_eq!(sharded.neighbor_to(curr, dest), next.first().copied());
+
+                    next.sort();
+                    assert_eq!(next, expected, "{shards} shards: from {curr} to {dest}");
+                }
+            }
+
+            let path = sharded.path_to(0, nodes_len as u16 - 1).collect::<Vec<_>>();
+            assert_eq!(path.len(), graph.path_to(0, nodes_len as u16 - 1).count());
+            assert_eq!(sharded.path_to(7, 7).collect::<Vec<_>>(), vec![7]);
+        }
+    }
+
+    #[test]
+    fn test_sharded_cache_and_files() {
+        // two corridors 0 - 1 - 2 and 3 - 4 - 5
+        let mut builder = Graph::<u16>::builder(6);
+        builder.connect(0, 1);
+        builder.connect(1, 2);
+        builder.connect(3, 4);
+        builder.connect(4, 5);
+
+        let dir = std::env::temp_dir().join(format!("bit_gossip-test-{}", std::process::id()));
+        let graph = builder
+            .build_sharded_in(&dir, 3)
+            .unwrap()
+            .with_resident_shards(1);
+        let files = graph.files.clone();
+        assert_eq!(files.len(), 3);
+
+        // no path between the corridors; answered without loading a shard
+        assert_eq!(graph.neighbor_to(0, 5), None);
+        assert!(graph.path_to(0, 5).next().is_none());
+        assert_eq!(graph.loads(), 0);
+
+        assert_eq!(graph.neighbor_to(0, 2), Some(1));
+        assert_eq!(graph.neighbor_to(2, 0), Some(1));
+        assert_eq!(graph.loads(), 2);
+        assert_eq!(graph.neighbor_to(1, 0), Some(0));
+        assert_eq!(graph.loads(), 2);
+
+        // a damaged file is reported, not read as paths
+        fs::write(&files[2], [0u8; 3]).unwrap();
+        let err = graph.try_neighbor_to(3, 5).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // a failed read isn't left in flight, so the next query reads the file again
+        let err = graph.try_neighbor_to(3, 5).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(graph.loads(), 2);
+
+        drop(graph);
+        assert!(files.iter().all(|file| !file.exists()));
+        assert!(dir.exists());
+        fs::remove_dir(&dir).unwrap();
+
+        // an empty graph has no shards
+        let graph = Graph::<u16>::builder(0).build_sharded(4).unwrap();
+        assert_eq!(graph.shards_len(), 0);
+        let dir = graph.temp_dir.clone().unwrap();
+        drop(graph);
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn test_sharded_concurrent_loads() {
+        // a corridor of 64 nodes, in 4 shards of 16 destinations
+        let mut builder = Graph::<u16>::builder(64);
+        for i in 0..63 {
+            builder.connect(i, i + 1);
+        }
+        let graph = builder.build_sharded(4).unwrap().with_resident_shards(1);
+
+        // threads querying the same shard at once wait for one read of its file
+        std::thread::scope(|s| {
+            for curr in 0..8 {
+                let graph = &graph;
+                s.spawn(move || assert_eq!(graph.neighbor_to(curr, 63), Some(curr + 1)));
+            }
+        });
+        assert_eq!(graph.loads(), 1);
+        assert!(graph.dest_ready(63));
+    }
+
+    #[test]
+    fn test_sharded_greedy_fallback() {
+        // a corridor of 10 nodes, in 5 shards of 2 destinations
+        let mut builder = Graph::<u16>::builder(10);
+        for i in 0..9 {
+            builder.connect(i, i + 1);
+        }
+        let graph = builder.build_sharded(5).unwrap().with_resident_shards(1);
+
+        // the shard of 0 isn't resident, so the search answers without reading it
+        assert!(!graph.dest_ready(0));
+        assert!(graph.ready_dests().is_zero());
+        assert_eq!(graph.neighbor_to_or_greedy(5, 0, 5), Some(4));
+        assert_eq!(graph.loads(), 0);
+
+        // out of budget, it still moves, toward the last node reached: 9, through 6
+        assert_eq!(graph.neighbor_to_or_greedy(5, 0, 4), Some(6));
+        assert_eq!(graph.loads(), 0);
+
+        // once it is, the precomputed paths answer within any budget
+        graph.prefetch(0).unwrap();
+        assert_eq!(graph.loads(), 1);
+        assert!(graph.dest_ready(1));
+        assert_eq!(
+            graph.ready_dests().iter_ones().collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+        assert_eq!(graph.neighbor_to_or_greedy(5, 0, 0), Some(4));
+        assert_eq!(graph.neighbor_to_or_greedy(9, 1, 0), Some(8));
+        assert_eq!(graph.loads(), 1);
+
+        // the fallback doesn't evict the resident shard
+        assert_eq!(graph.neighbor_to_or_greedy(0, 9, 9), Some(1));
+        assert_eq!(graph.neighbor_to(5, 0), Some(4));
+        assert_eq!(graph.loads(), 1);
+    }
+}
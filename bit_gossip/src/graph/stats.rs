@@ -0,0 +1,144 @@
+//! statistics of a graph build, returned by [GraphBuilder::build_with_stats](super::GraphBuilder::build_with_stats).
+//!
+//! With the `track-alloc` feature, internal allocations are counted as well:
+//! every time a bit vector grows its buffer, and every time an edge map grows its table.
+//! This lets you measure how much pre-sizing helps on your own graphs, instead of guessing.
+//!
+//! The counters are global, so allocations of other builds running at the same time are counted too.
+//! To count allocations outside of a build, like while connecting edges,
+//! take the difference of two `allocations` snapshots.
+
+use std::time::Duration;
+
+/// Statistics of a single graph build.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BuildStats {
+    /// Wall-clock time the build took.
+    pub duration: Duration,
+    /// Allocations made during the build.
+    #[cfg(feature = "track-alloc")]
+    pub allocations: Allocations,
+}
+
+/// Counts of internal allocations.
+#[cfg(feature = "track-alloc")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Allocations {
+    /// Number of times a bit vector allocated or grew its buffer while setting or merging bits.
+    pub bitvec_grows: usize,
+    /// Number of times an edge map allocated or grew its table.
+    pub map_grows: usize,
+}
+
+#[cfg(feature = "track-alloc")]
+impl Allocations {
+    /// Return the total number of allocations.
+    #[inline]
+    pub fn total(&self) -> usize {
+        self.bitvec_grows + self.map_grows
+    }
+}
+
+#[cfg(feature = "track-alloc")]
+impl std::ops::Sub for Allocations {
+    type Output = Allocations;
+
+    #[inline]
+    fn sub(self, rhs: Self) -> Self::Output {
+        Allocations {
+            bitvec_grows: self.bitvec_grows - rhs.bitvec_grows,
+            map_grows: self.map_grows - rhs.map_grows,
+        }
+    }
+}
+
+#[cfg(feature = "track-alloc")]
+mod counters {
+    use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+    pub(super) static BITVEC_GROWS: AtomicUsize = AtomicUsize::new(0);
+    pub(super) static MAP_GROWS: AtomicUsize = AtomicUsize::new(0);
+
+    #[inline]
+    pub(super) fn add(counter: &AtomicUsize) {
+        counter.fetch_add(1, Relaxed);
+    }
+
+    #[inline]
+    pub(super) fn load(counter: &AtomicUsize) -> usize {
+        counter.load(Relaxed)
+    }
+}
+
+/// Return a snapshot of the allocations counted since the program started.
+#[cfg(feature = "track-alloc")]
+#[inline]
+pub fn allocations() -> Allocations {
+    Allocations {
+        bitvec_grows: counters::load(&counters::BITVEC_GROWS),
+        map_grows: counters::load(&counters::MAP_GROWS),
+    }
+}
+
+/// Record a bit vector buffer growing from `capacity_before` to `capacity_after`.
+#[inline(always)]
+pub(crate) fn record_bitvec_grow(capacity_before: usize, capacity_after: usize) {
+    #[cfg(feature = "track-alloc")]
+    if capacity_after > capacity_before {
+        counters::add(&counters::BITVEC_GROWS);
+    }
+    #[cfg(not(feature = "track-alloc"))]
+    let _ = (capacity_before, capacity_after);
+}
+
+/// Record an edge map table growing from `capacity_before` to `capacity_after`.
+#[inline(always)]
+pub(crate) fn record_map_grow(capacity_before: usize, capacity_after: usize) {
+    #[cfg(feature = "track-alloc")]
+    if capacity_after > capacity_before {
+        counters::add(&counters::MAP_GROWS);
+    }
+    #[cfg(not(feature = "track-alloc"))]
+    let _ = (capacity_before, capacity_after);
+}
+
+/// Run the build `f`, and return its result with its statistics.
+pub(crate) fn measure<T>(f: impl FnOnce() -> T) -> (T, BuildStats) {
+    #[cfg(feature = "track-alloc")]
+    let before = allocations();
+    let start = std::time::Instant::now();
+
+    let res = f();
+
+    let stats = BuildStats {
+        duration: start.elapsed(),
+        #[cfg(feature = "track-alloc")]
+        allocations: allocations() - before,
+    };
+
+    (res, stats)
+}
+
+#[cfg(all(test, feature = "track-alloc"))]
+mod tests {
+    use super::*;
+    use crate::Graph;
+
+    #[test]
+    fn test_allocations() {
+        let before = allocations();
+
+        let mut builder = Graph::<u16>::builder(64);
+        for i in 0..63 {
+            builder.connect(i, i + 1);
+        }
+
+        // connecting edges grows the edge map
+        assert!((allocations() - before).map_grows > 0);
+
+        let (graph, stats) = builder.build_with_stats();
+        assert!(stats.allocations.bitvec_grows > 0);
+        assert!(stats.allocations.total() >= stats.allocations.bitvec_grows);
+        assert_eq!(graph.neighbor_to(0, 63), Some(1));
+    }
+}
@@ -0,0 +1,720 @@
+//! storage backends for the edge bit vectors of [SeqGraph](super::sequential::SeqGraph).
+//!
+//! The gossip algorithm only needs to read, merge and insert the bit vector of an edge,
+//! so the storage is abstracted behind the [EdgeStore] trait.
+//!
+//! - [HashMapStore]: the default; a `HashMap` keyed by edge id.
+//! - [CsrStore]: a dense compressed-sparse-row layout; edges are stored contiguously per node.
+//! - [CompressedStore]: only stores the non-zero words of each bit vector.
+//!
+//! The store is selected with the type parameter of the builder;
+//! [GraphBuilder::with_store](super::GraphBuilder::with_store) selects it for a [Graph](super::Graph)'s
+//! sequential backend, while the parallel backend always keeps its atomic bit vectors:
+//!
+//! ```
+//! use bit_gossip::graph::{
+//!     sequential::{SeqGraph, SeqGraphBuilder},
+//!     store::CsrStore,
+//! };
+//!
+//! let mut builder: SeqGraphBuilder<u16, CsrStore<u16>> = SeqGraphBuilder::with_store(4);
+//! builder.connect(0, 1);
+//! builder.connect(1, 2);
+//! builder.connect(2, 3);
+//!
+//! let graph: SeqGraph<u16, CsrStore<u16>> = builder.build();
+//! assert_eq!(graph.path_to(0, 3).collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+//! ```
+
+use super::{stats::record_map_grow, U16orU32};
+use crate::bitvec::{BitVec, Digit, BITS};
+use std::{
+    borrow::Cow,
+    collections::{hash_map::Entry, HashMap},
+    fmt::Debug,
+    sync::Arc,
+};
+
+/// Storage of the bit vectors of each edge, keyed by edge id.
+///
+/// Edge ids are always given in ascending order, as returned by [edge_id](crate::edge_id).
+pub trait EdgeStore<NodeId: U16orU32>: Debug + Clone + Default {
+    /// Return the bit vector of the given edge.
+    fn get(&self, edge_id: (NodeId, NodeId)) -> Option<Cow<'_, BitVec>>;
+
+    /// Return a single bit of the given edge.
+    ///
+    /// This should not need to materialize the whole bit vector.
+    fn get_bit(&self, edge_id: (NodeId, NodeId), bit_index: usize) -> Option<bool>;
+
+    /// Insert a new edge with the given bits.
+    ///
+    /// If the edge already exists, the bits will be merged.
+    fn insert(&mut self, edge_id: (NodeId, NodeId), val: BitVec);
+
+    /// Remove the edge and return its bits.
+    fn remove(&mut self, edge_id: (NodeId, NodeId)) -> Option<BitVec>;
+
+    /// Check if the edge exists.
+    fn contains(&self, edge_id: (NodeId, NodeId)) -> bool;
+
+    /// Return the number of edges.
+    fn len(&self) -> usize;
+
+    /// Check if there are no edges.
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterate over all edges and their bits.
+    ///
+    /// The order of the edges is not guaranteed.
+    fn iter(&self) -> Box<dyn Iterator<Item = ((NodeId, NodeId), Cow<'_, BitVec>)> + '_>;
+
+    /// Truncate the edges to the given length of nodes.
+    ///
+    /// Edges connected to removed nodes are removed, and bits beyond `nodes_len` are cleared.
+    fn truncate(&mut self, nodes_len: usize);
+
+    /// Finish any deferred work of earlier inserts, to make later lookups fast.
+    ///
+    /// Builds call this before gossiping. Stores that don't defer work do nothing.
+    #[inline]
+    fn compact(&mut self) {}
+
+    /// Keep only the bit vectors that are shared with other stores, like the ones of a fork's parent.
+    ///
+    /// Stores that never share their bits return an empty store.
+    #[inline]
+    fn into_shared(self) -> Self {
+        Self::default()
+    }
+
+    /// Replace the bit vectors that are equal to the ones in `shared` with the shared ones,
+    /// so they are not kept in memory twice.
+    ///
+    /// Stores that never share their bits do nothing.
+    #[inline]
+    fn share_equal(&mut self, shared: &Self) {
+        let _ = shared;
+    }
+}
+
+/// Edge store backed by a `HashMap`.
+///
+/// This is the default store.
+///
+/// Each edge's bits are kept behind an [Arc] and are only written when they actually change,
+/// so cloning the store shares every bit vector, and a clone copies only the edges it changes.
+/// A rebuilt graph shares the bits that came out the same with the graph it was converted from.
+/// This is what lets a [fork](crate::Graph::fork) of a graph be rebuilt with memory
+/// proportional to the differences.
+#[derive(Debug, Clone)]
+pub struct HashMapStore<NodeId: U16orU32> {
+    /// key: edge_id
+    ///
+    /// value: for each bit, if this edge is the shortest path
+    /// to that bit location's node, bit is set to 1
+    inner: HashMap<(NodeId, NodeId), Arc<BitVec>>,
+}
+
+impl<NodeId: U16orU32> Default for HashMapStore<NodeId> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            inner: HashMap::new(),
+        }
+    }
+}
+
+/// Merge `val` into `bits`, copying shared bits only if `val` has a bit that is not set yet.
+#[inline]
+fn merge_shared(bits: &mut Arc<BitVec>, val: &BitVec) {
+    let covered = val
+        .0
+        .iter()
+        .enumerate()
+        .all(|(i, &d)| d == 0 || bits.0.get(i).map_or(false, |&b| b & d == d));
+
+    if !covered {
+        Arc::make_mut(bits).bitor_assign(val);
+    }
+}
+
+impl<NodeId: U16orU32> EdgeStore<NodeId> for HashMapStore<NodeId> {
+    #[inline]
+    fn get(&self, edge_id: (NodeId, NodeId)) -> Option<Cow<'_, BitVec>> {
+        self.inner.get(&edge_id).map(|bits| Cow::Borrowed(&**bits))
+    }
+
+    #[inline]
+    fn get_bit(&self, edge_id: (NodeId, NodeId), bit_index: usize) -> Option<bool> {
+        self.inner.get(&edge_id).map(|bits| bits.get_bit(bit_index))
+    }
+
+    #[inline]
+    fn insert(&mut self, edge_id: (NodeId, NodeId), val: BitVec) {
+        let capacity = self.inner.capacity();
+
+        match self.inner.entry(edge_id) {
+            Entry::Occupied(mut entry) => merge_shared(entry.get_mut(), &val),
+            Entry::Vacant(entry) => {
+                entry.insert(Arc::new(val));
+            }
+        }
+
+        record_map_grow(capacity, self.inner.capacity());
+    }
+
+    #[inline]
+    fn remove(&mut self, edge_id: (NodeId, NodeId)) -> Option<BitVec> {
+        self.inner
+            .remove(&edge_id)
+            .map(|bits| Arc::try_unwrap(bits).unwrap_or_else(|bits| (*bits).clone()))
+    }
+
+    #[inline]
+    fn contains(&self, edge_id: (NodeId, NodeId)) -> bool {
+        self.inner.contains_key(&edge_id)
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = ((NodeId, NodeId), Cow<'_, BitVec>)> + '_> {
+        Box::new(self.inner.iter().map(|(k, v)| (*k, Cow::Borrowed(&**v))))
+    }
+
+    fn truncate(&mut self, nodes_len: usize) {
+        self.inner
+            .retain(|(a, b), _| a.as_usize() < nodes_len && b.as_usize() < nodes_len);
+
+        for edge in self.inner.values_mut() {
+            // only copy the edges that have bits beyond `nodes_len`
+            if edge.0.len() * BITS > nodes_len {
+                Arc::make_mut(edge).truncate(nodes_len);
+            }
+        }
+    }
+
+    fn into_shared(mut self) -> Self {
+        self.inner.retain(|_, bits| Arc::strong_count(bits) > 1);
+        self
+    }
+
+    fn share_equal(&mut self, shared: &Self) {
+        for (k, bits) in self.inner.iter_mut() {
+            if let Some(shared) = shared.inner.get(k) {
+                if !Arc::ptr_eq(bits, shared) && trimmed(&bits.0) == trimmed(&shared.0) {
+                    *bits = shared.clone();
+                }
+            }
+        }
+    }
+}
+
+/// Return the digits without the trailing zero digits.
+#[inline]
+fn trimmed(digits: &[Digit]) -> &[Digit] {
+    let len = digits.iter().rposition(|&d| d != 0).map_or(0, |i| i + 1);
+    &digits[..len]
+}
+
+/// Edge store with a dense compressed-sparse-row layout.
+///
+/// Edges `(a, b)` are grouped by their lower node `a` and sorted by `b`,
+/// so all edges of a node are laid out contiguously and found with a binary search.
+///
+/// Lookups avoid hashing and the layout has no per-entry overhead.
+/// New edges are kept in a small pending map, and removed edges are only marked as removed,
+/// until the store is [compacted](EdgeStore::compact), which rebuilds the layout with one sort;
+/// builds compact their stores before gossiping, so building through this store is `O(E log E)`.
+#[derive(Debug, Clone)]
+pub struct CsrStore<NodeId: U16orU32> {
+    /// edges of node `a` are in `offsets[a]..offsets[a + 1]`
+    offsets: Vec<usize>,
+    /// higher node of each edge
+    targets: Vec<NodeId>,
+    /// bits of each edge
+    bits: Vec<BitVec>,
+    /// edges of the layout that were removed since the last compaction, by position
+    removed: BitVec,
+    /// number of edges in `removed`
+    removed_len: usize,
+    /// edges inserted since the last compaction
+    pending: HashMap<(NodeId, NodeId), BitVec>,
+}
+
+impl<NodeId: U16orU32> Default for CsrStore<NodeId> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            offsets: vec![0],
+            targets: Vec::new(),
+            bits: Vec::new(),
+            removed: BitVec::ZERO,
+            removed_len: 0,
+            pending: HashMap::new(),
+        }
+    }
+}
+
+impl<NodeId: U16orU32> CsrStore<NodeId> {
+    /// Return the position of the edge in the layout, removed or not.
+    #[inline]
+    fn position(&self, (a, b): (NodeId, NodeId)) -> Option<usize> {
+        let a = a.as_usize();
+
+        if a + 1 >= self.offsets.len() {
+            return None;
+        }
+
+        let (start, end) = (self.offsets[a], self.offsets[a + 1]);
+
+        self.targets[start..end]
+            .binary_search(&b)
+            .ok()
+            .map(|i| start + i)
+    }
+
+    /// Return the position of the edge in the layout, if it was not removed.
+    #[inline]
+    fn live(&self, edge_id: (NodeId, NodeId)) -> Option<usize> {
+        self.position(edge_id).filter(|&i| !self.removed.get_bit(i))
+    }
+}
+
+impl<NodeId: U16orU32> EdgeStore<NodeId> for CsrStore<NodeId> {
+    #[inline]
+    fn get(&self, edge_id: (NodeId, NodeId)) -> Option<Cow<'_, BitVec>> {
+        match self.live(edge_id) {
+            Some(i) => Some(Cow::Borrowed(&self.bits[i])),
+            None => self.pending.get(&edge_id).map(Cow::Borrowed),
+        }
+    }
+
+    #[inline]
+    fn get_bit(&self, edge_id: (NodeId, NodeId), bit_index: usize) -> Option<bool> {
+        match self.live(edge_id) {
+            Some(i) => Some(self.bits[i].get_bit(bit_index)),
+            None => self
+                .pending
+                .get(&edge_id)
+                .map(|bits| bits.get_bit(bit_index)),
+        }
+    }
+
+    fn insert(&mut self, edge_id: (NodeId, NodeId), val: BitVec) {
+        if let Some(i) = self.position(edge_id) {
+            // a removed edge takes its place in the layout again
+            if self.removed.get_bit(i) {
+                self.removed.set_bit(i, false);
+                self.removed_len -= 1;
+                self.bits[i] = val;
+            } else {
+                self.bits[i].bitor_assign(&val);
+            }
+            return;
+        }
+
+        let capacity = self.pending.capacity();
+
+        match self.pending.entry(edge_id) {
+            Entry::Occupied(mut entry) => entry.get_mut().bitor_assign(&val),
+            Entry::Vacant(entry) => {
+                entry.insert(val);
+            }
+        }
+
+        record_map_grow(capacity, self.pending.capacity());
+    }
+
+    fn remove(&mut self, edge_id: (NodeId, NodeId)) -> Option<BitVec> {
+        if let Some(bits) = self.pending.remove(&edge_id) {
+            return Some(bits);
+        }
+
+        let i = self.live(edge_id)?;
+        self.removed.set_bit(i, true);
+        self.removed_len += 1;
+
+        Some(std::mem::replace(&mut self.bits[i], BitVec::ZERO))
+    }
+
+    #[inline]
+    fn contains(&self, edge_id: (NodeId, NodeId)) -> bool {
+        self.live(edge_id).is_some() || self.pending.contains_key(&edge_id)
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.targets.len() - self.removed_len + self.pending.len()
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = ((NodeId, NodeId), Cow<'_, BitVec>)> + '_> {
+        let laid_out = self.offsets.windows(2).enumerate().flat_map(move |(a, w)| {
+            (w[0]..w[1])
+                .filter(move |&i| !self.removed.get_bit(i))
+                .map(move |i| {
+                    (
+                        (NodeId::from_usize(a), self.targets[i]),
+                        Cow::Borrowed(&self.bits[i]),
+                    )
+                })
+        });
+        let pending = self.pending.iter().map(|(k, v)| (*k, Cow::Borrowed(v)));
+
+        Box::new(laid_out.chain(pending))
+    }
+
+    fn truncate(&mut self, nodes_len: usize) {
+        let mut res = Self::default();
+
+        for ((a, b), bits) in self.iter() {
+            if a.as_usize() < nodes_len && b.as_usize() < nodes_len {
+                let mut bits = bits.into_owned();
+                bits.truncate(nodes_len);
+                res.insert((a, b), bits);
+            }
+        }
+
+        res.compact();
+        *self = res;
+    }
+
+    fn compact(&mut self) {
+        if self.pending.is_empty() && self.removed_len == 0 {
+            return;
+        }
+
+        let offsets = std::mem::take(&mut self.offsets);
+        let targets = std::mem::take(&mut self.targets);
+        let bits = std::mem::take(&mut self.bits);
+        let removed = std::mem::replace(&mut self.removed, BitVec::ZERO);
+        self.removed_len = 0;
+
+        // pending edges are never in the layout, so every key is unique
+        let mut edges = offsets
+            .windows(2)
+            .enumerate()
+            .flat_map(|(a, w)| (w[0]..w[1]).map(move |_| NodeId::from_usize(a)))
+            .zip(targets.into_iter().zip(bits))
+            .enumerate()
+            .filter(|(i, _)| !removed.get_bit(*i))
+            .map(|(_, (a, (b, bits)))| ((a, b), bits))
+            .chain(self.pending.drain())
+            .collect::<Vec<_>>();
+        edges.sort_unstable_by_key(|(k, _)| *k);
+
+        let rows = edges.last().map_or(0, |((a, _), _)| a.as_usize() + 1);
+        self.offsets = vec![0; rows + 1];
+        self.targets = Vec::with_capacity(edges.len());
+        self.bits = Vec::with_capacity(edges.len());
+
+        for ((a, b), bits) in edges {
+            self.offsets[a.as_usize() + 1] += 1;
+            self.targets.push(b);
+            self.bits.push(bits);
+        }
+
+        for a in 0..rows {
+            self.offsets[a + 1] += self.offsets[a];
+        }
+    }
+}
+
+/// Edge store that only keeps the non-zero words of each bit vector.
+///
+/// Each edge is stored as a sorted list of `(word index, word)` pairs.
+/// This saves memory when the set bits are clustered,
+/// like in graphs with many disconnected regions,
+/// at the cost of decompressing the bit vector on every [get](EdgeStore::get).
+///
+/// Single bit lookups with [get_bit](EdgeStore::get_bit) do not decompress.
+#[derive(Debug, Clone)]
+pub struct CompressedStore<NodeId: U16orU32> {
+    inner: HashMap<(NodeId, NodeId), Vec<(u32, Digit)>>,
+}
+
+impl<NodeId: U16orU32> Default for CompressedStore<NodeId> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            inner: HashMap::new(),
+        }
+    }
+}
+
+impl<NodeId: U16orU32> CompressedStore<NodeId> {
+    fn compress(bits: &BitVec) -> Vec<(u32, Digit)> {
+        bits.0
+            .iter()
+            .enumerate()
+            .filter(|(_, d)| **d != 0)
+            .map(|(i, d)| (i as u32, *d))
+            .collect()
+    }
+
+    fn decompress(words: &[(u32, Digit)]) -> BitVec {
+        let mut bits = BitVec::ZERO;
+
+        if let Some((last, _)) = words.last() {
+            bits.0.resize(*last as usize + 1, 0);
+        }
+
+        for (i, d) in words {
+            bits.0[*i as usize] = *d;
+        }
+
+        bits
+    }
+}
+
+impl<NodeId: U16orU32> EdgeStore<NodeId> for CompressedStore<NodeId> {
+    #[inline]
+    fn get(&self, edge_id: (NodeId, NodeId)) -> Option<Cow<'_, BitVec>> {
+        self.inner
+            .get(&edge_id)
+            .map(|words| Cow::Owned(Self::decompress(words)))
+    }
+
+    #[inline]
+    fn get_bit(&self, edge_id: (NodeId, NodeId), bit_index: usize) -> Option<bool> {
+        let words = self.inner.get(&edge_id)?;
+        let (i, j) = ((bit_index / BITS) as u32, bit_index % BITS);
+
+        let bit = match words.binary_search_by_key(&i, |(k, _)| *k) {
+            Ok(k) => words[k].1 & (1 << j) != 0,
+            Err(_) => false,
+        };
+
+        Some(bit)
+    }
+
+    #[inline]
+    fn insert(&mut self, edge_id: (NodeId, NodeId), val: BitVec) {
+        let capacity = self.inner.capacity();
+
+        match self.inner.entry(edge_id) {
+            Entry::Occupied(mut words) => {
+                let mut bits = Self::decompress(words.get());
+                bits.bitor_assign(&val);
+                *words.get_mut() = Self::compress(&bits);
+            }
+            Entry::Vacant(words) => {
+                words.insert(Self::compress(&val));
+            }
+        }
+
+        record_map_grow(capacity, self.inner.capacity());
+    }
+
+    #[inline]
+    fn remove(&mut self, edge_id: (NodeId, NodeId)) -> Option<BitVec> {
+        self.inner
+            .remove(&edge_id)
+            .map(|words| Self::decompress(&words))
+    }
+
+    #[inline]
+    fn contains(&self, edge_id: (NodeId, NodeId)) -> bool {
+        self.inner.contains_key(&edge_id)
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = ((NodeId, NodeId), Cow<'_, BitVec>)> + '_> {
+        Box::new(
+            self.inner
+                .iter()
+                .map(|(k, v)| (*k, Cow::Owned(Self::decompress(v)))),
+        )
+    }
+
+    fn truncate(&mut self, nodes_len: usize) {
+        self.inner
+            .retain(|(a, b), _| a.as_usize() < nodes_len && b.as_usize() < nodes_len);
+
+        for words in self.inner.values_mut() {
+            let mut bits = Self::decompress(words);
+            bits.truncate(nodes_len);
+            *words = Self::compress(&bits);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::sequential::{SeqGraph, SeqGraphBuilder};
+
+    fn build_grid<S: EdgeStore<u16>>(w: u16, h: u16) -> SeqGraph<u16, S> {
+        let mut builder: SeqGraphBuilder<u16, S> = SeqGraphBuilder::with_store((w * h) as usize);
+
+        for y in 0..h {
+            for x in 0..w {
+                let node = y * w + x;
+
+                if x + 1 < w {
+                    builder.connect(node, node + 1);
+                }
+                if y + 1 < h {
+                    builder.connect(node, node + w);
+                }
+            }
+        }
+
+        builder.build()
+    }
+
+    #[test]
+    fn test_stores_agree() {
+        let hash_map = build_grid::<HashMapStore<u16>>(9, 9);
+        let csr = build_grid::<CsrStore<u16>>(9, 9);
+        let compressed = build_grid::<CompressedStore<u16>>(9, 9);
+
+        assert_eq!(hash_map.edges_len(), csr.edges_len());
+        assert_eq!(hash_map.edges_len(), compressed.edges_len());
+
+        for curr in 0..81 {
+            for dest in 0..81 {
+                let expected = hash_map.neighbors_to(curr, dest).collect::<Vec<_>>();
+
+                assert_eq!(csr.neighbors_to(curr, dest).collect::<Vec<_>>(), expected);
+                assert_eq!(
+                    compressed.neighbors_to(curr, dest).collect::<Vec<_>>(),
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_csr_store() {
+        let mut store = CsrStore::<u16>::default();
+
+        store.insert((3, 5), BitVec::one(5));
+        store.insert((0, 9), BitVec::one(9));
+        store.insert((3, 4), BitVec::one(4));
+        store.insert((3, 5), BitVec::one(1));
+
+        // new edges are pending until compacted, and found either way
+        for compacted in [false, true] {
+            if compacted {
+                store.compact();
+                assert!(store.pending.is_empty());
+                assert_eq!(store.offsets, vec![0, 1, 1, 1, 3]);
+            }
+
+            assert_eq!(store.len(), 3);
+            assert_eq!(store.get_bit((3, 5), 5), Some(true));
+            assert_eq!(store.get_bit((3, 5), 1), Some(true));
+        }
+
+        // edges already laid out are merged in place
+        store.insert((0, 9), BitVec::one(2));
+        assert!(store.pending.is_empty());
+        assert_eq!(store.get_bit((0, 9), 2), Some(true));
+
+        assert_eq!(store.len(), 3);
+        assert_eq!(store.get_bit((3, 5), 5), Some(true));
+        assert_eq!(store.get_bit((3, 5), 1), Some(true));
+        assert_eq!(store.get_bit((3, 4), 5), Some(false));
+        assert_eq!(store.get_bit((1, 2), 0), None);
+
+        // removed edges keep their place until compacted, and take it again if inserted
+        assert!(store.remove((3, 4)).is_some());
+        assert!(!store.contains((3, 4)));
+        assert_eq!((store.len(), store.targets.len()), (2, 3));
+
+        store.insert((3, 4), BitVec::one(7));
+        assert_eq!(store.get_bit((3, 4), 4), Some(false));
+        assert_eq!(store.get_bit((3, 4), 7), Some(true));
+        assert!(store.pending.is_empty());
+
+        assert!(store.remove((3, 4)).is_some());
+        store.compact();
+        assert_eq!((store.len(), store.targets.len()), (2, 2));
+        assert_eq!(store.get_bit((3, 5), 5), Some(true));
+
+        store.truncate(6);
+        assert_eq!(
+            store.iter().map(|(k, _)| k).collect::<Vec<_>>(),
+            vec![(3, 5)]
+        );
+    }
+
+    #[test]
+    fn test_graph_builder_store() {
+        use crate::graph::{Graph, GraphBuilder};
+
+        let mut builder = GraphBuilder::<u16, CsrStore<u16>>::with_store(81);
+        let mut default = Graph::<u16>::builder(81);
+        for y in 0..9 {
+            for x in 0..9 {
+                let node = y * 9 + x;
+                let right = (x + 1 < 9).then(|| node + 1);
+                let down = (y + 1 < 9).then(|| node + 9);
+                for next in right.into_iter().chain(down) {
+                    builder.connect(node, next);
+                    default.connect(node, next);
+                }
+            }
+        }
+
+        let csr: Graph<u16, CsrStore<u16>> = Graph::Sequential(builder.build_seq());
+        let default = default.build();
+
+        for curr in 0..81 {
+            for dest in 0..81 {
+                assert!(csr
+                    .neighbors_to(curr, dest)
+                    .eq(default.neighbors_to(curr, dest)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_hash_map_store_shares_unchanged_edges() {
+        // two separate 4x4 grids: nodes 0..16 and 16..32
+        let mut builder = SeqGraph::<u16>::builder(32);
+        for offset in [0, 16] {
+            for i in 0..16 {
+                if i % 4 != 3 {
+                    builder.connect(offset + i, offset + i + 1);
+                }
+                if i < 12 {
+                    builder.connect(offset + i, offset + i + 4);
+                }
+            }
+        }
+        let graph = builder.build();
+
+        // a variant with a passage closed in the second grid only
+        let mut builder = graph.clone().into_builder();
+        builder.disconnect(16, 17);
+        let variant = builder.build();
+
+        let shared = |a: u16, b: u16| {
+            Arc::ptr_eq(&graph.edges.inner[&(a, b)], &variant.edges.inner[&(a, b)])
+        };
+
+        // the first grid's shortest paths did not change, so its bits are still shared
+        assert!(graph
+            .edges
+            .inner
+            .keys()
+            .filter(|(a, _)| *a < 16)
+            .all(|&(a, b)| shared(a, b)));
+        assert!(variant.edges.inner.keys().any(|&(a, b)| !shared(a, b)));
+
+        assert_eq!(graph.neighbor_to(16, 17), Some(17));
+        assert_eq!(variant.neighbor_to(16, 17), Some(20));
+    }
+}
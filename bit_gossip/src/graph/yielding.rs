@@ -0,0 +1,38 @@
+//! yield points between the gossip rounds of a build,
+//! used by `build_yielding` and `build_async`.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// A future that is pending once, and ready the next time it is polled.
+///
+/// Waking the task right away puts it at the back of the executor's queue,
+/// so other tasks get to run before the build continues.
+#[derive(Debug, Default)]
+pub(crate) struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+
+        self.yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+/// Return whether a build yields after the given number of completed rounds;
+/// `every` of `0` never yields.
+#[inline]
+pub(crate) fn should_yield(rounds: usize, every: usize) -> bool {
+    every > 0 && rounds % every == 0
+}
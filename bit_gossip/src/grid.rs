@@ -0,0 +1,364 @@
+//! Graph helpers for 2D grids, where each node is a cell at `(x, y)`.
+//!
+//! Cells are numbered row by row, so the node ID of cell `(x, y)` is `y * width + x`.
+//! [GridGraph] mirrors the query API of [Graph] with coordinates,
+//! so game code doesn't have to convert between node IDs and cells on every query.
+//!
+//! # Example
+//!
+//! ```sh
+//! (0,0) -- (1,0) -- (2,0)
+//!                     |
+//! (0,1) -- (1,1) -- (2,1)
+//! ```
+//!
+//! ```
+//! use bit_gossip::grid::GridGraph;
+//!
+//! let mut builder = GridGraph::<u16>::builder(3, 2);
+//! builder.connect((0, 0), (1, 0));
+//! builder.connect((1, 0), (2, 0));
+//! builder.connect((2, 0), (2, 1));
+//! builder.connect((2, 1), (1, 1));
+//! builder.connect((1, 1), (0, 1));
+//!
+//! let grid = builder.build();
+//!
+//! assert_eq!(grid.next_cell((0, 0), (0, 1)), Some((1, 0)));
+//! assert_eq!(
+//!     grid.path_cells((1, 0), (1, 1)).collect::<Vec<_>>(),
+//!     vec![(1, 0), (2, 0), (2, 1), (1, 1)]
+//! );
+//! ```
+
+use crate::{
+    graph::{greedy_neighbor_to, GraphBuilder, U16orU32},
+    Graph,
+};
+
+/// A [Graph] over the cells of a `width x height` grid.
+///
+/// To see a basic use case example, check the [grid](crate::grid) module documentation.
+#[derive(Debug)]
+pub struct GridGraph<NodeId: U16orU32 = u16> {
+    graph: Graph<NodeId>,
+    width: u16,
+    height: u16,
+}
+
+impl<NodeId: U16orU32> GridGraph<NodeId> {
+    /// Create a new GridGraphBuilder for a grid of the given size.
+    ///
+    /// Panics if the number of cells exceeds the limit of the NodeId type.
+    #[inline]
+    pub fn builder(width: u16, height: u16) -> GridGraphBuilder<NodeId> {
+        GridGraphBuilder {
+            builder: Graph::builder(width as usize * height as usize),
+            width,
+            height,
+        }
+    }
+
+    /// Wrap a graph whose nodes are the cells of a `width x height` grid.
+    ///
+    /// Panics if the number of nodes is not `width * height`.
+    #[inline]
+    pub fn from_graph(graph: Graph<NodeId>, width: u16, height: u16) -> Self {
+        assert_eq!(
+            graph.nodes_len(),
+            width as usize * height as usize,
+            "Number of nodes must be width * height"
+        );
+
+        Self {
+            graph,
+            width,
+            height,
+        }
+    }
+
+    /// Converts this grid into a builder.
+    ///
+    /// This is useful if you want to add/remove edges.
+    #[inline]
+    pub fn into_builder(self) -> GridGraphBuilder<NodeId> {
+        GridGraphBuilder {
+            builder: self.graph.into_builder(),
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    /// Return the underlying graph.
+    #[inline]
+    pub fn graph(&self) -> &Graph<NodeId> {
+        &self.graph
+    }
+
+    /// Unwrap the underlying graph.
+    #[inline]
+    pub fn into_graph(self) -> Graph<NodeId> {
+        self.graph
+    }
+
+    /// Return the width of the grid.
+    #[inline]
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    /// Return the height of the grid.
+    #[inline]
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
+    /// Return the node ID of the given cell.
+    #[inline]
+    pub fn node(&self, cell: (u16, u16)) -> NodeId {
+        cell_to_node(cell, self.width, self.height)
+    }
+
+    /// Return the cell of the given node ID.
+    #[inline]
+    pub fn cell(&self, node: NodeId) -> (u16, u16) {
+        node_to_cell(node, self.width)
+    }
+
+    /// Given a current cell and a destination cell,
+    /// return the first neighboring cell that is the shortest path to the destination cell.
+    ///
+    /// Same as [Graph::neighbor_to] with coordinates.
+    #[inline]
+    pub fn next_cell(&self, curr: (u16, u16), dest: (u16, u16)) -> Option<(u16, u16)> {
+        self.graph
+            .neighbor_to(self.node(curr), self.node(dest))
+            .map(|n| self.cell(n))
+    }
+
+    /// Same as [next_cell](Self::next_cell) if `dest` is [ready](Graph::dest_ready);
+    /// otherwise, fall back to a breadth-first search of at most `hop_budget` hops from `curr`,
+    /// and move toward the searched cell closest to `dest` by Manhattan distance.
+    ///
+    /// This keeps agents moving plausibly while the exact data is unavailable,
+    /// e.g. for destinations dropped with [Graph::retain_dests];
+    /// the move is a shortest path's first hop if `dest` is within `hop_budget` hops.
+    ///
+    /// `None` is returned when:
+    /// - `curr` and `dest` are the same cell
+    /// - `curr` has no path to `dest`; if `dest` is not ready, this is only known when every cell
+    ///   connected to `curr` is within `hop_budget` hops
+    ///
+    /// ```
+    /// use bit_gossip::{bitvec::BitVec, grid::GridGraph};
+    ///
+    /// let mut builder = GridGraph::<u16>::builder(8, 1);
+    /// builder.connect_all();
+    /// let grid = builder.build();
+    ///
+    /// // only the paths to cell (7, 0) are kept
+    /// let mut dests = BitVec::ZERO;
+    /// dests.set_bit(7, true);
+    /// let mut graph = grid.into_graph();
+    /// graph.retain_dests(&dests);
+    /// let grid = GridGraph::from_graph(graph, 8, 1);
+    ///
+    /// assert_eq!(grid.next_cell((6, 0), (0, 0)), None);
+    /// assert_eq!(grid.next_cell_or_greedy((6, 0), (0, 0), 2), Some((5, 0)));
+    /// ```
+    pub fn next_cell_or_greedy(
+        &self,
+        curr: (u16, u16),
+        dest: (u16, u16),
+        hop_budget: usize,
+    ) -> Option<(u16, u16)> {
+        let (curr, dest) = (self.node(curr), self.node(dest));
+
+        if self.graph.dest_ready(dest) {
+            return self.graph.neighbor_to(curr, dest).map(|n| self.cell(n));
+        }
+
+        let (x, y) = self.cell(dest);
+        let distance = |node: NodeId| {
+            let (nx, ny) = self.cell(node);
+            nx.abs_diff(x) as u64 + ny.abs_diff(y) as u64
+        };
+
+        greedy_neighbor_to(
+            |node| self.graph.neighbors(node),
+            curr,
+            dest,
+            hop_budget,
+            Some(&distance),
+        )
+        .map(|n| self.cell(n))
+    }
+
+    /// Given a current cell and a destination cell, and a filter function,
+    /// return the neighboring cell that is the shortest path to the destination cell.
+    ///
+    /// Same as [Graph::neighbor_to_with] with coordinates.
+    #[inline]
+    pub fn next_cell_with(
+        &self,
+        curr: (u16, u16),
+        dest: (u16, u16),
+        f: impl Fn((u16, u16)) -> bool,
+    ) -> Option<(u16, u16)> {
+        self.next_cells(curr, dest).find(|&c| f(c))
+    }
+
+    /// Given a current cell and a destination cell,
+    /// return all neighboring cells that are shortest paths to the destination cell.
+    ///
+    /// Same as [Graph::neighbors_to] with coordinates.
+    #[inline]
+    pub fn next_cells(
+        &self,
+        curr: (u16, u16),
+        dest: (u16, u16),
+    ) -> impl Iterator<Item = (u16, u16)> + '_ {
+        self.graph
+            .neighbors_to(self.node(curr), self.node(dest))
+            .map(|n| self.cell(n))
+    }
+
+    /// Given a current cell and a destination cell,
+    /// return a path of cells from the current cell to the destination cell.
+    ///
+    /// Same as [Graph::path_to] with coordinates.
+    #[inline]
+    pub fn path_cells(
+        &self,
+        curr: (u16, u16),
+        dest: (u16, u16),
+    ) -> impl Iterator<Item = (u16, u16)> + '_ {
+        self.graph
+            .path_to(self.node(curr), self.node(dest))
+            .map(|n| self.cell(n))
+    }
+
+    /// Check if there is a path from the current cell to the destination cell.
+    #[inline]
+    pub fn path_exists(&self, curr: (u16, u16), dest: (u16, u16)) -> bool {
+        self.graph.path_exists(self.node(curr), self.node(dest))
+    }
+
+    /// Return all connected neighboring cells of the given cell.
+    #[inline]
+    pub fn neighbor_cells(&self, cell: (u16, u16)) -> impl Iterator<Item = (u16, u16)> + '_ {
+        self.graph
+            .neighbors(self.node(cell))
+            .iter()
+            .map(|n| self.cell(*n))
+    }
+}
+
+/// A builder for creating a [GridGraph].
+#[derive(Debug)]
+pub struct GridGraphBuilder<NodeId: U16orU32 = u16> {
+    builder: GraphBuilder<NodeId>,
+    width: u16,
+    height: u16,
+}
+
+impl<NodeId: U16orU32> GridGraphBuilder<NodeId> {
+    /// Add an edge between two cells.
+    #[inline]
+    pub fn connect(&mut self, a: (u16, u16), b: (u16, u16)) {
+        let (a, b) = (self.node(a), self.node(b));
+        self.builder.connect(a, b);
+    }
+
+    /// Remove an edge between two cells.
+    #[inline]
+    pub fn disconnect(&mut self, a: (u16, u16), b: (u16, u16)) {
+        let (a, b) = (self.node(a), self.node(b));
+        self.builder.disconnect(a, b);
+    }
+
+    /// Connect every cell to its right and bottom neighbors, opening up the whole grid.
+    pub fn connect_all(&mut self) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if x + 1 < self.width {
+                    self.connect((x, y), (x + 1, y));
+                }
+                if y + 1 < self.height {
+                    self.connect((x, y), (x, y + 1));
+                }
+            }
+        }
+    }
+
+    /// Return the node ID of the given cell.
+    #[inline]
+    pub fn node(&self, cell: (u16, u16)) -> NodeId {
+        cell_to_node(cell, self.width, self.height)
+    }
+
+    /// Return the underlying graph builder.
+    #[inline]
+    pub fn builder_mut(&mut self) -> &mut GraphBuilder<NodeId> {
+        &mut self.builder
+    }
+
+    /// Build the grid graph.
+    #[inline]
+    pub fn build(self) -> GridGraph<NodeId> {
+        GridGraph {
+            graph: self.builder.build(),
+            width: self.width,
+            height: self.height,
+        }
+    }
+}
+
+#[inline]
+fn cell_to_node<NodeId: U16orU32>((x, y): (u16, u16), width: u16, height: u16) -> NodeId {
+    debug_assert!(
+        x < width && y < height,
+        "Cell ({x}, {y}) is out of the {width}x{height} grid"
+    );
+
+    NodeId::from_usize(y as usize * width as usize + x as usize)
+}
+
+#[inline]
+fn node_to_cell<NodeId: U16orU32>(node: NodeId, width: u16) -> (u16, u16) {
+    let node = node.as_usize();
+    let width = width as usize;
+
+    ((node % width) as u16, (node / width) as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cells_roundtrip() {
+        let mut builder = GridGraph::<u16>::builder(4, 3);
+        builder.connect_all();
+        builder.disconnect((1, 0), (1, 1));
+        let grid = builder.build();
+
+        for y in 0..3 {
+            for x in 0..4 {
+                assert_eq!(grid.cell(grid.node((x, y))), (x, y));
+            }
+        }
+
+        let path = grid.path_cells((0, 0), (3, 2)).collect::<Vec<_>>();
+        assert_eq!(path.len(), 6);
+        assert_eq!(path.last(), Some(&(3, 2)));
+        for w in path.windows(2) {
+            let dist = w[0].0.abs_diff(w[1].0) + w[0].1.abs_diff(w[1].1);
+            assert_eq!(dist, 1);
+        }
+
+        assert!(grid.path_exists((1, 0), (1, 1)));
+        assert!(!grid.next_cells((1, 0), (1, 1)).any(|c| c == (1, 1)));
+        assert_eq!(grid.neighbor_cells((1, 0)).count(), 2);
+    }
+}
@@ -0,0 +1,404 @@
+//! Engine-agnostic building blocks for using a [Graph] in a game loop.
+//!
+//! These are the patterns of the maze example, without depending on any engine:
+//!
+//! - [BackgroundBuild]: build a graph on its own thread, and poll it every frame until it is done.
+//! - [BackgroundSwap]: keep answering queries with the current graph while a rebuilt one is on its way,
+//!   and swap it in once it is done.
+//! - [FollowTarget]: an agent's next hop toward a target,
+//!   only queried again when the agent, the target or the graph changes.
+//!
+//! ```
+//! use bit_gossip::{
+//!     integration::{FollowTarget, BackgroundSwap},
+//!     Graph,
+//! };
+//!
+//! // a corridor 0 - 1 - 2 - 3
+//! let mut builder = Graph::builder(4);
+//! builder.connect(0u16, 1);
+//! builder.connect(1, 2);
+//! builder.connect(2, 3);
+//!
+//! let mut graph = BackgroundSwap::new();
+//! graph.rebuild(builder);
+//!
+//! // the game loop
+//! let mut enemy = None;
+//! loop {
+//!     graph.update();
+//!     let Some(g) = graph.graph() else {
+//!         // still building; enemies wait
+//!         continue;
+//!     };
+//!
+//!     let enemy = enemy.get_or_insert_with(|| FollowTarget::new(g, 0, 3));
+//!     match enemy.next() {
+//!         Some(next) => enemy.arrive(g, next),
+//!         None => break,
+//!     };
+//! }
+//!
+//! assert_eq!(enemy.map(|e| e.curr()), Some(3));
+//! ```
+
+use crate::graph::{Graph, GraphBuilder, U16orU32};
+use std::thread::{self, JoinHandle};
+
+/// Handle to a graph being built on its own thread.
+///
+/// Poll it every frame with [poll](Self::poll), or block on it with [wait](Self::wait).
+/// If the build panics, the panic is resumed on the thread that polls or waits for it.
+///
+/// Dropping the handle does not stop the build; its result is discarded.
+///
+/// ```
+/// use bit_gossip::{integration::BackgroundBuild, Graph};
+///
+/// let mut builder = Graph::builder(3);
+/// builder.connect(0u16, 1);
+/// builder.connect(1, 2);
+///
+/// let mut build = BackgroundBuild::spawn(builder);
+///
+/// let graph = loop {
+///     if let Some(graph) = build.poll() {
+///         break graph;
+///     }
+///     // do the rest of the frame
+/// };
+///
+/// assert_eq!(graph.neighbor_to(0, 2), Some(1));
+/// assert!(build.is_finished());
+/// ```
+#[derive(Debug)]
+pub struct BackgroundBuild<NodeId: U16orU32 = u16> {
+    handle: Option<JoinHandle<Graph<NodeId>>>,
+}
+
+impl<NodeId: U16orU32> BackgroundBuild<NodeId>
+where
+    GraphBuilder<NodeId>: Send + 'static,
+    Graph<NodeId>: Send + 'static,
+{
+    /// Start building the graph on a new thread.
+    pub fn spawn(builder: GraphBuilder<NodeId>) -> Self {
+        Self {
+            handle: Some(thread::spawn(move || builder.build())),
+        }
+    }
+}
+
+impl<NodeId: U16orU32> BackgroundBuild<NodeId> {
+    /// Return the built graph if the build is done, without blocking.
+    ///
+    /// The graph is returned only once; every later call returns `None`.
+    pub fn poll(&mut self) -> Option<Graph<NodeId>> {
+        if !self.handle.as_ref()?.is_finished() {
+            return None;
+        }
+
+        self.handle.take().map(join)
+    }
+
+    /// Block until the build is done, and return the built graph.
+    ///
+    /// `None` is returned if the graph was already returned by [poll](Self::poll).
+    pub fn wait(mut self) -> Option<Graph<NodeId>> {
+        self.handle.take().map(join)
+    }
+
+    /// Return whether the build is done, or its graph was already returned.
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.handle
+            .as_ref()
+            .map_or(true, |handle| handle.is_finished())
+    }
+}
+
+fn join<T>(handle: JoinHandle<T>) -> T {
+    handle
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
+
+/// The graph currently used for queries, and a rebuilt one on its way.
+///
+/// [rebuild](Self::rebuild) starts a [BackgroundBuild];
+/// the current graph keeps answering queries until [update](Self::update) swaps the rebuilt one in.
+///
+/// This is for a single owner, like a game loop; to share the graph with other threads,
+/// hand each swapped-in graph to an [ArcGraph](crate::graph::hot_swap::ArcGraph).
+///
+/// ```
+/// use bit_gossip::{integration::BackgroundSwap, Graph};
+///
+/// // a corridor 0 - 1 - 2 - 3
+/// let mut builder = Graph::builder(4);
+/// builder.connect(0u16, 1);
+/// builder.connect(1, 2);
+/// builder.connect(2, 3);
+/// let mut graph = BackgroundSwap::with_graph(builder.build());
+///
+/// // open a shortcut
+/// let mut builder = graph.graph().unwrap().clone().into_builder();
+/// builder.connect(0, 3);
+/// graph.rebuild(builder);
+///
+/// // the old graph is still used until the new one is swapped in
+/// assert_eq!(graph.generation(), 0);
+/// while graph.is_rebuilding() {
+///     assert_eq!(graph.graph().unwrap().neighbor_to(0, 3), Some(1));
+///     graph.update();
+/// }
+///
+/// assert_eq!(graph.generation(), 1);
+/// assert_eq!(graph.graph().unwrap().neighbor_to(0, 3), Some(3));
+/// ```
+#[derive(Debug)]
+pub struct BackgroundSwap<NodeId: U16orU32 = u16> {
+    current: Option<Graph<NodeId>>,
+    pending: Option<BackgroundBuild<NodeId>>,
+    generation: u64,
+}
+
+impl<NodeId: U16orU32> Default for BackgroundSwap<NodeId> {
+    fn default() -> Self {
+        Self {
+            current: None,
+            pending: None,
+            generation: 0,
+        }
+    }
+}
+
+impl<NodeId: U16orU32> BackgroundSwap<NodeId> {
+    /// Create an empty slot; queries have no graph until the first rebuild is swapped in.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a slot that starts with the given graph.
+    #[inline]
+    pub fn with_graph(graph: Graph<NodeId>) -> Self {
+        Self {
+            current: Some(graph),
+            ..Self::default()
+        }
+    }
+
+    /// Return the graph to use for queries, or `None` if no graph was built yet.
+    #[inline]
+    pub fn graph(&self) -> Option<&Graph<NodeId>> {
+        self.current.as_ref()
+    }
+
+    /// Return the number of graphs swapped in so far.
+    ///
+    /// Compare it with the value from the last frame to tell whether the graph changed,
+    /// e.g. to [refresh](FollowTarget::refresh) every agent.
+    #[inline]
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Return whether a rebuilt graph is on its way.
+    #[inline]
+    pub fn is_rebuilding(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Swap in the rebuilt graph if it is done.
+    ///
+    /// Returns `true` if a graph was swapped in.
+    pub fn update(&mut self) -> bool {
+        let Some(graph) = self.pending.as_mut().and_then(BackgroundBuild::poll) else {
+            return false;
+        };
+
+        self.pending = None;
+        self.current = Some(graph);
+        self.generation += 1;
+        true
+    }
+}
+
+impl<NodeId: U16orU32> BackgroundSwap<NodeId>
+where
+    GraphBuilder<NodeId>: Send + 'static,
+    Graph<NodeId>: Send + 'static,
+{
+    /// Start building the given builder in the background.
+    ///
+    /// A rebuild that was still on its way is discarded, so the latest edits always win.
+    pub fn rebuild(&mut self, builder: GraphBuilder<NodeId>) {
+        self.pending = Some(BackgroundBuild::spawn(builder));
+    }
+}
+
+/// An agent's next hop toward a target node.
+///
+/// The next hop is only queried again when the agent arrives at a node,
+/// the target moves, or the graph is [refreshed](Self::refresh);
+/// like the enemies of the maze example, which only look up their path on `Changed<>` components.
+///
+/// ```
+/// use bit_gossip::{integration::FollowTarget, Graph};
+///
+/// // a corridor 0 - 1 - 2 - 3
+/// let mut builder = Graph::builder(4);
+/// builder.connect(0u16, 1);
+/// builder.connect(1, 2);
+/// builder.connect(2, 3);
+/// let graph = builder.build();
+///
+/// let mut enemy = FollowTarget::new(&graph, 0, 3);
+/// assert_eq!(enemy.next(), Some(1));
+///
+/// enemy.arrive(&graph, 1);
+/// assert_eq!(enemy.next(), Some(2));
+///
+/// // the player walks back past the enemy
+/// assert!(enemy.set_target(&graph, 0));
+/// assert_eq!(enemy.next(), Some(0));
+///
+/// // the same target again; nothing changes
+/// assert!(!enemy.set_target(&graph, 0));
+///
+/// enemy.arrive(&graph, 0);
+/// assert!(enemy.reached());
+/// assert_eq!(enemy.next(), None);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FollowTarget<NodeId: U16orU32 = u16> {
+    curr: NodeId,
+    target: NodeId,
+    next: Option<NodeId>,
+}
+
+impl<NodeId: U16orU32> FollowTarget<NodeId> {
+    /// Start following `target` from `curr`.
+    pub fn new(graph: &Graph<NodeId>, curr: NodeId, target: NodeId) -> Self {
+        Self {
+            curr,
+            target,
+            next: graph.neighbor_to(curr, target),
+        }
+    }
+
+    /// Return the node the agent is at.
+    #[inline]
+    pub fn curr(&self) -> NodeId {
+        self.curr
+    }
+
+    /// Return the node the agent is following.
+    #[inline]
+    pub fn target(&self) -> NodeId {
+        self.target
+    }
+
+    /// Return the neighboring node to move to next.
+    ///
+    /// `None` is returned when:
+    /// - the agent has reached the target
+    /// - there is no path to the target
+    #[inline]
+    pub fn next(&self) -> Option<NodeId> {
+        self.next
+    }
+
+    /// Return whether the agent is at the target.
+    #[inline]
+    pub fn reached(&self) -> bool {
+        self.curr == self.target
+    }
+
+    /// Follow a new target node.
+    ///
+    /// Returns `true` if the next hop changed; the graph is not queried if the target is the same.
+    pub fn set_target(&mut self, graph: &Graph<NodeId>, target: NodeId) -> bool {
+        if self.target == target {
+            return false;
+        }
+
+        self.target = target;
+        self.refresh(graph)
+    }
+
+    /// Move the agent to the given node, usually the previous [next](Self::next) hop.
+    ///
+    /// Returns `true` if the next hop changed; the graph is not queried if the node is the same.
+    pub fn arrive(&mut self, graph: &Graph<NodeId>, node: NodeId) -> bool {
+        if self.curr == node {
+            return false;
+        }
+
+        self.curr = node;
+        self.refresh(graph)
+    }
+
+    /// Query the next hop again, e.g. after a new graph was swapped in.
+    ///
+    /// Returns `true` if the next hop changed.
+    pub fn refresh(&mut self, graph: &Graph<NodeId>) -> bool {
+        let next = graph.neighbor_to(self.curr, self.target);
+        let changed = next != self.next;
+        self.next = next;
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_follow_target_across_swaps() {
+        // a ring of 8 nodes, with 7 -- 0 cut, then closed again
+        let ring = |closed: bool| {
+            let mut builder = Graph::<u16>::builder(8);
+            for i in 0..8 {
+                if closed || i != 7 {
+                    builder.connect(i, (i + 1) % 8);
+                }
+            }
+            builder
+        };
+
+        let mut graph = BackgroundSwap::new();
+        assert!(graph.graph().is_none());
+        assert!(!graph.update());
+
+        graph.rebuild(ring(false));
+        while !graph.update() {}
+        assert!(!graph.is_rebuilding());
+
+        let mut enemy = FollowTarget::new(graph.graph().unwrap(), 0, 7);
+        assert_eq!(enemy.next(), Some(1));
+
+        // the newest rebuild wins over one that is still on its way
+        graph.rebuild(ring(false));
+        graph.rebuild(ring(true));
+        while !graph.update() {}
+        assert_eq!(graph.generation(), 2);
+        assert!(graph.graph().unwrap().neighbors(0).contains(&7));
+
+        assert!(enemy.refresh(graph.graph().unwrap()));
+        assert_eq!(enemy.next(), Some(7));
+        assert!(!enemy.refresh(graph.graph().unwrap()));
+    }
+
+    #[test]
+    fn test_background_build_wait() {
+        let mut builder = Graph::<u32>::builder(3);
+        builder.connect(0, 1);
+
+        let build = BackgroundBuild::spawn(builder);
+        let graph = build.wait().unwrap();
+        assert_eq!(graph.neighbor_to(1, 0), Some(0));
+        assert_eq!(graph.neighbor_to(2, 0), None);
+    }
+}
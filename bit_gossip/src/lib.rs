@@ -2,9 +2,49 @@
 //!
 //! See [prim] and [graph] modules for more information.
 //!
+//! For quick scripts and tests, [build] and [shortest_next_hop] build a [Graph] from a list of edges in one call.
+//!
+//! For game loops, the [integration] module has engine-agnostic helpers to build a graph in the background,
+//! swap in a rebuilt graph, and follow a moving target.
+//! The [testing] module stress-tests swapping graphs while other threads query them.
+//!
 //! ## Features
 //!
 //! - **parallel**: Enable parallelism using Rayon; this feature is enabled by default.
+//! - **serde**: Enable `Graph::describe`, a JSON summary of a built graph for external tooling.
+//! - **track-alloc**: Count internal allocations, reported by `GraphBuilder::build_with_stats`.
+//! - **strict-checks**: Check that node IDs given to graph queries are in range in release builds too;
+//!   they are always checked in debug builds.
+//!
+//! ## Minimum Supported Rust Version
+//!
+//! The MSRV is **1.65**, with dependency versions that support it;
+//! recent versions of Rayon and serde_json need a newer toolchain.
+//! CI runs the tests on 1.65 with no features, the default features,
+//! each feature on its own, and all features.
+//! Raising it is noted in the release notes.
+
+/// Panic if any of the given node IDs is out of range for a graph of `$nodes_len` nodes,
+/// with a message naming the method and the offending argument.
+///
+/// Only checked in debug builds, or with the `strict-checks` feature;
+/// otherwise an out-of-range ID panics on indexing or returns a meaningless result.
+macro_rules! check_nodes {
+    ($method:literal, $nodes_len:expr, $($node:ident),+) => {
+        if cfg!(any(debug_assertions, feature = "strict-checks")) {
+            let nodes_len: usize = $nodes_len;
+            $(
+                let node: u64 = $node.into();
+                if node >= nodes_len as u64 {
+                    panic!(
+                        concat!($method, ": `", stringify!($node), "` {} is out of range for a graph of {} nodes"),
+                        node, nodes_len
+                    );
+                }
+            )+
+        }
+    };
+}
 
 pub mod prim;
 pub use prim::{
@@ -13,10 +53,15 @@ pub use prim::{
 };
 
 pub mod graph;
+use graph::U16orU32;
 pub use graph::{Graph, GraphBuilder};
 
 pub mod bitvec;
+pub mod grid;
+pub mod integration;
 pub mod maze;
+pub mod multimodal;
+pub mod testing;
 
 /// Given two node IDs, return a tuple of the two IDs in ascending order.
 #[inline]
@@ -27,3 +72,83 @@ pub fn edge_id<T: Ord>(node_a_index: T, node_b_index: T) -> (T, T) {
         (node_a_index, node_b_index)
     }
 }
+
+/// Hash the number of nodes and the set of edges of a graph.
+///
+/// Edges are normalized with [edge_id], sorted and deduplicated before hashing,
+/// so the hash does not depend on the order the edges were added in.
+///
+/// Uses FNV-1a over the node IDs widened to `u64`, which is stable across
+/// Rust versions, platforms and NodeId types.
+pub(crate) fn topology_hash(nodes_len: usize, edges: impl Iterator<Item = (usize, usize)>) -> u64 {
+    const OFFSET: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    let mut edges = edges.map(|(a, b)| edge_id(a, b)).collect::<Vec<_>>();
+    edges.sort_unstable();
+    edges.dedup();
+
+    let mut hash = OFFSET;
+    let mut write = |value: u64| {
+        for byte in value.to_le_bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(PRIME);
+        }
+    };
+
+    write(nodes_len as u64);
+    for (a, b) in edges {
+        write(a as u64);
+        write(b as u64);
+    }
+
+    hash
+}
+
+/// Build a [Graph] with the given number of nodes from a list of edges.
+///
+/// Same as creating a builder with [Graph::builder], connecting all edges and building it.
+///
+/// Panics if the number of nodes exceeds the limit of the NodeId type.
+///
+/// ```
+/// let graph = bit_gossip::build([(0u16, 1), (1, 2), (2, 3)], 4);
+///
+/// assert_eq!(graph.path_to(0, 3).collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+/// ```
+pub fn build<NodeId: U16orU32>(
+    edges: impl IntoIterator<Item = (NodeId, NodeId)>,
+    nodes_len: usize,
+) -> Graph<NodeId> {
+    let mut builder = Graph::builder(nodes_len);
+
+    for (a, b) in edges {
+        builder.connect(a, b);
+    }
+
+    builder.build()
+}
+
+/// Build a graph from a list of edges, and return the first neighboring node of `src`
+/// that is the shortest path to `dst`.
+///
+/// This computes all paths of the graph just to answer a single query;
+/// if you have more than one query, use [build] and keep the graph around.
+///
+/// `None` is returned when:
+/// - `src` and `dst` are the same node
+/// - `src` has no path to `dst`
+///
+/// ```
+/// let edges = [(0u16, 1), (1, 2), (2, 3), (0, 4), (4, 3)];
+///
+/// assert_eq!(bit_gossip::shortest_next_hop(edges, 5, 0, 3), Some(4));
+/// ```
+pub fn shortest_next_hop<NodeId: U16orU32>(
+    edges: impl IntoIterator<Item = (NodeId, NodeId)>,
+    nodes_len: usize,
+    src: NodeId,
+    dst: NodeId,
+) -> Option<NodeId> {
+    build(edges, nodes_len).neighbor_to(src, dst)
+}
@@ -7,10 +7,157 @@
 
 use crate::graph::U16orU32;
 use rand::{rngs::StdRng, seq::SliceRandom, Rng, RngCore, SeedableRng};
+use std::fmt;
+
+/// Error returned when a maze of the given size cannot be built.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MazeError {
+    /// Width or height is zero.
+    Empty { width: usize, height: usize },
+    /// `width * height` cells do not fit in the node ID type.
+    TooManyCells {
+        width: usize,
+        height: usize,
+        max_nodes: usize,
+    },
+}
+
+impl fmt::Display for MazeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MazeError::Empty { width, height } => {
+                write!(f, "maze of {width}x{height} has no cells")
+            }
+            MazeError::TooManyCells {
+                width,
+                height,
+                max_nodes,
+            } => write!(
+                f,
+                "maze of {width}x{height} has more than {max_nodes} cells, the limit of its node ID type"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MazeError {}
+
+/// A maze with both its passages and its walls, and the open sides of each cell.
+///
+/// The builder needs the passages, while a renderer needs the walls;
+/// this has both, so neither has to be recomputed from the other.
+///
+/// Cells are numbered row by row: cell `x + y * width` is at column `x` and row `y`.
+///
+/// ```
+/// use bit_gossip::maze::MazeOutput;
+///
+/// let maze = MazeOutput::build(3u16, 2);
+///
+/// // a 3x2 grid has 7 sides between cells; a maze opens 5 of them
+/// assert_eq!(maze.passages.len(), 5);
+/// assert_eq!(maze.walls.len(), 2);
+/// assert_eq!(maze.cells.iter().map(|c| c.open_count()).sum::<usize>(), 10);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MazeOutput<N> {
+    pub width: N,
+    pub height: N,
+    /// pairs of adjacent cells that are connected, in the order they were carved
+    pub passages: Vec<(N, N)>,
+    /// pairs of adjacent cells that are separated by a wall, lower cell first, in cell order
+    pub walls: Vec<(N, N)>,
+    /// open sides of each cell, indexed by cell
+    pub cells: Vec<CellSides>,
+}
+
+/// Which sides of a maze cell have a passage.
+///
+/// `up` is toward row 0, and `left` is toward column 0.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct CellSides {
+    pub left: bool,
+    pub right: bool,
+    pub up: bool,
+    pub down: bool,
+}
+
+impl CellSides {
+    /// Return the number of open sides.
+    #[inline]
+    pub fn open_count(&self) -> usize {
+        self.left as usize + self.right as usize + self.up as usize + self.down as usize
+    }
+
+    /// Check if the cell has only one open side.
+    #[inline]
+    pub fn is_dead_end(&self) -> bool {
+        self.open_count() == 1
+    }
+}
+
+impl<N: U16orU32> MazeOutput<N> {
+    /// Builds a maze of the given width and height, with its walls and cell sides.
+    ///
+    /// Panics if the maze is empty or has too many cells for `N`, like [build_maze].
+    pub fn build(w: N, h: N) -> Self {
+        Self::new(w, h, build_maze(w, h))
+    }
+
+    /// Given width and height and the passages of a maze,
+    /// compute its walls and cell sides.
+    ///
+    /// This works with the output of any of the `build_maze` functions.
+    ///
+    /// Panics if a passage is not between two adjacent cells of the maze.
+    pub fn new(w: N, h: N, passages: Vec<(N, N)>) -> Self {
+        let w_usize = w.as_usize();
+        let h_usize = h.as_usize();
+
+        let mut cells = vec![CellSides::default(); w_usize * h_usize];
+
+        for &(a, b) in &passages {
+            let (a, b) = crate::edge_id(a.as_usize(), b.as_usize());
+            assert!(
+                b < cells.len() && (b == a + w_usize || (b == a + 1 && b % w_usize != 0)),
+                "passage ({a}, {b}) is not between adjacent cells of a {w_usize}x{h_usize} maze"
+            );
+
+            if b == a + 1 {
+                cells[a].right = true;
+                cells[b].left = true;
+            } else {
+                cells[a].down = true;
+                cells[b].up = true;
+            }
+        }
+
+        let mut walls = Vec::new();
+        for (i, sides) in cells.iter().enumerate() {
+            if i % w_usize < w_usize - 1 && !sides.right {
+                walls.push((N::from_usize(i), N::from_usize(i + 1)));
+            }
+            if i / w_usize < h_usize - 1 && !sides.down {
+                walls.push((N::from_usize(i), N::from_usize(i + w_usize)));
+            }
+        }
+
+        Self {
+            width: w,
+            height: h,
+            passages,
+            walls,
+            cells,
+        }
+    }
+}
 
 /// Builds a maze of the given width and height.
 ///
 /// Returns a list of pairs of cells that are connected.
+///
+/// Panics if the maze is empty or has too many cells for `N`;
+/// use [try_build_maze] to handle these cases.
 pub fn build_maze<N: U16orU32>(w: N, h: N) -> Vec<(N, N)> {
     build_maze_with_rng(w, h, &mut StdRng::from_entropy())
 }
@@ -20,6 +167,12 @@ pub fn build_maze<N: U16orU32>(w: N, h: N) -> Vec<(N, N)> {
 /// Returns a list of pairs of cells that are connected.
 ///
 /// Uses [StdRng] with the provided seed.
+///
+/// The same seed gives a different maze than in version 0.0.13 and earlier,
+/// since branches cut short by the depth limit are now revisited.
+///
+/// Panics if the maze is empty or has too many cells for `N`;
+/// use [try_build_maze_from_seed] to handle these cases.
 pub fn build_maze_from_seed<N: U16orU32>(w: N, h: N, seed: [u8; 32]) -> Vec<(N, N)> {
     build_maze_with_rng(w, h, &mut StdRng::from_seed(seed))
 }
@@ -27,10 +180,78 @@ pub fn build_maze_from_seed<N: U16orU32>(w: N, h: N, seed: [u8; 32]) -> Vec<(N,
 /// Given width and height, build a maze with the provided Rng.
 ///
 /// Returns a list of pairs of cells that are connected.
+///
+/// Panics if the maze is empty or has too many cells for `N`;
+/// use [try_build_maze_with_rng] to handle these cases.
 pub fn build_maze_with_rng<N: U16orU32, R: RngCore>(w: N, h: N, rng: &mut R) -> Vec<(N, N)> {
+    try_build_maze_with_rng(w, h, rng).unwrap_or_else(|err| panic!("{err}"))
+}
+
+/// Builds a maze of the given width and height,
+/// or returns an error if the maze is empty or has too many cells for `N`.
+///
+/// Any rectangular size works as long as `w * h` is at most `N::MAX_NODES`;
+/// e.g. 256x256 or 65535x1 for `u16`.
+///
+/// ```
+/// use bit_gossip::maze::{try_build_maze, MazeError};
+///
+/// assert_eq!(try_build_maze(256u16, 256).unwrap().len(), 256 * 256 - 1);
+/// assert!(matches!(
+///     try_build_maze(256u16, 257),
+///     Err(MazeError::TooManyCells { .. })
+/// ));
+/// ```
+pub fn try_build_maze<N: U16orU32>(w: N, h: N) -> Result<Vec<(N, N)>, MazeError> {
+    try_build_maze_with_rng(w, h, &mut StdRng::from_entropy())
+}
+
+/// Given width and height, build a maze with the provided seed,
+/// or return an error if the maze is empty or has too many cells for `N`.
+///
+/// Uses [StdRng] with the provided seed.
+///
+/// The same seed gives a different maze than in version 0.0.13 and earlier,
+/// since branches cut short by the depth limit are now revisited.
+pub fn try_build_maze_from_seed<N: U16orU32>(
+    w: N,
+    h: N,
+    seed: [u8; 32],
+) -> Result<Vec<(N, N)>, MazeError> {
+    try_build_maze_with_rng(w, h, &mut StdRng::from_seed(seed))
+}
+
+/// Given width and height, build a maze with the provided Rng,
+/// or return an error if the maze is empty or has too many cells for `N`.
+///
+/// An Rng in the same state gives a different maze than in version 0.0.13 and earlier,
+/// since branches cut short by the depth limit are now revisited.
+pub fn try_build_maze_with_rng<N: U16orU32, R: RngCore>(
+    w: N,
+    h: N,
+    rng: &mut R,
+) -> Result<Vec<(N, N)>, MazeError> {
     let w_usize = w.as_usize();
     let h_usize = h.as_usize();
 
+    if w_usize == 0 || h_usize == 0 {
+        return Err(MazeError::Empty {
+            width: w_usize,
+            height: h_usize,
+        });
+    }
+
+    match w_usize.checked_mul(h_usize) {
+        Some(cells) if cells <= N::MAX_NODES => {}
+        _ => {
+            return Err(MazeError::TooManyCells {
+                width: w_usize,
+                height: h_usize,
+                max_nodes: N::MAX_NODES,
+            })
+        }
+    }
+
     let mut maze = Vec::with_capacity(w_usize * h_usize);
 
     // create maze with following algorithm, in a loop, not recursive:
@@ -74,6 +295,11 @@ pub fn build_maze_with_rng<N: U16orU32, R: RngCore>(w: N, h: N, rng: &mut R) ->
             visited[curr as usize] = true;
             depth += 1;
         } else if let Some(prev) = stack.pop() {
+            // if the depth limit cut this branch short, come back to this cell later;
+            // otherwise its unvisited neighbors may never be reached, like in a single row maze
+            if !neighbors.is_empty() {
+                stack.push(curr);
+            }
             curr = prev;
             depth = 0;
         } else {
@@ -81,5 +307,84 @@ pub fn build_maze_with_rng<N: U16orU32, R: RngCore>(w: N, h: N, rng: &mut R) ->
         }
     }
 
-    maze
+    Ok(maze)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_maze_output() {
+        let (w, h) = (7u16, 5u16);
+        let maze = MazeOutput::new(w, h, build_maze_from_seed(w, h, [3; 32]));
+
+        // every side between two cells is either a passage or a wall
+        let sides = (w as usize - 1) * h as usize + w as usize * (h as usize - 1);
+        assert_eq!(maze.passages.len() + maze.walls.len(), sides);
+        assert!(maze.walls.iter().all(|&(a, b)| a < b
+            && !maze.passages.contains(&(a, b))
+            && !maze.passages.contains(&(b, a))));
+
+        for (cell, sides) in maze.cells.iter().enumerate() {
+            let open = maze
+                .passages
+                .iter()
+                .filter(|&&(a, b)| a as usize == cell || b as usize == cell)
+                .count();
+            assert_eq!(sides.open_count(), open);
+        }
+        assert!(!maze.cells[0].left && !maze.cells[0].up);
+        assert!(maze.cells.iter().any(CellSides::is_dead_end));
+    }
+
+    #[test]
+    #[should_panic(expected = "not between adjacent cells")]
+    fn test_maze_output_rejects_wrapping_passage() {
+        // cells 2 and 3 are at the ends of different rows
+        MazeOutput::new(3u16, 2, vec![(2, 3)]);
+    }
+
+    #[test]
+    fn test_boundary_sizes() {
+        let seed = [7; 32];
+
+        // every cell is reached, and every id fits in u16
+        for (w, h) in [(256u16, 256u16), (65535, 1), (1, 65535), (1, 1)] {
+            let maze = try_build_maze_from_seed(w, h, seed).unwrap();
+            let cells = w as usize * h as usize;
+            assert_eq!(maze.len(), cells - 1, "{w}x{h}");
+            assert!(maze
+                .iter()
+                .all(|&(a, b)| (a as usize) < cells && (b as usize) < cells));
+        }
+
+        assert_eq!(
+            try_build_maze_from_seed(256u16, 257, seed),
+            Err(MazeError::TooManyCells {
+                width: 256,
+                height: 257,
+                max_nodes: 1 << 16,
+            })
+        );
+        assert_eq!(
+            try_build_maze_from_seed(u16::MAX, u16::MAX, seed).unwrap_err(),
+            MazeError::TooManyCells {
+                width: 65535,
+                height: 65535,
+                max_nodes: 1 << 16,
+            }
+        );
+        assert_eq!(
+            try_build_maze_from_seed(0u16, 5, seed),
+            Err(MazeError::Empty {
+                width: 0,
+                height: 5
+            })
+        );
+
+        // u32 has room for what u16 does not
+        let maze = try_build_maze_from_seed(256u32, 257, seed).unwrap();
+        assert_eq!(maze.len(), 256 * 257 - 1);
+    }
 }
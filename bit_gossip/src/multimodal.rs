@@ -0,0 +1,322 @@
+//! Route planning over a walking graph combined with one-way links, like teleporters or zip-lines.
+//!
+//! Folding links into the walking graph as plain edges loses the distinction at query time:
+//! an agent can't tell whether its next node is a step or a teleport,
+//! and links can't be switched on and off without rebuilding the graph.
+//!
+//! [Multimodal] keeps them apart. The walking [Graph] answers the walking parts of a route,
+//! and the walking distances between links are precomputed when it is created;
+//! each query picks the cheapest combination of walking and links that are active for that query,
+//! and returns the first [Action] to take.
+//!
+//! ```
+//! use bit_gossip::{
+//!     multimodal::{Action, Link, Multimodal},
+//!     Graph,
+//! };
+//!
+//! // a long corridor, with a teleporter from 1 to 8
+//! let mut builder = Graph::builder(10);
+//! for i in 0..9u16 {
+//!     builder.connect(i, i + 1);
+//! }
+//! let teleporter = Link { from: 1, to: 8, cost: 1 };
+//! let planner = Multimodal::new(builder.build(), vec![teleporter]);
+//!
+//! assert_eq!(planner.next_action(0, 9), Some(Action::Move(1)));
+//! assert_eq!(planner.next_action(1, 9), Some(Action::UseLink(0)));
+//! assert_eq!(planner.next_action(8, 9), Some(Action::Move(9)));
+//!
+//! // the teleporter is not powered for this agent
+//! assert_eq!(planner.next_action_with(1, 9, |_| false), Some(Action::Move(2)));
+//! ```
+
+use crate::graph::{Graph, U16orU32};
+
+/// A one-way link from one node to another, like a teleporter or a zip-line.
+///
+/// Two-way links are two links, one in each direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Link<NodeId: U16orU32 = u16> {
+    /// Node where the link is used.
+    pub from: NodeId,
+    /// Node where the link arrives.
+    pub to: NodeId,
+    /// Cost of using the link, in walking steps.
+    pub cost: u32,
+}
+
+/// The next action to take toward a destination.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action<NodeId: U16orU32 = u16> {
+    /// Walk to this neighboring node.
+    Move(NodeId),
+    /// Use the link with this index; the current node is its `from` node.
+    UseLink(usize),
+}
+
+/// A walking graph combined with links between its nodes.
+///
+/// Link ids are the indices of the links given to [new](Self::new).
+#[derive(Debug, Clone)]
+pub struct Multimodal<NodeId: U16orU32 = u16> {
+    graph: Graph<NodeId>,
+    links: Vec<Link<NodeId>>,
+    /// `exit_to_entry[i * links.len() + j]`: walking steps from the `to` node of link `i`
+    /// to the `from` node of link `j`, or `None` if there is no walking path.
+    exit_to_entry: Vec<Option<u32>>,
+}
+
+impl<NodeId: U16orU32> Multimodal<NodeId> {
+    /// Combine a walking graph with the given links.
+    ///
+    /// This walks the paths between every pair of links once,
+    /// so it takes `O(links² × path length)`.
+    ///
+    /// Panics if a link's node is out of range of the graph.
+    pub fn new(graph: Graph<NodeId>, links: Vec<Link<NodeId>>) -> Self {
+        for link in &links {
+            assert!(
+                link.from.as_usize() < graph.nodes_len() && link.to.as_usize() < graph.nodes_len(),
+                "link from {} to {} is out of range for a graph of {} nodes",
+                link.from,
+                link.to,
+                graph.nodes_len()
+            );
+        }
+
+        let exit_to_entry = links
+            .iter()
+            .flat_map(|exit| {
+                links
+                    .iter()
+                    .map(|entry| walk_len(&graph, exit.to, entry.from))
+            })
+            .collect();
+
+        Self {
+            graph,
+            links,
+            exit_to_entry,
+        }
+    }
+
+    /// Return the walking graph.
+    #[inline]
+    pub fn graph(&self) -> &Graph<NodeId> {
+        &self.graph
+    }
+
+    /// Return the links; a link's id is its index.
+    #[inline]
+    pub fn links(&self) -> &[Link<NodeId>] {
+        &self.links
+    }
+
+    /// Given a current node and a destination node,
+    /// return the first action of the cheapest route to the destination node, using any link.
+    ///
+    /// Same as `self.next_action_with(curr, dest, |_| true)`.
+    #[inline]
+    pub fn next_action(&self, curr: NodeId, dest: NodeId) -> Option<Action<NodeId>> {
+        self.next_action_with(curr, dest, |_| true)
+    }
+
+    /// Given a current node and a destination node, and a function telling which links are active,
+    /// return the first action of the cheapest route to the destination node.
+    ///
+    /// The cost of a route is the number of walking steps, plus the cost of each link used.
+    /// Walking is preferred over links of the same total cost.
+    ///
+    /// Each query compares the routes through every active link, taking `O(links² + links × path length)`;
+    /// this is meant for a handful of links on top of a big walking graph.
+    ///
+    /// `None` is returned when:
+    /// - `curr` and `dest` are the same node
+    /// - `curr` has no route to `dest`
+    pub fn next_action_with(
+        &self,
+        curr: NodeId,
+        dest: NodeId,
+        active: impl Fn(usize) -> bool,
+    ) -> Option<Action<NodeId>> {
+        if curr == dest {
+            return None;
+        }
+
+        let links_len = self.links.len();
+        let active = (0..links_len).map(active).collect::<Vec<_>>();
+
+        // cost to arrive at the `to` node of each link, and the first link of that route
+        let mut arrive = vec![None::<(u32, usize)>; links_len];
+        for (i, link) in self.links.iter().enumerate() {
+            if active[i] {
+                arrive[i] = walk_len(&self.graph, curr, link.from).map(|d| (d + link.cost, i));
+            }
+        }
+
+        // extend the routes a link at a time, in order of arrival cost
+        let mut done = vec![false; links_len];
+        while let Some(i) = (0..links_len)
+            .filter(|&i| !done[i] && arrive[i].is_some())
+            .min_by_key(|&i| arrive[i].map(|(cost, _)| cost))
+        {
+            done[i] = true;
+            let (cost, first) = arrive[i].unwrap();
+
+            for j in 0..links_len {
+                let Some(walk) = self.exit_to_entry[i * links_len + j] else {
+                    continue;
+                };
+                let via = cost + walk + self.links[j].cost;
+                if active[j] && !done[j] && arrive[j].map_or(true, |(c, _)| via < c) {
+                    arrive[j] = Some((via, first));
+                }
+            }
+        }
+
+        let mut best = walk_len(&self.graph, curr, dest).map(|d| (d, None));
+        for (i, link) in self.links.iter().enumerate() {
+            let Some((cost, first)) = arrive[i] else {
+                continue;
+            };
+            let Some(walk) = walk_len(&self.graph, link.to, dest) else {
+                continue;
+            };
+            if best.map_or(true, |(c, _)| cost + walk < c) {
+                best = Some((cost + walk, Some(first)));
+            }
+        }
+
+        match best? {
+            (_, None) => self.graph.neighbor_to(curr, dest).map(Action::Move),
+            (_, Some(first)) if self.links[first].from == curr => Some(Action::UseLink(first)),
+            (_, Some(first)) => self
+                .graph
+                .neighbor_to(curr, self.links[first].from)
+                .map(Action::Move),
+        }
+    }
+}
+
+/// Return the number of steps of the walking path from `a` to `b`, or `None` if there is none.
+fn walk_len<NodeId: U16orU32>(graph: &Graph<NodeId>, a: NodeId, b: NodeId) -> Option<u32> {
+    // next hops toward an unreachable node are not meaningful,
+    // so only a path that actually arrives at `b` counts
+    let mut last = a;
+    let mut len = 0;
+    for node in graph.path_to(a, b).skip(1).take(graph.nodes_len()) {
+        last = node;
+        len += 1;
+    }
+
+    if last == b {
+        Some(len)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Follow the actions from `curr` to `dest`, and return the total cost.
+    fn travel(
+        planner: &Multimodal,
+        mut curr: u16,
+        dest: u16,
+        active: impl Fn(usize) -> bool,
+    ) -> Option<u32> {
+        let mut cost = 0;
+        while curr != dest {
+            match planner.next_action_with(curr, dest, &active)? {
+                Action::Move(next) => {
+                    assert!(planner.graph().neighbors(curr).contains(&next));
+                    curr = next;
+                    cost += 1;
+                }
+                Action::UseLink(i) => {
+                    let link = planner.links()[i];
+                    assert_eq!(link.from, curr);
+                    assert!(active(i));
+                    curr = link.to;
+                    cost += link.cost;
+                }
+            }
+        }
+        Some(cost)
+    }
+
+    #[test]
+    fn test_next_action() {
+        // two corridors 0..10 and 10..20, joined only by links
+        let mut builder = Graph::<u16>::builder(20);
+        for i in (0..9).chain(10..19) {
+            builder.connect(i, i + 1);
+        }
+        let links = vec![
+            // 0: a slow zip-line from the start of the first corridor to the start of the second
+            Link {
+                from: 0,
+                to: 10,
+                cost: 5,
+            },
+            // 1, 2: a teleporter chain through the first corridor to the second
+            Link {
+                from: 2,
+                to: 7,
+                cost: 1,
+            },
+            Link {
+                from: 8,
+                to: 15,
+                cost: 1,
+            },
+            // 3: a teleporter back
+            Link {
+                from: 19,
+                to: 9,
+                cost: 1,
+            },
+        ];
+        let planner = Multimodal::new(builder.build(), links);
+
+        // walking only
+        assert_eq!(travel(&planner, 0, 9, |_| false), Some(9));
+        assert_eq!(travel(&planner, 0, 19, |_| false), None);
+        assert_eq!(planner.next_action_with(0, 19, |_| false), None);
+        assert_eq!(planner.next_action(4, 4), None);
+
+        // 0 -> 2, teleport to 7, 7 -> 8, teleport to 15, 15 -> 19
+        assert_eq!(planner.next_action(0, 19), Some(Action::Move(1)));
+        assert_eq!(planner.next_action(2, 19), Some(Action::UseLink(1)));
+        assert_eq!(travel(&planner, 0, 19, |_| true), Some(2 + 1 + 1 + 1 + 4));
+
+        // without the teleporters, the zip-line is the only way over
+        assert_eq!(
+            planner.next_action_with(2, 19, |i| i == 0),
+            Some(Action::Move(1))
+        );
+        assert_eq!(travel(&planner, 2, 19, |i| i == 0), Some(2 + 5 + 9));
+
+        // walking wins ties: from 7, walking to 8 costs the same as the link
+        let tie = Multimodal::new(
+            planner.graph().clone(),
+            vec![Link {
+                from: 7,
+                to: 8,
+                cost: 1,
+            }],
+        );
+        assert_eq!(tie.next_action(7, 8), Some(Action::Move(8)));
+        assert_eq!(tie.next_action(7, 9), Some(Action::Move(8)));
+
+        // with every link, every node can reach every other node
+        for curr in 0..20 {
+            for dest in 0..20 {
+                assert!(travel(&planner, curr, dest, |_| true).is_some());
+            }
+        }
+    }
+}
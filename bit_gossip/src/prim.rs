@@ -114,12 +114,17 @@ macro_rules! impl_prim {
                 /// like resizing nodes or adding/removing edges.
                 ///
                 /// Then you can build the graph again.
+                ///
+                /// No computed paths are carried over: the builder holds the edges as if they were just connected,
+                /// so the rebuilt graph is the same as one built from scratch with the same edges.
                 pub fn into_builder(self) -> [<Graph $num Builder>] {
-                    [<Graph $num Builder>] {
+                    let mut builder = [<Graph $num Builder>] {
                         nodes: self.nodes,
-                        edge_masks: [<Edges $num>] { inner: self.edges.iter().map(|(k, _)| (*k, 0)).collect() },
-                        edges: [<Edges $num>] { inner: self.edges },
-                    }
+                        edges: [<Edges $num>]::new(),
+                        edge_masks: [<Edges $num>]::new(),
+                    };
+                    builder.reset_computed();
+                    builder
                 }
 
                 /// Given a current node and a destination node,
@@ -139,6 +144,7 @@ macro_rules! impl_prim {
                 /// or [neighbors_to](Self::neighbors_to) to get all neighboring nodes.
                 #[inline]
                 pub fn neighbor_to(&self, curr: $node_id, dest: $node_id) -> Option<$node_id> {
+                    check_nodes!("neighbor_to", self.nodes_len(), curr, dest);
                     self.neighbors_to(curr, dest).next()
                 }
 
@@ -162,6 +168,7 @@ macro_rules! impl_prim {
                     dest: $node_id,
                     f: impl Fn($node_id) -> bool,
                 ) -> Option<$node_id> {
+                    check_nodes!("neighbor_to_with", self.nodes_len(), curr, dest);
                     self.neighbors_to(curr, dest).find(|&n| f(n))
                 }
 
@@ -171,6 +178,7 @@ macro_rules! impl_prim {
                 /// The nodes will be returned in the same order for the same inputs. However, the ordering of the nodes is not guaranteed.
                 #[inline]
                 pub fn neighbors_to(&self, curr: $node_id, dest: $node_id) -> [<NextNodesIter $num>]<'_> {
+                    check_nodes!("neighbors_to", self.nodes_len(), curr, dest);
                     [<NextNodesIter $num>] {
                         graph: self,
                         neighbors: self.nodes.neighbors(curr),
@@ -189,26 +197,56 @@ macro_rules! impl_prim {
                 /// If there is no path, the list will be empty.
                 #[inline]
                 pub fn path_to(&self, curr: $node_id, dest: $node_id) -> [<PathIter $num>]<'_> {
+                    check_nodes!("path_to", self.nodes_len(), curr, dest);
                     [<PathIter $num>] {
                         map: self,
                         curr,
                         dest,
+                        mask: 1 << dest,
+                        prev: None,
                         init: false,
                     }
                 }
 
+                /// Given a current node and a source node,
+                /// return the node right before `curr` on a shortest path from `src` to `curr`.
+                ///
+                /// The graph is undirected, so this is the neighbor of `curr` that is one step closer to `src`;
+                /// calling it repeatedly retraces the way back to `src`.
+                ///
+                /// `None` is returned when:
+                /// - `curr` and `src` are the same node
+                /// - there is no path from `src` to `curr`
+                #[inline]
+                pub fn prev_node(&self, curr: $node_id, src: $node_id) -> Option<$node_id> {
+                    check_nodes!("prev_node", self.nodes_len(), curr, src);
+                    self.neighbor_to(curr, src)
+                }
+
                 /// Check if there is a path from the current node to the destination node.
                 #[inline]
                 pub fn path_exists(&self, curr: $node_id, dest: $node_id) -> bool {
+                    check_nodes!("path_exists", self.nodes_len(), curr, dest);
                     self.neighbor_to(curr, dest).is_some()
                 }
 
                 /// Return a list of all neighboring nodes of the given node.
                 #[inline]
                 pub fn neighbors(&self, node: $node_id) -> impl Iterator<Item = $node_id> + '_  {
+                    check_nodes!("neighbors", self.nodes_len(), node);
                     self.nodes.neighbors(node)
                 }
 
+                /// Return a hash of this graph's topology: the number of nodes and the set of edges.
+                ///
+                /// The hash does not depend on the order edges were added in or on the internal layout,
+                /// so it can be used as a cache key to check whether a map has changed since it was last built.
+                /// The same topology gives the same hash for every graph type and builder.
+                #[inline]
+                pub fn topology_hash(&self) -> u64 {
+                    self.nodes.topology_hash()
+                }
+
                 /// Return the number of nodes in this graph.
                 #[inline]
                 pub fn nodes_len(&self) -> usize {
@@ -223,11 +261,16 @@ macro_rules! impl_prim {
             }
 
             /// Iterator that returns a path from the current node to the destination node.
+            ///
+            /// The destination's bit mask is computed once for the whole path,
+            /// and the edge back to the previous node is skipped at each step.
             #[derive(Debug)]
             pub struct [<PathIter $num>]<'a> {
                 map: &'a [<Graph $num>],
                 curr: $node_id,
                 dest: $node_id,
+                mask: $node_bits,
+                prev: Option<$node_id>,
                 init: bool,
             }
 
@@ -240,13 +283,28 @@ macro_rules! impl_prim {
                         return Some(self.curr);
                     }
 
-                    let Some(next) = self.map.neighbor_to(self.curr, self.dest) else {
+                    if self.curr == self.dest {
                         return None;
-                    };
+                    }
 
-                    self.curr = next;
+                    let curr = self.curr;
+                    let mut neighbors = self.map.nodes.neighbors(curr);
+                    if let Some(prev) = self.prev {
+                        neighbors = neighbors.without(prev);
+                    }
+
+                    for neighbor in neighbors {
+                        let bit = self.map.edges.get(&edge_id(curr, neighbor))? & self.mask > 0;
+                        let bit = if curr > neighbor { !bit } else { bit };
 
-                    Some(next)
+                        if bit {
+                            self.prev = Some(curr);
+                            self.curr = neighbor;
+                            return Some(neighbor);
+                        }
+                    }
+
+                    None
                 }
             }
 
@@ -354,10 +412,28 @@ macro_rules! impl_prim {
                     self.edge_masks.insert(ab, a_bit | b_bit);
                 }
 
+                /// Forget all computed paths, so the next build computes every path from scratch.
+                ///
+                /// Every edge of `nodes` is reset to the state of a freshly connected edge,
+                /// and entries of `edges` and `edge_masks` that are not edges of `nodes` are dropped.
+                #[doc = "[Graph" $num "::into_builder] calls this, so rebuilding after edits gives the same paths"]
+                /// as building the edited graph from scratch.
+                pub fn reset_computed(&mut self) {
+                    self.edges = [<Edges $num>]::new();
+                    self.edge_masks = [<Edges $num>]::new();
+
+                    for (a, neighbors) in &self.nodes {
+                        for b in neighbors.filter(|&b| a < b) {
+                            self.edges.insert((a, b), 1 << b);
+                            self.edge_masks.insert((a, b), (1 << a) | (1 << b));
+                        }
+                    }
+                }
+
                 /// Remove edge between node_a and node_b
                 pub fn disconnect(&mut self, a: $node_id, b: $node_id) {
                     // if the edge doesn't exist, return
-                    if self.nodes.disconnect(a, b) {
+                    if !self.nodes.disconnect(a, b) {
                         return;
                     }
 
@@ -368,11 +444,52 @@ macro_rules! impl_prim {
                     }
                 }
 
+                /// Repair the builder before a build, so edits made through the public fields
+                /// cannot stall the gossip.
+                ///
+                /// The neighbor bits are canonicalized, entries of `edges` and `edge_masks` whose key
+                /// is not an ascending edge of `nodes` are dropped,
+                /// and edges missing from either map are reset to a freshly connected edge.
+                fn canonicalize(&mut self) {
+                    self.nodes.canonicalize();
+
+                    let nodes = &self.nodes;
+                    let is_edge = |&(a, b): &($node_id, $node_id)| {
+                        a < b && (b as usize) < nodes.len() && nodes.inner[a as usize] & (1 << b) > 0
+                    };
+
+                    self.edges.inner.retain(|ab, _| is_edge(ab));
+                    self.edge_masks.inner.retain(|ab, _| is_edge(ab));
+
+                    for (a, neighbors) in &self.nodes {
+                        for b in neighbors.filter(|&b| a < b) {
+                            let ab = (a, b);
+
+                            if !self.edges.inner.contains_key(&ab) || !self.edge_masks.inner.contains_key(&ab) {
+                                self.edges.inner.insert(ab, 1 << b);
+                                self.edge_masks.inner.insert(ab, (1 << a) | (1 << b));
+                            }
+                        }
+                    }
+                }
+
+                /// Return a hash of this graph's topology: the number of nodes and the set of edges.
+                ///
+                /// The hash does not depend on the order edges were added in or on the internal layout,
+                /// so it can be used as a cache key to check whether a map has changed since it was last built.
+                /// The same topology gives the same hash for every graph type and builder.
+                #[inline]
+                pub fn topology_hash(&self) -> u64 {
+                    self.nodes.topology_hash()
+                }
+
                 /// Build the graph.
                 ///
                 /// Consumes the builder, processes all shortest paths for all nodes,
                 #[doc = "and returns [Graph" $num "]."]
-                pub fn build(self) -> [< Graph $num >] {
+                pub fn build(mut self) -> [< Graph $num >] {
+                    self.canonicalize();
+
                     let Self {
                         nodes,
                         mut edges,
@@ -597,6 +714,20 @@ macro_rules! impl_prim {
                         edges: edges.inner,
                     }
                 }
+
+                /// Build many graphs at once, spreading the builders across all available threads.
+                ///
+                #[doc = "A single graph of at most " $num " nodes builds in microseconds, which is too little work to split up across threads;"]
+                /// so instead of parallelizing each build, the builders themselves are built in parallel.
+                /// This is useful for bulk generation, like procedurally generating thousands of arenas.
+                ///
+                /// The graphs are returned in the same order as the given builders.
+                #[cfg(feature = "parallel")]
+                pub fn build_batch_parallel(builders: Vec<Self>) -> Vec<[< Graph $num >]> {
+                    use rayon::prelude::*;
+
+                    builders.into_par_iter().map(Self::build).collect()
+                }
             }
 
             /// Map of nodes and their neighbors.
@@ -663,6 +794,34 @@ macro_rules! impl_prim {
                 pub fn resize(&mut self, new_len: usize) {
                     self.inner.resize(new_len, 0);
                 }
+
+                /// Repair the neighbor bits after they were edited by hand.
+                ///
+                /// Self-loop and out-of-range bits are cleared,
+                /// and an edge set on only one of its nodes is set on the other.
+                pub fn canonicalize(&mut self) {
+                    let len = self.len();
+                    let in_range: $node_bits = if len >= $num { !0 } else { (1 << len) - 1 };
+
+                    for (a, neighbors) in self.inner.iter_mut().enumerate() {
+                        *neighbors &= in_range & !(1 << a);
+                    }
+
+                    for a in 0..len {
+                        for b in [<node_bits_ $num _iter>](self.inner[a]) {
+                            self.inner[b as usize] |= 1 << a;
+                        }
+                    }
+                }
+
+                /// Return a hash of the number of nodes and the set of edges.
+                pub fn topology_hash(&self) -> u64 {
+                    let edges = self.into_iter().flat_map(|(a, neighbors)| {
+                        neighbors.map(move |b| (a as usize, b as usize))
+                    });
+
+                    crate::topology_hash(self.len(), edges)
+                }
             }
 
             /// Map of edges and bits indicating if the edge is the shortest path to the node.
@@ -937,4 +1096,40 @@ mod tests {
         let _graph = builder.build();
         println!("Time: {:?}", now.elapsed());
     }
+
+    #[cfg(any(debug_assertions, feature = "strict-checks"))]
+    #[test]
+    #[should_panic(expected = "path_to: `curr` 16 is out of range for a graph of 16 nodes")]
+    fn test_check_nodes() {
+        let mut builder = Graph16Builder::new(16);
+        builder.connect(0, 1);
+        let graph = builder.build();
+
+        graph.path_to(16, 0);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_build_batch_parallel() {
+        // arenas of increasing size: a line of `len` nodes
+        let arena = |len: usize| {
+            let mut builder = Graph128Builder::new(len);
+            for i in 1..len {
+                builder.connect((i - 1) as u8, i as u8);
+            }
+            builder
+        };
+
+        let builders = (2..100).map(arena).collect::<Vec<_>>();
+        let graphs = Graph128Builder::build_batch_parallel(builders);
+
+        assert_eq!(graphs.len(), 98);
+        for (len, graph) in (2..100).zip(&graphs) {
+            let expected = arena(len).build();
+
+            assert_eq!(graph.nodes_len(), len);
+            assert_eq!(graph.neighbor_to(0, len as u8 - 1), Some(1));
+            assert_eq!(graph.topology_hash(), expected.topology_hash());
+        }
+    }
 }
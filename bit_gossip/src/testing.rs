@@ -0,0 +1,377 @@
+//! Stress tests for hot-swapped graphs: build new graphs and swap them in while readers query the old ones.
+//!
+//! [stress_swap] runs the test on an [ArcGraph]; [stress_swap_with] runs it on any handle
+//! that implements [SwapHandle], so engine integrations can check their own wrappers.
+//!
+//! One thread builds a graph for each generation with the given factory, and swaps it in;
+//! meanwhile, every reader thread repeatedly takes a snapshot and queries it. The test panics if:
+//!
+//! - a reader panics; the reader's panic is resumed on the calling thread.
+//! - a snapshot's answers don't match the answers of the graph it was taken from,
+//!   or its topology changes while it is held (a torn read).
+//! - a reader takes a snapshot of an older generation than one it has already seen.
+//! - a graph that was swapped out is still alive after the test, once all snapshots are dropped
+//!   (a stale-generation leak).
+//!
+//! ```
+//! use bit_gossip::{testing::{stress_swap, QueryLoad}, Graph};
+//!
+//! // a corridor of 16 nodes, with a shortcut that moves every generation
+//! let factory = |generation: usize| {
+//!     let mut builder = Graph::builder(16);
+//!     for a in 0..15u16 {
+//!         builder.connect(a, a + 1);
+//!     }
+//!     builder.connect(0, 2 + generation as u16);
+//!     builder.build()
+//! };
+//!
+//! let load = QueryLoad::new(4, vec![(0u16, 15), (15, 0), (3, 12)]);
+//! let report = stress_swap(factory, &load, 10);
+//!
+//! assert_eq!(report.generations, 11);
+//! assert!(report.snapshots >= 4);
+//! ```
+
+use crate::graph::{
+    hot_swap::{ArcGraph, NeighborsGuard},
+    Graph, U16orU32,
+};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    ops::Deref,
+    panic,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    thread,
+};
+
+/// A handle to a graph that can be replaced while other threads are reading it,
+/// like [ArcGraph].
+pub trait SwapHandle<NodeId: U16orU32>: Sync {
+    /// A pinned graph, that stays the same when a new graph is swapped in.
+    type Snapshot: Deref<Target = Graph<NodeId>>;
+
+    /// Pin the current graph.
+    fn snapshot(&self) -> Self::Snapshot;
+
+    /// Replace the current graph with the given graph, and return the previous one.
+    fn swap(&self, graph: Graph<NodeId>) -> Arc<Graph<NodeId>>;
+}
+
+impl<NodeId: U16orU32> SwapHandle<NodeId> for ArcGraph<NodeId> {
+    type Snapshot = NeighborsGuard<NodeId>;
+
+    #[inline]
+    fn snapshot(&self) -> Self::Snapshot {
+        self.guard()
+    }
+
+    #[inline]
+    fn swap(&self, graph: Graph<NodeId>) -> Arc<Graph<NodeId>> {
+        ArcGraph::swap(self, graph)
+    }
+}
+
+/// The readers of a stress test, and the queries each of them runs on every snapshot.
+#[derive(Debug, Clone)]
+pub struct QueryLoad<NodeId: U16orU32 = u16> {
+    /// Number of reader threads.
+    pub readers: usize,
+    /// `(curr, dest)` pairs queried with `neighbor_to` on every snapshot;
+    /// they must be in range for the graph of every generation.
+    pub queries: Vec<(NodeId, NodeId)>,
+}
+
+impl<NodeId: U16orU32> QueryLoad<NodeId> {
+    /// Create a new load of `readers` threads running the given queries.
+    #[inline]
+    pub fn new(readers: usize, queries: Vec<(NodeId, NodeId)>) -> Self {
+        Self { readers, queries }
+    }
+}
+
+/// What a stress test did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StressReport {
+    /// Number of graphs built, including the first one.
+    pub generations: usize,
+    /// Number of snapshots taken by all readers.
+    pub snapshots: usize,
+    /// Number of queries run by all readers.
+    pub queries: usize,
+}
+
+/// Run a stress test on an [ArcGraph] holding `graph_factory(0)`,
+/// swapping in `graph_factory(1)` to `graph_factory(iterations)` while the readers of `query_load` query it.
+///
+/// Every graph returned by the factory must have a different topology.
+///
+/// See the [module](self) documentation for what is checked.
+pub fn stress_swap<NodeId: U16orU32>(
+    graph_factory: impl Fn(usize) -> Graph<NodeId> + Sync,
+    query_load: &QueryLoad<NodeId>,
+    iterations: usize,
+) -> StressReport {
+    let generations = Generations::new();
+
+    let first = graph_factory(0);
+    generations.register(0, &first, &query_load.queries);
+    let handle = ArcGraph::new(first);
+
+    run(&handle, &generations, graph_factory, query_load, iterations)
+}
+
+/// Run a stress test on the given handle: swap in `graph_factory(0)`, then swap in
+/// `graph_factory(1)` to `graph_factory(iterations)` while the readers of `query_load` query it.
+///
+/// Every graph returned by the factory must have a different topology.
+///
+/// See the [module](self) documentation for what is checked.
+pub fn stress_swap_with<NodeId: U16orU32, H: SwapHandle<NodeId>>(
+    handle: &H,
+    graph_factory: impl Fn(usize) -> Graph<NodeId> + Sync,
+    query_load: &QueryLoad<NodeId>,
+    iterations: usize,
+) -> StressReport {
+    let generations = Generations::new();
+
+    let first = graph_factory(0);
+    generations.register(0, &first, &query_load.queries);
+    handle.swap(first);
+
+    run(handle, &generations, graph_factory, query_load, iterations)
+}
+
+/// Swap in the graphs of generations 1 to `iterations` while the readers query the handle,
+/// which already holds the graph of generation 0.
+fn run<NodeId: U16orU32, H: SwapHandle<NodeId>>(
+    handle: &H,
+    generations: &Generations<NodeId>,
+    graph_factory: impl Fn(usize) -> Graph<NodeId> + Sync,
+    query_load: &QueryLoad<NodeId>,
+    iterations: usize,
+) -> StressReport {
+    // the graph of each generation, once it is swapped out
+    let mut swapped_out = Vec::with_capacity(iterations);
+
+    let done = AtomicBool::new(false);
+    let snapshots = AtomicUsize::new(0);
+    let queries = AtomicUsize::new(0);
+
+    thread::scope(|s| {
+        let readers: Vec<_> = (0..query_load.readers)
+            .map(|_| {
+                s.spawn(|| {
+                    let mut last_seen = 0;
+
+                    loop {
+                        let stopping = done.load(Ordering::Acquire);
+
+                        let snapshot = handle.snapshot();
+                        last_seen = generations.check(&snapshot, &query_load.queries, last_seen);
+
+                        snapshots.fetch_add(1, Ordering::Relaxed);
+                        queries.fetch_add(query_load.queries.len(), Ordering::Relaxed);
+
+                        if stopping {
+                            break;
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        let swapper = s.spawn(|| {
+            let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+                for generation in 1..=iterations {
+                    let graph = graph_factory(generation);
+                    generations.register(generation, &graph, &query_load.queries);
+                    swapped_out.push(Arc::downgrade(&handle.swap(graph)));
+                }
+            }));
+            done.store(true, Ordering::Release);
+
+            if let Err(payload) = result {
+                panic::resume_unwind(payload);
+            }
+        });
+
+        for thread in readers.into_iter().chain([swapper]) {
+            if let Err(payload) = thread.join() {
+                panic::resume_unwind(payload);
+            }
+        }
+    });
+
+    for (generation, graph) in swapped_out.iter().enumerate() {
+        assert!(
+            graph.upgrade().is_none(),
+            "graph of generation {} is still alive after generation {} was swapped in",
+            generation,
+            iterations
+        );
+    }
+
+    StressReport {
+        generations: iterations + 1,
+        snapshots: snapshots.into_inner(),
+        queries: queries.into_inner(),
+    }
+}
+
+/// The generation of a graph, and its answers to the queries.
+type Expected<NodeId> = (usize, Arc<[Option<NodeId>]>);
+
+/// The generation and the expected answers of every graph built so far, keyed by topology hash.
+struct Generations<NodeId> {
+    by_hash: Mutex<HashMap<u64, Expected<NodeId>>>,
+}
+
+impl<NodeId: U16orU32> Generations<NodeId> {
+    fn new() -> Self {
+        Self {
+            by_hash: Mutex::default(),
+        }
+    }
+
+    /// Record the answers of a graph before it is swapped in.
+    fn register(&self, generation: usize, graph: &Graph<NodeId>, queries: &[(NodeId, NodeId)]) {
+        let answers = answers(graph, queries).into();
+        let mut by_hash = self.by_hash.lock().unwrap_or_else(PoisonError::into_inner);
+
+        match by_hash.entry(graph.topology_hash()) {
+            Entry::Occupied(entry) => panic!(
+                "graph factory returned the same topology for generations {} and {}",
+                entry.get().0,
+                generation
+            ),
+            Entry::Vacant(entry) => {
+                entry.insert((generation, answers));
+            }
+        }
+    }
+
+    /// Check a snapshot against the graph it was taken from, and return its generation.
+    fn check(
+        &self,
+        graph: &Graph<NodeId>,
+        queries: &[(NodeId, NodeId)],
+        last_seen: usize,
+    ) -> usize {
+        let hash = graph.topology_hash();
+        let (generation, expected) = self
+            .by_hash
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&hash)
+            .cloned()
+            .expect("snapshot is of a graph that was never swapped in");
+
+        assert!(
+            generation >= last_seen,
+            "snapshot of generation {} taken after a snapshot of generation {}",
+            generation,
+            last_seen
+        );
+
+        let answers = answers(graph, queries);
+        for (i, (&query, (answer, expected))) in queries
+            .iter()
+            .zip(answers.iter().zip(&*expected))
+            .enumerate()
+        {
+            assert_eq!(
+                answer, expected,
+                "torn read in generation {}: query #{} {:?}",
+                generation, i, query
+            );
+        }
+
+        assert_eq!(
+            graph.topology_hash(),
+            hash,
+            "torn read in generation {}: topology changed while the snapshot was held",
+            generation
+        );
+
+        generation
+    }
+}
+
+fn answers<NodeId: U16orU32>(
+    graph: &Graph<NodeId>,
+    queries: &[(NodeId, NodeId)],
+) -> Vec<Option<NodeId>> {
+    queries
+        .iter()
+        .map(|&(curr, dest)| graph.neighbor_to(curr, dest))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corridor(nodes_len: u16, shortcut: u16, multi_threaded: bool) -> Graph {
+        let builder = Graph::builder(nodes_len as usize);
+
+        #[cfg(feature = "parallel")]
+        let builder = builder.multi_threaded(multi_threaded);
+        #[cfg(not(feature = "parallel"))]
+        let _ = multi_threaded;
+
+        let mut builder = builder;
+        for a in 0..nodes_len - 1 {
+            builder.connect(a, a + 1);
+        }
+        builder.connect(0, shortcut);
+        builder.build()
+    }
+
+    #[test]
+    fn test_stress_swap() {
+        let queries = (0..32u16).flat_map(|a| [(a, 31 - a), (a, 0)]).collect();
+        let load = QueryLoad::new(4, queries);
+
+        let report = stress_swap(|i| corridor(32, 2 + i as u16, i % 2 == 1), &load, 20);
+
+        assert_eq!(report.generations, 21);
+        assert!(report.snapshots >= load.readers);
+        assert_eq!(report.queries, report.snapshots * load.queries.len());
+    }
+
+    #[test]
+    #[should_panic(expected = "same topology")]
+    fn test_stress_swap_repeated_topology() {
+        let load = QueryLoad::new(1, vec![(0u16, 7)]);
+        stress_swap(|i| corridor(8, 2 + i as u16 % 2, false), &load, 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "still alive")]
+    fn test_stress_swap_leak() {
+        /// A handle that keeps every graph it swaps out.
+        struct Leaky(ArcGraph, Mutex<Vec<Arc<Graph>>>);
+
+        impl SwapHandle<u16> for Leaky {
+            type Snapshot = NeighborsGuard;
+
+            fn snapshot(&self) -> Self::Snapshot {
+                self.0.guard()
+            }
+
+            fn swap(&self, graph: Graph) -> Arc<Graph> {
+                let previous = self.0.swap(graph);
+                self.1.lock().unwrap().push(previous.clone());
+                previous
+            }
+        }
+
+        let handle = Leaky(ArcGraph::new(corridor(8, 7, false)), Mutex::default());
+        let load = QueryLoad::new(2, vec![(0u16, 7)]);
+        stress_swap_with(&handle, |i| corridor(8, 2 + i as u16, false), &load, 3);
+    }
+}
@@ -0,0 +1,120 @@
+//! Shared helpers for the integration tests.
+//!
+//! These mirror what the example crates do with the library, without depending on Bevy.
+
+#![allow(dead_code)]
+
+use bit_gossip::{maze::build_maze_from_seed, Graph};
+use std::collections::VecDeque;
+
+/// Build a maze of the given size from a seed, like the examples' `MazePlugin` does.
+pub fn maze(width: u16, height: u16, seed: u8) -> Vec<(u16, u16)> {
+    build_maze_from_seed(width, height, [seed; 32])
+}
+
+/// Every passage of a full `width x height` grid.
+pub fn grid(width: u16, height: u16) -> Vec<(u16, u16)> {
+    let mut edges = Vec::new();
+
+    for y in 0..height {
+        for x in 0..width {
+            let node = y * width + x;
+
+            if x + 1 < width {
+                edges.push((node, node + 1));
+            }
+            if y + 1 < height {
+                edges.push((node, node + width));
+            }
+        }
+    }
+
+    edges
+}
+
+/// Adjacency lists of the given edges, like the examples' `Neighbors` resource.
+pub fn neighbors(nodes_len: usize, edges: &[(u16, u16)]) -> Vec<Vec<u16>> {
+    let mut neighbors = vec![Vec::new(); nodes_len];
+
+    for &(a, b) in edges {
+        neighbors[a as usize].push(b);
+        neighbors[b as usize].push(a);
+    }
+
+    neighbors
+}
+
+/// Distances from `src` to every node, or `None` if unreachable.
+pub fn bfs(neighbors: &[Vec<u16>], src: u16) -> Vec<Option<u32>> {
+    let mut dist = vec![None; neighbors.len()];
+    let mut queue = VecDeque::new();
+
+    dist[src as usize] = Some(0);
+    queue.push_back(src);
+
+    while let Some(a) = queue.pop_front() {
+        let d = dist[a as usize].unwrap();
+
+        for &b in &neighbors[a as usize] {
+            if dist[b as usize].is_none() {
+                dist[b as usize] = Some(d + 1);
+                queue.push_back(b);
+            }
+        }
+    }
+
+    dist
+}
+
+/// Build a graph with the chosen backend.
+pub fn build(nodes_len: usize, edges: &[(u16, u16)], multi_threaded: bool) -> Graph {
+    let builder = Graph::builder(nodes_len);
+
+    #[cfg(feature = "parallel")]
+    let builder = builder.multi_threaded(multi_threaded);
+    #[cfg(not(feature = "parallel"))]
+    let _ = multi_threaded;
+
+    let mut builder = builder;
+    for &(a, b) in edges {
+        builder.connect(a, b);
+    }
+
+    builder.build()
+}
+
+/// Assert that every next hop and path of the graph is a shortest path,
+/// checking against a BFS over the given edges.
+pub fn assert_shortest_paths(graph: &Graph, nodes_len: usize, edges: &[(u16, u16)]) {
+    let neighbors = neighbors(nodes_len, edges);
+
+    for dest in 0..nodes_len as u16 {
+        let dist = bfs(&neighbors, dest);
+
+        for curr in 0..nodes_len as u16 {
+            let Some(d) = dist[curr as usize] else {
+                continue;
+            };
+
+            let mut expected = neighbors[curr as usize]
+                .iter()
+                .copied()
+                .filter(|&n| d > 0 && dist[n as usize] == Some(d - 1))
+                .collect::<Vec<_>>();
+            expected.sort();
+
+            let mut actual = graph.neighbors_to(curr, dest).collect::<Vec<_>>();
+            actual.sort();
+
+            assert_eq!(actual, expected, "next hops from {curr} to {dest}");
+
+            // next hops are checked for every pair; walking whole paths is only sampled
+            if curr != dest && (curr as usize + dest as usize) % 7 == 0 {
+                let path = graph.path_to(curr, dest).collect::<Vec<_>>();
+                assert_eq!(path.len() as u32, d + 1, "path from {curr} to {dest}");
+                assert_eq!(path.first(), Some(&curr));
+                assert_eq!(path.last(), Some(&dest));
+            }
+        }
+    }
+}
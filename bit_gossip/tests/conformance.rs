@@ -0,0 +1,356 @@
+//! Conformance suite for the graph implementations.
+//!
+//! The prim graphs, [SeqGraph] and [ParaGraph] are built from identical topologies,
+//! and must return identical next-hop sets for every pair of nodes.
+//! Most topologies here are tie-heavy (odd cycles, cliques, random graphs),
+//! where the implementations are the most likely to drift apart.
+
+mod common;
+
+#[cfg(feature = "parallel")]
+use bit_gossip::graph::parallel::ParaGraph;
+use bit_gossip::{graph::sequential::SeqGraph, Graph128, Graph16, Graph64};
+use common::{grid, maze};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::collections::BTreeSet;
+
+/// Build a graph with the given builder and collect the sorted next hops
+/// for every `(curr, dest)` pair, indexed by `curr * nodes_len + dest`.
+macro_rules! next_hops {
+    ($builder:expr, $node_id:ty, $nodes_len:expr, $edges:expr) => {{
+        let mut builder = $builder;
+        for &(a, b) in $edges {
+            builder.connect(a as $node_id, b as $node_id);
+        }
+        let graph = builder.build();
+
+        let mut hops = Vec::with_capacity($nodes_len * $nodes_len);
+        for curr in 0..$nodes_len {
+            for dest in 0..$nodes_len {
+                let mut next = graph
+                    .neighbors_to(curr as $node_id, dest as $node_id)
+                    .map(|n| n as u16)
+                    .collect::<Vec<_>>();
+                next.sort();
+                hops.push(next);
+            }
+        }
+        hops
+    }};
+}
+
+/// Build a graph with the given builder from `edges`, then convert it back into a builder,
+/// apply the edits, rebuild it, and collect the next hops like [next_hops].
+macro_rules! rebuilt_hops {
+    ($builder:expr, $node_id:ty, $nodes_len:expr, $edges:expr, $edits:expr) => {{
+        let mut builder = $builder;
+        for &(a, b) in $edges {
+            builder.connect(a as $node_id, b as $node_id);
+        }
+        let mut builder = builder.build().into_builder();
+        for &(connect, a, b) in $edits {
+            if connect {
+                builder.connect(a as $node_id, b as $node_id);
+            } else {
+                builder.disconnect(a as $node_id, b as $node_id);
+            }
+        }
+        let graph = builder.build();
+
+        let mut hops = Vec::with_capacity($nodes_len * $nodes_len);
+        for curr in 0..$nodes_len {
+            for dest in 0..$nodes_len {
+                let mut next = graph
+                    .neighbors_to(curr as $node_id, dest as $node_id)
+                    .map(|n| n as u16)
+                    .collect::<Vec<_>>();
+                next.sort();
+                hops.push(next);
+            }
+        }
+        hops
+    }};
+}
+
+fn assert_same_hops(name: &str, nodes_len: usize, expected: &[Vec<u16>], actual: &[Vec<u16>]) {
+    for (i, (e, a)) in expected.iter().zip(actual).enumerate() {
+        let (curr, dest) = (i / nodes_len, i % nodes_len);
+        assert_eq!(a, e, "{name}: next hops from {curr} to {dest}");
+    }
+}
+
+/// Run every implementation that can hold `nodes_len` nodes,
+/// and compare them all against `SeqGraph`.
+fn assert_conformance(nodes_len: usize, edges: &[(u16, u16)]) {
+    let expected = next_hops!(SeqGraph::<u16>::builder(nodes_len), u16, nodes_len, edges);
+
+    #[cfg(feature = "parallel")]
+    {
+        let hops = next_hops!(ParaGraph::<u16>::builder(nodes_len), u16, nodes_len, edges);
+        assert_same_hops("ParaGraph", nodes_len, &expected, &hops);
+
+        let builder = SeqGraph::<u16>::builder(nodes_len).parallel_setup(true);
+        let hops = next_hops!(builder, u16, nodes_len, edges);
+        assert_same_hops("SeqGraph (parallel setup)", nodes_len, &expected, &hops);
+
+        let builder = ParaGraph::<u16>::builder(nodes_len).stable_order(true);
+        let hops = next_hops!(builder, u16, nodes_len, edges);
+        assert_same_hops("ParaGraph (stable order)", nodes_len, &expected, &hops);
+    }
+
+    if nodes_len <= 16 {
+        let hops = next_hops!(Graph16::builder(nodes_len), u8, nodes_len, edges);
+        assert_same_hops("Graph16", nodes_len, &expected, &hops);
+    }
+    if nodes_len <= 64 {
+        let hops = next_hops!(Graph64::builder(nodes_len), u8, nodes_len, edges);
+        assert_same_hops("Graph64", nodes_len, &expected, &hops);
+    }
+    if nodes_len <= 128 {
+        let hops = next_hops!(Graph128::builder(nodes_len), u8, nodes_len, edges);
+        assert_same_hops("Graph128", nodes_len, &expected, &hops);
+    }
+}
+
+/// Edit a built graph of every implementation that can hold `nodes_len` nodes,
+/// and compare the rebuilt graphs against `SeqGraph` built from scratch with the edited edges.
+///
+/// Each edit is `(connect, a, b)`: connect the edge if `connect` is true, or disconnect it.
+fn assert_rebuild_conformance(nodes_len: usize, edges: &[(u16, u16)], edits: &[(bool, u16, u16)]) {
+    let mut edited = edges
+        .iter()
+        .map(|&(a, b)| bit_gossip::edge_id(a, b))
+        .collect::<BTreeSet<_>>();
+    for &(connect, a, b) in edits {
+        if connect {
+            edited.insert(bit_gossip::edge_id(a, b));
+        } else {
+            edited.remove(&bit_gossip::edge_id(a, b));
+        }
+    }
+    let edited = edited.into_iter().collect::<Vec<_>>();
+    let expected = next_hops!(SeqGraph::<u16>::builder(nodes_len), u16, nodes_len, &edited);
+
+    let hops = rebuilt_hops!(
+        SeqGraph::<u16>::builder(nodes_len),
+        u16,
+        nodes_len,
+        edges,
+        edits
+    );
+    assert_same_hops("SeqGraph (rebuilt)", nodes_len, &expected, &hops);
+
+    #[cfg(feature = "parallel")]
+    {
+        let hops = rebuilt_hops!(
+            ParaGraph::<u16>::builder(nodes_len),
+            u16,
+            nodes_len,
+            edges,
+            edits
+        );
+        assert_same_hops("ParaGraph (rebuilt)", nodes_len, &expected, &hops);
+    }
+
+    if nodes_len <= 16 {
+        let hops = rebuilt_hops!(Graph16::builder(nodes_len), u8, nodes_len, edges, edits);
+        assert_same_hops("Graph16 (rebuilt)", nodes_len, &expected, &hops);
+    }
+    if nodes_len <= 64 {
+        let hops = rebuilt_hops!(Graph64::builder(nodes_len), u8, nodes_len, edges, edits);
+        assert_same_hops("Graph64 (rebuilt)", nodes_len, &expected, &hops);
+    }
+    if nodes_len <= 128 {
+        let hops = rebuilt_hops!(Graph128::builder(nodes_len), u8, nodes_len, edges, edits);
+        assert_same_hops("Graph128 (rebuilt)", nodes_len, &expected, &hops);
+    }
+}
+
+fn cycle(nodes_len: u16) -> Vec<(u16, u16)> {
+    (0..nodes_len).map(|i| (i, (i + 1) % nodes_len)).collect()
+}
+
+fn complete(nodes_len: u16) -> Vec<(u16, u16)> {
+    (0..nodes_len)
+        .flat_map(|a| (a + 1..nodes_len).map(move |b| (a, b)))
+        .collect()
+}
+
+/// A random graph with each possible edge present with probability `p`;
+/// it may have several components.
+fn random(nodes_len: u16, p: f64, seed: u64) -> Vec<(u16, u16)> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut edges = BTreeSet::new();
+
+    for a in 0..nodes_len {
+        for b in a + 1..nodes_len {
+            if rng.gen_bool(p) {
+                edges.insert((a, b));
+            }
+        }
+    }
+
+    edges.into_iter().collect()
+}
+
+#[test]
+fn grids() {
+    assert_conformance(16, &grid(4, 4));
+    assert_conformance(64, &grid(8, 8));
+    assert_conformance(128, &grid(8, 16));
+}
+
+#[test]
+fn odd_cycles() {
+    for len in [3, 5, 7, 15, 63, 127] {
+        assert_conformance(len as usize, &cycle(len));
+    }
+}
+
+#[test]
+fn cliques() {
+    assert_conformance(9, &complete(9));
+    assert_conformance(16, &complete(16));
+}
+
+#[test]
+fn wheel() {
+    // a hub connected to every node of a 15-cycle: every rim pair ties through the hub
+    let mut edges = cycle(15);
+    edges.extend((0..15).map(|i| (i, 15)));
+    assert_conformance(16, &edges);
+}
+
+#[test]
+fn mazes_with_loops() {
+    let mut edges = maze(8, 8, 3);
+    edges.extend([(0, 9), (18, 27), (36, 45), (54, 63)]);
+    let edges = edges
+        .into_iter()
+        .map(|(a, b)| bit_gossip::edge_id(a, b))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    assert_conformance(64, &edges);
+}
+
+#[test]
+fn random_graphs() {
+    for (seed, (nodes_len, p)) in [(16, 0.3), (16, 0.1), (64, 0.08), (100, 0.03), (128, 0.05)]
+        .into_iter()
+        .enumerate()
+    {
+        assert_conformance(nodes_len as usize, &random(nodes_len, p, seed as u64));
+    }
+}
+
+#[test]
+fn rebuild_after_edits() {
+    // open a shortcut through the middle of a grid
+    assert_rebuild_conformance(16, &grid(4, 4), &[(true, 0, 15)]);
+
+    // cut a grid in two, so some destinations become unreachable
+    let cut = (0..8).map(|x| (false, 24 + x, 32 + x)).collect::<Vec<_>>();
+    assert_rebuild_conformance(64, &grid(8, 8), &cut);
+
+    // turn an even cycle into an odd one, and back into a longer even one
+    assert_rebuild_conformance(
+        16,
+        &cycle(14),
+        &[
+            (false, 13, 0),
+            (true, 13, 14),
+            (true, 14, 0),
+            (true, 0, 15),
+            (true, 15, 7),
+        ],
+    );
+
+    // reconnect an edge that was already there, and remove one twice
+    assert_rebuild_conformance(
+        100,
+        &random(100, 0.03, 7),
+        &[
+            (true, 0, 1),
+            (true, 0, 1),
+            (false, 2, 3),
+            (false, 2, 3),
+            (true, 2, 3),
+        ],
+    );
+}
+
+/// Run many `into_builder` round trips with a few random edits each,
+/// and compare every rebuilt graph against one built from scratch with the same edges.
+macro_rules! assert_editing_session {
+    ($name:expr, $builder:expr, $node_id:ty, $nodes_len:expr, $edges:expr, $seed:expr) => {{
+        let nodes_len: usize = $nodes_len;
+        let mut rng = StdRng::seed_from_u64($seed);
+        let mut edges = $edges
+            .iter()
+            .map(|&(a, b)| bit_gossip::edge_id(a, b))
+            .collect::<BTreeSet<_>>();
+
+        let mut builder = $builder;
+        for &(a, b) in &edges {
+            builder.connect(a as $node_id, b as $node_id);
+        }
+        let mut graph = builder.build();
+
+        for round in 0..40 {
+            let mut builder = graph.into_builder();
+            for _ in 0..rng.gen_range(1..4) {
+                let a = rng.gen_range(0..nodes_len as u16);
+                let b = rng.gen_range(0..nodes_len as u16);
+                if rng.gen_bool(0.5) {
+                    builder.connect(a as $node_id, b as $node_id);
+                    if a != b {
+                        edges.insert(bit_gossip::edge_id(a, b));
+                    }
+                } else {
+                    builder.disconnect(a as $node_id, b as $node_id);
+                    edges.remove(&bit_gossip::edge_id(a, b));
+                }
+            }
+            graph = builder.build();
+
+            let edited = edges.iter().copied().collect::<Vec<_>>();
+            let expected = next_hops!(SeqGraph::<u16>::builder(nodes_len), u16, nodes_len, &edited);
+
+            for curr in 0..nodes_len {
+                for dest in 0..nodes_len {
+                    let mut next = graph
+                        .neighbors_to(curr as $node_id, dest as $node_id)
+                        .map(|n| n as u16)
+                        .collect::<Vec<_>>();
+                    next.sort();
+                    assert_eq!(
+                        next,
+                        expected[curr * nodes_len + dest],
+                        "{} round {round}: next hops from {curr} to {dest}",
+                        $name
+                    );
+                }
+            }
+        }
+    }};
+}
+
+#[test]
+fn long_editing_sessions() {
+    let edges = grid(6, 6);
+
+    assert_editing_session!("SeqGraph", SeqGraph::<u16>::builder(36), u16, 36, &edges, 1);
+
+    #[cfg(feature = "parallel")]
+    assert_editing_session!(
+        "ParaGraph",
+        ParaGraph::<u16>::builder(36),
+        u16,
+        36,
+        &edges,
+        2
+    );
+
+    assert_editing_session!("Graph64", Graph64::builder(36), u8, 36, &edges, 3);
+}
@@ -0,0 +1,76 @@
+//! The core logic of the `astar_maze` example, checking that the precomputed paths
+//! are as short as the ones A* finds on the same maze.
+
+mod common;
+
+use common::{build, maze, neighbors};
+use std::collections::{BinaryHeap, HashMap};
+
+const WIDTH: u16 = 20;
+const HEIGHT: u16 = 12;
+const SIZE: usize = (WIDTH * HEIGHT) as usize;
+
+fn manhattan(a: u16, b: u16) -> u32 {
+    let (ax, ay) = (a % WIDTH, a / WIDTH);
+    let (bx, by) = (b % WIDTH, b / WIDTH);
+
+    (ax.abs_diff(bx) + ay.abs_diff(by)) as u32
+}
+
+/// A* over the maze's neighbors, like the example's `astar_path`.
+fn astar(neighbors: &[Vec<u16>], start: u16, goal: u16) -> Option<Vec<u16>> {
+    let mut open = BinaryHeap::new();
+    let mut came_from = HashMap::new();
+    let mut cost = HashMap::new();
+
+    cost.insert(start, 0u32);
+    open.push(std::cmp::Reverse((manhattan(start, goal), start)));
+
+    while let Some(std::cmp::Reverse((_, node))) = open.pop() {
+        if node == goal {
+            let mut path = vec![goal];
+            while let Some(&prev) = came_from.get(path.last().unwrap()) {
+                path.push(prev);
+            }
+            path.reverse();
+            return Some(path);
+        }
+
+        let node_cost = cost[&node];
+
+        for &next in &neighbors[node as usize] {
+            let next_cost = node_cost + 1;
+
+            if cost.get(&next).map_or(true, |&c| next_cost < c) {
+                cost.insert(next, next_cost);
+                came_from.insert(next, node);
+                open.push(std::cmp::Reverse((next_cost + manhattan(next, goal), next)));
+            }
+        }
+    }
+
+    None
+}
+
+#[test]
+fn paths_match_astar() {
+    for multi_threaded in [false, true] {
+        let edges = maze(WIDTH, HEIGHT, 5);
+        let neighbors = neighbors(SIZE, &edges);
+        let graph = build(SIZE, &edges, multi_threaded);
+
+        for start in (0..SIZE as u16).step_by(7) {
+            for goal in (0..SIZE as u16).step_by(5) {
+                if start == goal {
+                    continue;
+                }
+
+                let expected = astar(&neighbors, start, goal).unwrap();
+                let actual = graph.path_to(start, goal).collect::<Vec<_>>();
+
+                // a maze is a tree, so the shortest path is unique
+                assert_eq!(actual, expected);
+            }
+        }
+    }
+}
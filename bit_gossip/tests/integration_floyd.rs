@@ -0,0 +1,86 @@
+//! The core logic of the `floyd_warshall_maze` example, checking the precomputed paths
+//! against all-pairs distances from Floyd-Warshall.
+//!
+//! A few extra passages are opened in the maze, so there are multiple shortest paths.
+
+mod common;
+
+use common::{build, maze};
+
+const WIDTH: u16 = 12;
+const HEIGHT: u16 = 12;
+const SIZE: usize = (WIDTH * HEIGHT) as usize;
+
+fn floyd_warshall(edges: &[(u16, u16)]) -> Vec<Vec<Option<u32>>> {
+    let mut dist = vec![vec![None; SIZE]; SIZE];
+
+    for (i, row) in dist.iter_mut().enumerate() {
+        row[i] = Some(0);
+    }
+    for &(a, b) in edges {
+        dist[a as usize][b as usize] = Some(1);
+        dist[b as usize][a as usize] = Some(1);
+    }
+
+    for k in 0..SIZE {
+        let row_k = dist[k].clone();
+
+        for row_i in dist.iter_mut() {
+            let Some(ik) = row_i[k] else {
+                continue;
+            };
+
+            for (ij, kj) in row_i.iter_mut().zip(&row_k) {
+                if let Some(kj) = kj {
+                    if ij.map_or(true, |ij| ik + kj < ij) {
+                        *ij = Some(ik + kj);
+                    }
+                }
+            }
+        }
+    }
+
+    dist
+}
+
+#[test]
+fn paths_match_floyd_warshall() {
+    let mut edges = maze(WIDTH, HEIGHT, 6);
+
+    // open a passage to the right of every 5th cell, creating loops
+    for node in (0..SIZE as u16).step_by(5) {
+        if node % WIDTH + 1 < WIDTH
+            && !edges.contains(&(node, node + 1))
+            && !edges.contains(&(node + 1, node))
+        {
+            edges.push((node, node + 1));
+        }
+    }
+
+    let dist = floyd_warshall(&edges);
+
+    for multi_threaded in [false, true] {
+        let graph = build(SIZE, &edges, multi_threaded);
+
+        for curr in 0..SIZE as u16 {
+            for dest in 0..SIZE as u16 {
+                let d = dist[curr as usize][dest as usize].unwrap();
+
+                for next in graph.neighbors_to(curr, dest) {
+                    assert_eq!(dist[next as usize][dest as usize], Some(d - 1));
+                }
+
+                let expected = graph
+                    .neighbors(curr)
+                    .iter()
+                    .filter(|&&n| d > 0 && dist[n as usize][dest as usize] == Some(d - 1))
+                    .count();
+                assert_eq!(graph.neighbors_to(curr, dest).count(), expected);
+
+                if curr != dest {
+                    assert_eq!(graph.path_to(curr, dest).count() as u32, d + 1);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,262 @@
+//! The core logic of the `maze` example: enemies chasing a player through a maze,
+//! re-querying the next tile whenever they arrive at a tile or the player moves.
+
+mod common;
+
+use bit_gossip::{
+    bitvec::{BitVec, Digit},
+    graph::{
+        sequential::SeqGraph,
+        store::{EdgeStore, HashMapStore},
+    },
+    Graph,
+};
+use common::{assert_shortest_paths, bfs, build, maze, neighbors};
+use std::sync::Arc;
+
+const WIDTH: u16 = 16;
+const HEIGHT: u16 = 16;
+const SIZE: usize = (WIDTH * HEIGHT) as usize;
+
+#[test]
+fn maze_paths_are_shortest() {
+    for multi_threaded in [false, true] {
+        let edges = maze(WIDTH, HEIGHT, 1);
+        let graph = build(SIZE, &edges, multi_threaded);
+
+        assert_eq!(graph.nodes_len(), SIZE);
+        assert_eq!(graph.edges_len(), edges.len());
+
+        assert_shortest_paths(&graph, SIZE, &edges);
+    }
+}
+
+#[test]
+fn enemies_catch_moving_player() {
+    let edges = maze(WIDTH, HEIGHT, 2);
+    let neighbors = neighbors(SIZE, &edges);
+    let graph = build(SIZE, &edges, false);
+
+    let mut player = 0u16;
+    let mut enemies = vec![SIZE as u16 - 1, WIDTH - 1, (HEIGHT - 1) * WIDTH];
+
+    let mut tick = 0usize;
+    while !enemies.is_empty() {
+        let dist = bfs(&neighbors, player);
+
+        // enemies move to the next tile toward the player
+        for enemy in enemies.iter_mut() {
+            let before = dist[*enemy as usize].unwrap();
+
+            *enemy = graph.neighbor_to(*enemy, player).unwrap();
+
+            assert_eq!(dist[*enemy as usize].unwrap() + 1, before);
+        }
+
+        // caught enemies are despawned
+        enemies.retain(|enemy| *enemy != player);
+
+        // player moves every other tick, so enemies can catch up
+        if tick % 2 == 0 {
+            let options = &neighbors[player as usize];
+            player = options[tick % options.len()];
+            enemies.retain(|enemy| *enemy != player);
+        }
+
+        tick += 1;
+        assert!(tick < 10 * SIZE, "enemies never caught the player");
+    }
+}
+
+#[test]
+fn rebuild_after_closing_passages() {
+    for multi_threaded in [false, true] {
+        let mut edges = maze(WIDTH, HEIGHT, 3);
+        let graph = build(SIZE, &edges, multi_threaded);
+
+        // rebuilding without changes gives the same paths
+        let graph = graph.into_builder().build();
+        assert_shortest_paths(&graph, SIZE, &edges);
+
+        // close a few passages, splitting the maze into separate regions
+        let mut builder = graph.into_builder();
+        for (a, b) in edges.drain(..3) {
+            builder.disconnect(a, b);
+        }
+        let graph = builder.build();
+
+        assert_eq!(graph.edges_len(), edges.len());
+        assert_shortest_paths(&graph, SIZE, &edges);
+    }
+}
+
+#[test]
+fn rebuild_after_opening_passages() {
+    for multi_threaded in [false, true] {
+        let mut edges = maze(WIDTH, HEIGHT, 6);
+        let graph = build(SIZE, &edges, multi_threaded);
+
+        // knock down walls to the right of every 5th tile, adding loops to the maze
+        let mut builder = graph.into_builder();
+        for node in (0..SIZE as u16).step_by(5) {
+            let right = node + 1;
+            if right % WIDTH != 0
+                && !edges.contains(&(node, right))
+                && !edges.contains(&(right, node))
+            {
+                builder.connect(node, right);
+                edges.push((node, right));
+            }
+        }
+        let graph = builder.build();
+
+        assert_eq!(graph.edges_len(), edges.len());
+        assert_shortest_paths(&graph, SIZE, &edges);
+    }
+}
+
+/// Save the bits of every edge as raw digits, like a game saving its graph to disk.
+fn save(graph: &SeqGraph) -> Vec<((u16, u16), Vec<Digit>)> {
+    graph
+        .edges
+        .iter()
+        .map(|(edge_id, bits)| (edge_id, bits.as_raw_digits().to_vec()))
+        .collect()
+}
+
+/// Load a graph saved with [save], reusing everything but the edges' bits of the original graph.
+fn load(graph: &SeqGraph, saved: Vec<((u16, u16), Vec<Digit>)>) -> SeqGraph {
+    let mut edges = HashMapStore::default();
+    for (edge_id, digits) in saved {
+        edges.insert(edge_id, BitVec::from_raw_digits(digits));
+    }
+
+    let mut loaded = graph.clone();
+    loaded.edges = Arc::new(edges);
+    loaded
+}
+
+#[test]
+fn paths_survive_raw_digits_round_trip() {
+    for multi_threaded in [false, true] {
+        let edges = maze(WIDTH, HEIGHT, 9);
+        let graph = build(SIZE, &edges, multi_threaded).into_seq();
+
+        let loaded = load(&graph, save(&graph));
+
+        assert_eq!(loaded.edges.len(), graph.edges.len());
+        for (edge_id, bits) in graph.edges.iter() {
+            assert!(
+                loaded.edges.get(edge_id).unwrap().eq(&bits),
+                "bits of {edge_id:?}"
+            );
+        }
+
+        assert_shortest_paths(&Graph::Sequential(loaded), SIZE, &edges);
+    }
+}
+
+#[test]
+fn rebuild_after_shrinking() {
+    for multi_threaded in [false, true] {
+        let edges = maze(WIDTH, HEIGHT, 4);
+        let graph = build(SIZE, &edges, multi_threaded);
+
+        // drop the bottom half of the maze
+        let half = SIZE / 2;
+        let mut builder = graph.into_builder();
+        builder.resize(half);
+        let graph = builder.build();
+
+        let edges = edges
+            .into_iter()
+            .filter(|&(a, b)| (a as usize) < half && (b as usize) < half)
+            .collect::<Vec<_>>();
+
+        assert_eq!(graph.nodes_len(), half);
+        assert_eq!(graph.edges_len(), edges.len());
+        assert_shortest_paths(&graph, half, &edges);
+    }
+}
+
+#[test]
+fn rebuild_after_shrinking_and_growing() {
+    for multi_threaded in [false, true] {
+        let edges = maze(WIDTH, HEIGHT, 5);
+        let graph = build(SIZE, &edges, multi_threaded);
+
+        // drop the bottom half, rebuild, then grow the maze back to its full size;
+        // a maze is a tree, so the passages kept in the top half stay shortest
+        let half = SIZE / 2;
+        let mut builder = graph.into_builder();
+        builder.resize(half);
+        let graph = builder.build();
+        assert_eq!(graph.nodes_len(), half);
+
+        let mut builder = graph.into_builder();
+        builder.resize(SIZE);
+        for &(a, b) in &edges {
+            if a as usize >= half || b as usize >= half {
+                builder.connect(a, b);
+            }
+        }
+        let graph = builder.build();
+
+        assert_eq!(graph.nodes_len(), SIZE);
+        assert_eq!(graph.edges_len(), edges.len());
+        assert_shortest_paths(&graph, SIZE, &edges);
+    }
+}
+
+#[test]
+fn retreat_retraces_path() {
+    let edges = maze(WIDTH, HEIGHT, 7);
+    let graph = build(SIZE, &edges, false);
+
+    let src = 0;
+    for dest in (1..SIZE as u16).step_by(11) {
+        let path = graph.path_to(src, dest).collect::<Vec<_>>();
+
+        // walk back from the destination to where we came from
+        let mut retreat = vec![dest];
+        while let Some(prev) = graph.prev_node(*retreat.last().unwrap(), src) {
+            retreat.push(prev);
+        }
+        retreat.reverse();
+
+        // a maze is a tree, so the way back is the same way
+        assert_eq!(retreat, path);
+    }
+}
+
+#[test]
+fn waypoints_follow_path() {
+    let edges = maze(WIDTH, HEIGHT, 8);
+    let graph = build(SIZE, &edges, false);
+
+    for stride in [1, 4, 7] {
+        for dest in (1..SIZE as u16).step_by(13) {
+            let path = graph.path_to(0, dest).collect::<Vec<_>>();
+            let waypoints = graph.waypoints_to(0, dest, stride).collect::<Vec<_>>();
+
+            // every stride-th node after the start, then the destination
+            let mut expected = path
+                .iter()
+                .copied()
+                .skip(stride)
+                .step_by(stride)
+                .collect::<Vec<_>>();
+            if expected.last() != Some(&dest) {
+                expected.push(dest);
+            }
+            assert_eq!(waypoints, expected);
+
+            // moving between waypoints locally walks the same path
+            let mut walked = vec![0];
+            for waypoint in waypoints {
+                walked.extend(graph.path_to(*walked.last().unwrap(), waypoint).skip(1));
+            }
+            assert_eq!(walked, path);
+        }
+    }
+}
@@ -99,7 +99,8 @@ fn compute_graph(mut commands: Commands, maze: Res<Maze>, grid: Res<GridDimensio
 
         let now = std::time::Instant::now();
 
-        let g = builder.build();
+        // yield between rounds, so the other tasks of the pool are not starved by the build
+        let g = builder.build_async(8).await;
 
         println!("graph built in {:?}", now.elapsed());
 